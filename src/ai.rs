@@ -0,0 +1,309 @@
+//! Computer opponent: it searches every placement of the current and held tetrominoes by
+//! playing them on a copy of its board, and keeps the one leaving the best looking stack
+
+use rand::Rng;
+use rand::seq::IndexedRandom;
+
+use crate::action::Action;
+use crate::placement;
+use crate::tetris::{FPS, Tetris};
+use crate::tetromino_type::TetrominoType;
+
+// Weights of the placement evaluation
+const HEIGHT_WEIGHT: f64 = -0.5; // Per cell of aggregate column height
+const DANGER_WEIGHT: f64 = -2.0; // Per row of the highest column above DANGER_HEIGHT
+const DANGER_HEIGHT: usize = 12;
+const HOLE_WEIGHT: f64 = -4.0; // Per empty cell with a block above it
+const BUMPINESS_WEIGHT: f64 = -0.3; // Per cell of height difference between neighbour columns
+const WELL_WEIGHT: f64 = 0.8; // Per cell of depth of the deepest well, up to a tetris
+const OTHER_WELLS_WEIGHT: f64 = -0.6; // Per cell of depth of the other wells
+const T_SLOT_WEIGHT: f64 = 3.0; // Per T-Spin double setup left on the stack
+const ATTACK_WEIGHT: f64 = 4.0; // Per line sent
+const BURN_WEIGHT: f64 = -1.5; // Per line cleared without attacking while the stack is low
+
+// Number of the best placements a mistake is picked among
+const MISTAKE_CHOICES: usize = 3;
+
+#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum)]
+pub enum Difficulty {
+    /// Slow, never holds nor spins and often misplaces pieces
+    Easy,
+    /// Holds, tucks and sets up T-Spins, with a few mistakes
+    Medium,
+    /// Fast and never makes a mistake
+    Hard,
+}
+
+impl Difficulty {
+    pub fn pieces_per_second(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.8,
+            Difficulty::Medium => 1.5,
+            Difficulty::Hard => 2.5,
+        }
+    }
+
+    fn mistake_probability(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.15,
+            Difficulty::Medium => 0.05,
+            Difficulty::Hard => 0.0,
+        }
+    }
+
+    /// Whether it considers holding and the placements needing a soft drop and a last rotation
+    fn is_advanced(&self) -> bool {
+        *self != Difficulty::Easy
+    }
+}
+
+/// Something playing a board on its own, the built-in bot or an external one
+pub trait Controller {
+    /// Shown above its board
+    fn name(&self) -> &str;
+
+    /// Play on `game`, to be called once per tick before the game itself ticks
+    fn on_tick(&mut self, game: &mut Tetris);
+
+    /// Forget everything about the board, to be called when it is replaced
+    fn reset(&mut self);
+}
+
+// Cells of the current tetromino, to notice when it did not move as planned
+type PieceState = [(i8, i8); 4];
+
+#[derive(Clone, Debug)]
+pub struct Bot {
+    difficulty: Difficulty,
+    pieces_per_second: f64,
+    piece: Option<usize>, // Locked pieces count of the board when the plan was made
+    plan: Vec<Action>,
+    planned_from: Option<PieceState>,
+    done: usize,  // Actions of the plan already applied
+    ticks: usize, // Ticks spent on the current tetromino
+    wait: usize,  // Ticks to wait before moving the current tetromino
+}
+
+impl Controller for Bot {
+    fn name(&self) -> &str {
+        "CPU"
+    }
+
+    /// It waits for the time left by the chosen speed, then applies one action per tick,
+    /// searching again if gravity moved the tetromino away from the plan.
+    fn on_tick(&mut self, game: &mut Tetris) {
+        if !game.is_falling() {
+            return;
+        }
+        if self.piece != Some(game.locked_pieces()) {
+            self.piece = Some(game.locked_pieces());
+            self.plan(game);
+            self.ticks = 0;
+            let ticks_per_piece = FPS as f64 / self.pieces_per_second;
+            self.wait = (ticks_per_piece - self.plan.len() as f64).max(0.0) as usize;
+        }
+        self.ticks += 1;
+        if self.ticks <= self.wait || self.done >= self.plan.len() {
+            return;
+        }
+
+        if self.planned_from.as_ref() != Some(&Self::piece_state(game)) {
+            self.plan(game);
+        }
+        let action = self.plan[self.done];
+        game.apply(action);
+        self.done += 1;
+        let state = Self::piece_state(game);
+        if self.planned_from.as_ref() == Some(&state) && action != Action::Hold {
+            // Blocked, the tetromino is not where the plan expected it
+            self.plan(game);
+        } else {
+            self.planned_from = Some(state);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.piece = None;
+        self.plan.clear();
+    }
+}
+
+impl Bot {
+    /// `pieces_per_second` defaults to the one of the difficulty
+    pub fn new(difficulty: Difficulty, pieces_per_second: Option<f64>) -> Self {
+        Self {
+            difficulty,
+            pieces_per_second: pieces_per_second
+                .unwrap_or(difficulty.pieces_per_second())
+                .max(0.1),
+            piece: None,
+            plan: Vec::new(),
+            planned_from: None,
+            done: 0,
+            ticks: 0,
+            wait: 0,
+        }
+    }
+
+    /// Speed given to the external bots, which come with the serde feature
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub fn pieces_per_second(&self) -> f64 {
+        self.pieces_per_second
+    }
+
+    fn plan(&mut self, game: &Tetris) {
+        self.plan = self.find_plan(game);
+        self.planned_from = Some(Self::piece_state(game));
+        self.done = 0;
+    }
+
+    fn piece_state(game: &Tetris) -> PieceState {
+        game.current().cells()
+    }
+
+    /// Actions leading to the chosen placement, ending with a hard drop
+    fn find_plan(&self, game: &Tetris) -> Vec<Action> {
+        let mut placements: Vec<(f64, Vec<Action>)> = self
+            .placements(game)
+            .into_iter()
+            .map(|plan| {
+                let mut board = game.clone();
+                for action in &plan {
+                    board.apply(*action);
+                }
+                (self.evaluate(game, &board), plan)
+            })
+            .collect();
+        placements.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut rng = rand::rng();
+        let placement = if rng.random_bool(self.difficulty.mistake_probability()) {
+            placements[..placements.len().min(MISTAKE_CHOICES)].choose(&mut rng)
+        } else {
+            placements.first()
+        };
+        placement
+            .map(|(_, plan)| plan.clone())
+            .unwrap_or(vec![Action::HardDrop])
+    }
+
+    /// Actions reaching every placement of the current and the held tetrominoes.
+    /// Without the advanced moves it neither holds nor soft drops, so no tucks nor spins.
+    fn placements(&self, game: &Tetris) -> Vec<Vec<Action>> {
+        let advanced = self.difficulty.is_advanced();
+        let mut starts = vec![(Vec::new(), game.clone())];
+        let mut held = game.clone();
+        held.apply(Action::Hold);
+        if advanced && held.hold() != game.hold() {
+            starts.push((vec![Action::Hold], held));
+        }
+        starts
+            .into_iter()
+            .flat_map(|(prefix, start)| {
+                placement::placements(&start)
+                    .into_iter()
+                    .filter(|placement| {
+                        advanced || !placement.actions().contains(&Action::SoftDrop)
+                    })
+                    .map(move |placement| [prefix.as_slice(), placement.actions()].concat())
+            })
+            .collect()
+    }
+
+    fn evaluate(&self, before: &Tetris, after: &Tetris) -> f64 {
+        if after.is_lost() {
+            return f64::NEG_INFINITY;
+        }
+        // Lines waiting for their clear animation are already gone
+        let board = after.board();
+        let (width, height) = (board.width(), board.height());
+        let rows: Vec<&[TetrominoType]> = board
+            .lines()
+            .filter(|row| row.contains(&TetrominoType::E))
+            .collect();
+        let missing_rows = height - rows.len();
+        let is_filled =
+            |y: usize, x: usize| y >= missing_rows && rows[y - missing_rows][x] != TetrominoType::E;
+        let is_empty = |y: usize, x: usize| y < height && !is_filled(y, x);
+
+        let heights: Vec<usize> = (0..width)
+            .map(|x| {
+                (0..height)
+                    .find(|y| is_filled(*y, x))
+                    .map(|y| height - y)
+                    .unwrap_or(0)
+            })
+            .collect();
+        let max_height = heights.iter().copied().max().unwrap_or(0);
+        let holes: usize = (0..width)
+            .map(|x| {
+                (height - heights[x]..height)
+                    .filter(|y| is_empty(*y, x))
+                    .count()
+            })
+            .sum();
+        let bumpiness: usize = heights.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+
+        let mut wells: Vec<usize> = (0..width)
+            .map(|x| {
+                let left = if x == 0 { usize::MAX } else { heights[x - 1] };
+                let right = heights.get(x + 1).copied().unwrap_or(usize::MAX);
+                left.min(right).saturating_sub(heights[x])
+            })
+            .collect();
+        wells.sort_unstable();
+        let deepest_well = wells.pop().unwrap_or(0);
+        let other_wells: usize = wells.iter().sum();
+
+        let t_slots = if self.difficulty.is_advanced() {
+            Self::t_slots(&heights, height, &is_filled)
+        } else {
+            0
+        };
+
+        let attack = after.total_attack() - before.total_attack();
+        let lines = after.cleared_lines() - before.cleared_lines();
+        let burnt = if attack == 0 && max_height < DANGER_HEIGHT {
+            lines
+        } else {
+            0
+        };
+
+        HEIGHT_WEIGHT * heights.iter().sum::<usize>() as f64
+            + DANGER_WEIGHT * max_height.saturating_sub(DANGER_HEIGHT) as f64
+            + HOLE_WEIGHT * holes as f64
+            + BUMPINESS_WEIGHT * bumpiness as f64
+            + WELL_WEIGHT * deepest_well.min(4) as f64
+            + OTHER_WELLS_WEIGHT * other_wells as f64
+            + T_SLOT_WEIGHT * t_slots as f64
+            + ATTACK_WEIGHT * attack as f64
+            + BURN_WEIGHT * burnt as f64
+    }
+
+    /// T-Spin double setups on top of the stack: a T shaped hole whose only way in
+    /// is a rotation under an overhang on one side
+    fn t_slots(
+        heights: &[usize],
+        height: usize,
+        is_filled: &impl Fn(usize, usize) -> bool,
+    ) -> usize {
+        (1..heights.len() - 1)
+            .filter(|x| {
+                let x = *x;
+                let Some(y) = height.checked_sub(heights[x] + 2) else {
+                    return false;
+                };
+                if y == 0 || y + 1 >= height {
+                    return false;
+                }
+                let stem = !is_filled(y + 1, x)
+                    && is_filled(y + 1, x - 1)
+                    && is_filled(y + 1, x + 1)
+                    && !is_filled(y, x - 1)
+                    && !is_filled(y, x + 1);
+                let overhang = is_filled(y - 1, x - 1) != is_filled(y - 1, x + 1);
+                stem && overhang
+            })
+            .count()
+    }
+}
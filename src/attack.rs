@@ -0,0 +1,84 @@
+//! Attack sent for a line clear, for consecutive clears and the bonuses, following the guideline tables
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Bonus lines depending on the combo, the first clear of a chain being combo 0
+const COMBO_TABLE: [usize; 13] = [0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const BACK_TO_BACK_BONUS: usize = 1;
+const PERFECT_CLEAR_BONUS: usize = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+/// What happened when a tetromino locked
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClearInfo {
+    pub lines: usize,
+    pub t_spin: TSpin,
+    pub perfect_clear: bool,
+    /// Consecutive line clears before this one, `None` if no line was cleared
+    pub combo: Option<usize>,
+    /// Difficult clear following another difficult clear
+    pub back_to_back: bool,
+}
+
+impl ClearInfo {
+    /// Tetrises and T-Spins clearing lines keep the back to back chain alive
+    pub fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.t_spin != TSpin::None && self.lines > 0)
+    }
+
+    pub fn attack(&self) -> usize {
+        let lines = match (self.t_spin, self.lines) {
+            (_, 0) => 0,
+            (TSpin::Full, lines) => 2 * lines,
+            (TSpin::Mini, lines) => lines - 1,
+            (TSpin::None, 4) => 4,
+            (TSpin::None, lines) => lines - 1,
+        };
+        let combo = self
+            .combo
+            .map(|combo| COMBO_TABLE[combo.min(COMBO_TABLE.len() - 1)])
+            .unwrap_or(0);
+        let back_to_back = if self.back_to_back {
+            BACK_TO_BACK_BONUS
+        } else {
+            0
+        };
+        let perfect_clear = if self.perfect_clear {
+            PERFECT_CLEAR_BONUS
+        } else {
+            0
+        };
+        lines + combo + back_to_back + perfect_clear
+    }
+
+    pub fn name(&self) -> String {
+        let lines = match self.lines {
+            0 => "",
+            1 => "Single",
+            2 => "Double",
+            3 => "Triple",
+            _ => "Tetris",
+        };
+        let mut name = match self.t_spin {
+            TSpin::None => lines.to_string(),
+            TSpin::Mini => format!("T-Spin Mini {lines}"),
+            TSpin::Full => format!("T-Spin {lines}"),
+        };
+        if self.back_to_back {
+            name = format!("B2B {name}");
+        }
+        if self.perfect_clear {
+            name = format!("Perfect Clear {name}");
+        }
+        name.trim().to_string()
+    }
+}
@@ -0,0 +1,262 @@
+//! The playfield kept twice: the shape of every cell to draw it, and the filled columns of
+//! every row as a bitmask, for the collision and line checks the bots and the solvers make
+//! millions of times. Its size is chosen when the game starts, the standard one being
+//! `MAP_WIDTH` by `TRUE_MAP_HEIGHT`.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::tetris::{HIDDEN_ROWS, MAP_WIDTH, Map, TRUE_MAP_HEIGHT};
+use crate::tetromino::Tetromino;
+use crate::tetromino_type::TetrominoType;
+
+/// Widest board, every column having its bit in the row masks
+pub const MAX_WIDTH: usize = u32::BITS as usize;
+
+/// Saved as its rows of cells only, the masks being rebuilt from them
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "Vec<Vec<TetrominoType>>", into = "Vec<Vec<TetrominoType>>")
+)]
+pub struct Board {
+    width: usize,
+    cells: Vec<TetrominoType>, // Row after row, from the top
+    rows: Vec<u32>,            // Bit `x` set when column `x` is filled
+}
+
+impl Board {
+    /// Empty board of `width` columns and `height` rows, the hidden ones included
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(
+            (4..=MAX_WIDTH).contains(&width) && height > HIDDEN_ROWS,
+            "Board too small or too wide"
+        );
+        Self {
+            width,
+            cells: vec![TetrominoType::E; width * height],
+            rows: vec![0; height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Rows of the board, the hidden ones included
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Rows shown to the player
+    pub fn visible_height(&self) -> usize {
+        self.height() - HIDDEN_ROWS
+    }
+
+    /// Cells of the board when it has the standard size
+    pub fn to_map(&self) -> Option<Map> {
+        if self.width != MAP_WIDTH || self.height() != TRUE_MAP_HEIGHT {
+            return None;
+        }
+        let mut map = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+        for (row, cells) in map.iter_mut().zip(self.lines()) {
+            row.copy_from_slice(cells);
+        }
+        Some(map)
+    }
+
+    /// Cells of every row, from the top
+    pub fn lines(&self) -> impl Iterator<Item = &[TetrominoType]> {
+        self.cells.chunks(self.width)
+    }
+
+    pub fn line(&self, row: usize) -> &[TetrominoType] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> TetrominoType {
+        self.cells[row * self.width + column]
+    }
+
+    pub fn set(&mut self, row: usize, column: usize, shape: TetrominoType) {
+        self.cells[row * self.width + column] = shape;
+        if shape == TetrominoType::E {
+            self.rows[row] &= !(1 << column);
+        } else {
+            self.rows[row] |= 1 << column;
+        }
+    }
+
+    /// Filled columns of `row`, bit `x` for column `x`
+    pub fn row(&self, row: usize) -> u32 {
+        self.rows[row]
+    }
+
+    pub fn set_line(&mut self, row: usize, cells: &[TetrominoType]) {
+        self.cells[row * self.width..(row + 1) * self.width].copy_from_slice(cells);
+        self.rows[row] = mask(cells);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+
+    /// Mask of a row without any empty cell
+    fn full_row(&self) -> u32 {
+        u32::MAX >> (MAX_WIDTH - self.width)
+    }
+
+    /// Whether `(row, column)` is outside the board or filled
+    pub fn is_blocked(&self, row: i8, column: i8) -> bool {
+        row < 0
+            || row as usize >= self.height()
+            || column < 0
+            || column as usize >= self.width
+            || self.rows[row as usize] & 1 << column != 0
+    }
+
+    /// Whether `tetromino` moved by `vector` stays inside the board without overlapping anything
+    pub fn fits(&self, tetromino: &Tetromino, vector: [i8; 2]) -> bool {
+        let (row, column) = tetromino.pos();
+        let (row, column) = (row + vector[0], column + vector[1]);
+        let full_row = self.full_row() as u64;
+        tetromino
+            .layout()
+            .masks()
+            .iter()
+            .zip(row..)
+            .filter(|(mask, _)| **mask != 0)
+            .all(|(mask, row)| {
+                if row < 0 || row as usize >= self.height() {
+                    return false;
+                }
+                // Shifted past the walls, the blocks leave the bits of the board
+                let mask = *mask as u64;
+                let shifted = if column < 0 {
+                    if mask & ((1 << -column) - 1) != 0 {
+                        return false;
+                    }
+                    mask >> -column
+                } else {
+                    mask << column
+                };
+                shifted & !full_row == 0 && shifted & self.rows[row as usize] as u64 == 0
+            })
+    }
+
+    /// Fill the cells of `tetromino` with its shape
+    pub fn lock(&mut self, tetromino: &Tetromino) {
+        for (row, column) in tetromino.cells() {
+            self.set(row as usize, column as usize, tetromino.shape());
+        }
+    }
+
+    /// Indexes of the rows without any empty cell
+    pub fn full_lines(&self) -> Vec<usize> {
+        (0..self.height())
+            .filter(|row| self.rows[*row] == self.full_row())
+            .collect()
+    }
+
+    /// Move every row up by `lines`, the bottom ones staying as they were until replaced
+    pub fn push_up(&mut self, lines: usize) {
+        self.cells.copy_within(lines * self.width.., 0);
+        self.rows.copy_within(lines.., 0);
+    }
+
+    /// Remove the `lines` rows and move everything above them down. Returns the row each row
+    /// came from, the empty rows appearing at the top keeping their own.
+    pub fn collapse(&mut self, lines: &[usize]) -> Vec<usize> {
+        let mut sources: Vec<usize> = (0..self.height()).collect();
+        let mut target = self.height();
+        for row in (0..self.height()).rev() {
+            if lines.contains(&row) {
+                continue;
+            }
+            target -= 1;
+            self.cells.copy_within(
+                row * self.width..(row + 1) * self.width,
+                target * self.width,
+            );
+            self.rows[target] = self.rows[row];
+            sources[target] = row;
+        }
+        self.cells[..target * self.width].fill(TetrominoType::E);
+        self.rows[..target].fill(0);
+        sources
+    }
+}
+
+/// The standard board, empty
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(MAP_WIDTH, TRUE_MAP_HEIGHT)
+    }
+}
+
+impl From<Map> for Board {
+    fn from(map: Map) -> Self {
+        let mut board = Self::default();
+        for (row, cells) in map.iter().enumerate() {
+            board.set_line(row, cells);
+        }
+        board
+    }
+}
+
+impl TryFrom<Vec<Vec<TetrominoType>>> for Board {
+    type Error = String;
+
+    fn try_from(lines: Vec<Vec<TetrominoType>>) -> Result<Self, Self::Error> {
+        let width = lines.first().map_or(0, Vec::len);
+        if !(4..=MAX_WIDTH).contains(&width) || lines.len() <= HIDDEN_ROWS {
+            return Err(format!("{width} by {} is not a board size", lines.len()));
+        }
+        if lines.iter().any(|line| line.len() != width) {
+            return Err("the rows of the board are not all as wide".to_string());
+        }
+        let mut board = Self::new(width, lines.len());
+        for (row, cells) in lines.iter().enumerate() {
+            board.set_line(row, cells);
+        }
+        Ok(board)
+    }
+}
+
+impl From<Board> for Vec<Vec<TetrominoType>> {
+    fn from(board: Board) -> Self {
+        board.lines().map(<[TetrominoType]>::to_vec).collect()
+    }
+}
+
+fn mask(cells: &[TetrominoType]) -> u32 {
+    cells
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| **cell != TetrominoType::E)
+        .fold(0, |mask, (column, _)| mask | 1 << column)
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_round_trip() {
+        let mut board = Board::new(12, 30);
+        board.set(29, 0, TetrominoType::G);
+        board.set(29, 11, TetrominoType::T);
+        board.set(28, 5, TetrominoType::I);
+        let json = serde_json::to_string(&board).unwrap();
+        let copy: Board = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy, board);
+        assert_eq!(copy.row(29), board.row(29));
+    }
+
+    #[test]
+    fn serde_refuses_ragged_rows() {
+        let json = r#"[["E","E","E","E"],["E","E","E"],["E","E","E","E"],["E","E","E","E"],["E","E","E","E"]]"#;
+        assert!(serde_json::from_str::<Board>(json).is_err());
+    }
+}
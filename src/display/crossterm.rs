@@ -1,46 +1,362 @@
 use std::error::Error;
 use std::io;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
-use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, KeyCode};
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture, KeyCode};
 use crossterm::execute;
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
 use ratatui::Terminal;
 use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Position, Rect};
 
-use crate::display::{game_ui, menu_ui};
+use crate::ai::{Bot, Controller};
+use crate::display::input::{Input, Press, TerminalInput};
+use crate::display::keymap::{self, KeyMap};
+use crate::display::{editor_ui, game_ui, menu_ui, spectate_ui, versus_ui};
+use crate::editor::{Editor, Prompt};
+use crate::game_mode::{GameMode, GameSettings};
+use crate::history::History;
 use crate::menu::{Menu, Options};
-use crate::tetris::Tetris;
+use crate::net::connection::Connection;
+use crate::net::lockstep::Lockstep;
+use crate::net::spectate::{Broadcast, Spectator, Spectators};
+use crate::pc::Hints;
+use crate::puzzle::{self, Progress};
+#[cfg(feature = "serde")]
+use crate::save;
+#[cfg(feature = "serde")]
+use crate::state::State;
+#[cfg(feature = "serde")]
+use crate::tbp::ExternalBot;
+use crate::tetris::{MAP_HEIGHT, MAP_WIDTH, Tetris};
+use crate::tetromino_type::TetrominoType;
+use crate::versus::{PLAYERS, Versus};
 
-pub fn run(tick_rate: Duration) -> Result<(), Box<dyn Error>> {
-    // setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+/// How often a spectator looks for a newer broadcast
+const SPECTATE_REFRESH: Duration = Duration::from_millis(16);
+
+/// What the player chose in the menu
+enum Choice {
+    Play(GameMode),
+    /// The saved game
+    Continue,
+    Editor,
+}
+
+/// What the command line chose, shared by every game started from the menu
+#[derive(Clone)]
+pub struct Config {
+    pub tick_rate: Duration,
+    pub settings: GameSettings,
+    pub best_of: usize,
+    pub bot: Bot,
+    /// Command starting a Tetris Bot Protocol bot, played instead of `bot`
+    pub external_bot: Option<String>,
+    /// The computer plays the solo games instead of the player
+    pub demo: bool,
+    /// Perfect clear placements are suggested in the solo games
+    pub pc_hints: bool,
+    /// Directory of the puzzle files and of the stars earned
+    pub puzzles: PathBuf,
+    /// The editor may save and load setup files, not on a server where they would be its own
+    pub files: bool,
+    /// Where the solo game in progress is saved, `None` on a server
+    #[cfg(feature = "serde")]
+    pub save: Option<PathBuf>,
+    /// Where the games are streamed, if anyone may watch
+    pub spectators: Option<Spectators>,
+}
+
+impl Config {
+    /// A new computer player, external bots being started for every game. The protocol of
+    /// the external bots only knows the standard board.
+    fn computer(&self) -> Result<Box<dyn Controller>, Box<dyn Error>> {
+        let is_standard = self.settings.width.is_none_or(|width| width == MAP_WIDTH)
+            && self
+                .settings
+                .height
+                .is_none_or(|height| height == MAP_HEIGHT);
+        if self.external_bot.is_some() && !is_standard {
+            return Err("external bots only play on a 10 by 20 board".into());
+        }
+        Ok(match &self.external_bot {
+            #[cfg(feature = "serde")]
+            Some(command) => Box::new(ExternalBot::launch(command, self.bot.pieces_per_second())?),
+            // The bot protocol is JSON
+            #[cfg(not(feature = "serde"))]
+            Some(_) => return Err("external bots need the serde feature".into()),
+            None => Box::new(self.bot.clone()),
+        })
+    }
+}
 
-    // create game and run it
+pub fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut terminal = setup_terminal()?;
+    let result = run_menus(&mut terminal, &mut TerminalInput, config);
+    restore_terminal(&mut terminal)?;
+    result
+}
+
+/// Menu then the chosen games, until the player quits
+pub fn run_menus<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let spectators = config.spectators.as_ref();
     loop {
-        match run_menu(&mut terminal) {
-            Ok(true) => break,
-            Err(e) => {
-                eprintln!("{e:?}");
+        publish(spectators, || Broadcast::Idle);
+        // A save that cannot be read is not offered
+        #[cfg(feature = "serde")]
+        let saved = config
+            .save
+            .as_deref()
+            .and_then(|path| save::load(path).ok());
+        #[cfg(not(feature = "serde"))]
+        let saved: Option<Tetris> = None;
+        let mode = match run_menu(terminal, input, saved.is_some())? {
+            Some(Choice::Play(mode)) => mode,
+            Some(Choice::Continue) => {
+                let Some(mut game) = saved else {
+                    continue;
+                };
+                let quit = run_game(terminal, input, &mut game, config)?;
+                if game.puzzle().is_some() {
+                    record_stars(&mut Progress::load(&config.puzzles), &game)?;
+                }
+                if quit {
+                    break;
+                }
+                continue;
             }
-            _ => {}
+            Some(Choice::Editor) => {
+                run_editor(terminal, input, config)?;
+                continue;
+            }
+            None => break,
+        };
+        let quit = if mode == GameMode::Versus || mode == GameMode::VersusCpu {
+            let bot = match mode {
+                GameMode::VersusCpu => Some(config.computer()?),
+                _ => None,
+            };
+            run_versus(
+                terminal,
+                input,
+                Versus::new(config.best_of, config.settings),
+                config.tick_rate,
+                bot,
+                spectators,
+            )?
+        } else if mode == GameMode::Puzzle {
+            run_puzzles(terminal, input, config)?
+        } else {
+            play_solo(terminal, input, &mut Tetris::new(mode), config)?
         };
-        match run_game(&mut terminal, Tetris::new(), tick_rate) {
-            Ok(true) => break,
-            Err(e) => {
-                eprintln!("{e:?}");
+        if quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Play a solo game with what the command line chose, returns whether the player quit
+fn play_solo<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    game: &mut Tetris,
+    config: &Config,
+) -> Result<bool, Box<dyn Error>> {
+    game.apply_settings(config.settings);
+    run_game(terminal, input, game, config)
+}
+
+/// Keep the stars earned if the game solved its puzzle
+fn record_stars(progress: &mut Progress, game: &Tetris) -> Result<(), Box<dyn Error>> {
+    match game.puzzle() {
+        Some(puzzle) if game.is_over() && !game.is_lost() => {
+            progress.record(puzzle, puzzle.stars_for(game.locked_pieces()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Pick puzzles and play them until the player goes back to the menu, saving the stars
+/// earned. Returns whether the player quit.
+fn run_puzzles<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    config: &Config,
+) -> Result<bool, Box<dyn Error>> {
+    let (puzzles, error) = match puzzle::load(&config.puzzles) {
+        Ok(puzzles) => (puzzles, None),
+        Err(error) => (Vec::new(), Some(error.to_string())),
+    };
+    let mut progress = Progress::load(&config.puzzles);
+    let mut hovered = 0;
+    loop {
+        publish(config.spectators.as_ref(), || Broadcast::Idle);
+        terminal.draw(|frame| {
+            menu_ui::draw_puzzles(frame, &puzzles, &progress, hovered, error.as_deref())
+        })?;
+        match input.wait_key()? {
+            KeyCode::Char('q') => return Ok(true),
+            KeyCode::Esc | KeyCode::Left => return Ok(false),
+            KeyCode::Up | KeyCode::Char('w') => hovered = hovered.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('s') => {
+                hovered = (hovered + 1).min(puzzles.len().saturating_sub(1))
+            }
+            KeyCode::Enter => {
+                let Some(puzzle) = puzzles.get(hovered) else {
+                    continue;
+                };
+                let mut game = Tetris::with_puzzle(puzzle.clone());
+                let quit = play_solo(terminal, input, &mut game, config)?;
+                record_stars(&mut progress, &game)?;
+                if quit {
+                    return Ok(true);
+                }
             }
             _ => {}
+        }
+    }
+}
+
+/// Edit a board and play from it until the player goes back to the menu
+fn run_editor<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    // The games played from the board are not continued from the menu
+    let config = &Config {
+        #[cfg(feature = "serde")]
+        save: None,
+        ..config.clone()
+    };
+    let mut editor = Editor::new(config.files);
+    loop {
+        publish(config.spectators.as_ref(), || Broadcast::Idle);
+        let mut playfield = Rect::default();
+        terminal.draw(|frame| playfield = editor_ui::draw(frame, &editor))?;
+        let key = match input.wait_press()? {
+            Press::Key(key) => key,
+            Press::Click { column, row, right } => {
+                if playfield.contains(Position::new(column, row)) {
+                    editor.paint_at(
+                        (row - playfield.y) as usize,
+                        ((column - playfield.x) / 2) as usize,
+                        right,
+                    );
+                }
+                continue;
+            }
         };
+        if editor.prompt().is_some() {
+            match key {
+                KeyCode::Enter => editor.submit_prompt(),
+                KeyCode::Esc => editor.cancel_prompt(),
+                KeyCode::Backspace => editor.erase_char(),
+                KeyCode::Char(c) => editor.type_char(c),
+                _ => {}
+            }
+            continue;
+        }
+        match key {
+            KeyCode::Esc => return Ok(()),
+            KeyCode::Up => editor.move_cursor(-1, 0),
+            KeyCode::Down => editor.move_cursor(1, 0),
+            KeyCode::Left => editor.move_cursor(0, -1),
+            KeyCode::Right => editor.move_cursor(0, 1),
+            KeyCode::Char(' ') | KeyCode::Enter => editor.paint(false),
+            KeyCode::Char('x') | KeyCode::Backspace | KeyCode::Delete => editor.paint(true),
+            KeyCode::Char('g') => editor.set_brush(TetrominoType::G),
+            KeyCode::Char('n') => editor.start_prompt(Prompt::Queue),
+            KeyCode::Char('h') => editor.start_prompt(Prompt::Hold),
+            KeyCode::Char('w') => editor.start_prompt(Prompt::Save),
+            KeyCode::Char('r') => editor.start_prompt(Prompt::Load),
+            KeyCode::Char('f') => editor.export_fumen(),
+            KeyCode::Char('c') => editor.clear(),
+            // Esc only ends the game, to come back to the board
+            KeyCode::Char('p') => {
+                let mut game = Tetris::with_setup(GameMode::Endless, &editor.setup());
+                play_solo(terminal, input, &mut game, config)?;
+            }
+            KeyCode::Char(letter) if TetrominoType::from_letter(letter) != TetrominoType::E => {
+                editor.set_brush(TetrominoType::from_letter(letter))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Play an online match set up by `net::host` or `net::join`, without going through the menu
+pub fn run_online(
+    tick_rate: Duration,
+    connection: Connection,
+    lockstep: Lockstep,
+    spectators: Option<Spectators>,
+) -> Result<(), Box<dyn Error>> {
+    let mut terminal = setup_terminal()?;
+    let result = run_lockstep(
+        &mut terminal,
+        &mut TerminalInput,
+        connection,
+        lockstep,
+        tick_rate,
+        spectators.as_ref(),
+    );
+    restore_terminal(&mut terminal)?;
+    result
+}
+
+/// Watch the games of another terminal until the spectator quits
+pub fn run_spectator(spectator: Spectator) -> Result<(), Box<dyn Error>> {
+    let mut terminal = setup_terminal()?;
+    let result = watch(&mut terminal, &mut TerminalInput, spectator);
+    restore_terminal(&mut terminal)?;
+    result
+}
+
+fn watch<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    mut spectator: Spectator,
+) -> Result<(), Box<dyn Error>> {
+    let mut broadcast = None;
+    loop {
+        if let Some(latest) = spectator.latest() {
+            broadcast = Some(latest);
+        }
+        terminal
+            .draw(|frame| spectate_ui::draw(frame, broadcast.as_ref(), spectator.is_connected()))?;
+        if let Some(KeyCode::Esc | KeyCode::Char('q')) = input.next_key(SPECTATE_REFRESH)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Send the game to the spectators, `broadcast` is only built if someone may watch
+fn publish(spectators: Option<&Spectators>, broadcast: impl FnOnce() -> Broadcast) {
+    if let Some(spectators) = spectators {
+        spectators.publish(&broadcast());
     }
+}
 
-    // restore terminal
+fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>, Box<dyn Error>> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    Ok(Terminal::new(backend)?)
+}
+
+fn restore_terminal(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+) -> Result<(), Box<dyn Error>> {
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -48,70 +364,305 @@ pub fn run(tick_rate: Duration) -> Result<(), Box<dyn Error>> {
         DisableMouseCapture
     )?;
     terminal.show_cursor()?;
-
     Ok(())
 }
 
+/// Play a solo game with the settings it already has, as a saved one, returns whether the
+/// player quit
 fn run_game<B: Backend>(
     terminal: &mut Terminal<B>,
-    mut game: Tetris,
-    tick_rate: Duration,
+    input: &mut impl Input,
+    game: &mut Tetris,
+    config: &Config,
 ) -> Result<bool, Box<dyn Error>> {
+    let (tick_rate, spectators) = (config.tick_rate, config.spectators.as_ref());
+    let mut bot = match config.demo {
+        true => Some(config.computer()?),
+        false => None,
+    };
+    // Only the player's games are continued from the menu
+    #[cfg(feature = "serde")]
+    let save = config.save.as_deref().filter(|_| bot.is_none());
+    let mut hints = config.pc_hints.then(Hints::new);
     let mut last_tick = Instant::now();
+    #[cfg(feature = "serde")]
+    let mut saved_pieces = None;
+    // Watching the computer play, nothing can be undone
+    let mut history = (bot.is_none() && game.mode().can_undo()).then(|| History::new(game));
 
     loop {
-        terminal.draw(|frame| game_ui::draw(frame, &game))?;
+        if let Some(hints) = hints.as_mut() {
+            hints.update(game);
+        }
+        if let Some(history) = history.as_mut() {
+            history.update(game);
+        }
+        terminal.draw(|frame| game_ui::draw(frame, game))?;
+        publish(spectators, || Broadcast::solo(game));
+        // A save that cannot be written only loses the way back to the game, not the game
+        #[cfg(feature = "serde")]
+        if let Some(path) = save {
+            if game.is_over() {
+                let _ = save::remove(path);
+            } else if saved_pieces != Some(game.locked_pieces()) {
+                let _ = save::save(path, game);
+                saved_pieces = Some(game.locked_pieces());
+            }
+        }
 
-        if game.is_lost() {
-            return Ok(false);
+        if game.is_over() {
+            let Some(history) = history.as_mut() else {
+                return wait_end_screen(input);
+            };
+            match input.wait_key()? {
+                KeyCode::Enter | KeyCode::Esc => return Ok(false),
+                keymap::UNDO if history.undo(game) => {
+                    if let Some(hints) = hints.as_mut() {
+                        hints.reset();
+                    }
+                    last_tick = Instant::now();
+                }
+                _ => {}
+            }
+            continue;
         }
 
         let timeout = tick_rate.saturating_sub(last_tick.elapsed());
-        if !event::poll(timeout)? {
+        let Some(key) = input.next_key(timeout)? else {
+            if let Some(bot) = bot.as_mut() {
+                bot.on_tick(game);
+            }
             game.on_tick();
             last_tick = Instant::now();
             continue;
+        };
+        if key == KeyCode::Esc {
+            #[cfg(feature = "serde")]
+            if let Some(path) = save {
+                let _ = save::save(path, game);
+            }
+            return Ok(true);
+        }
+        #[cfg(feature = "serde")]
+        if key == keymap::DUMP || key == keymap::DUMP_BINARY {
+            if config.files {
+                State::dump(game, key == keymap::DUMP_BINARY)?;
+            }
+            continue;
+        }
+        if key == keymap::RESTART && (game.mode().opener().is_some() || game.puzzle().is_some()) {
+            game.restart();
+            if let Some(bot) = bot.as_mut() {
+                bot.reset();
+            }
+            if let Some(hints) = hints.as_mut() {
+                hints.reset();
+            }
+            if let Some(history) = history.as_mut() {
+                history.reset(game);
+            }
+            continue;
+        }
+        if let Some(history) = history.as_mut()
+            && (key == keymap::UNDO || key == keymap::REDO)
+        {
+            let is_moved = match key {
+                keymap::UNDO => history.undo(game),
+                _ => history.redo(game),
+            };
+            if is_moved && let Some(hints) = hints.as_mut() {
+                hints.reset();
+            }
+            continue;
+        }
+        // Watching the computer play, the keys only quit
+        if bot.is_none()
+            && let Some(action) = keymap::action(&keymap::SOLO, key)
+        {
+            game.apply(action);
         }
-        if let Some(key) = event::read()?.as_key_press_event() {
-            match key.code {
+    }
+}
+
+fn run_versus<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    mut versus: Versus,
+    tick_rate: Duration,
+    mut bot: Option<Box<dyn Controller>>,
+    spectators: Option<&Spectators>,
+) -> Result<bool, Box<dyn Error>> {
+    let mut last_tick = Instant::now();
+    // The computer plays the second board
+    let bot_name = bot.as_ref().map(|bot| bot.name().to_string());
+    let (key_maps, names): (&[&KeyMap], _) = match &bot_name {
+        Some(bot_name) => (&[&keymap::SOLO], ["Player", bot_name.as_str()]),
+        None => (
+            &[&keymap::PLAYER_ONE, &keymap::PLAYER_TWO],
+            ["Player 1", "Player 2"],
+        ),
+    };
+
+    loop {
+        terminal.draw(|frame| versus_ui::draw(frame, &versus, names))?;
+        publish(spectators, || Broadcast::versus(&versus, names));
+
+        if versus.round_winner().is_some() {
+            if versus.is_over() {
+                return wait_end_screen(input);
+            }
+            match input.wait_key()? {
                 KeyCode::Esc => return Ok(true),
+                KeyCode::Enter => {
+                    versus.next_round();
+                    if let Some(bot) = bot.as_mut() {
+                        bot.reset();
+                    }
+                    last_tick = Instant::now();
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        let Some(key) = input.next_key(timeout)? else {
+            if let Some(bot) = bot.as_mut() {
+                bot.on_tick(versus.player_mut(PLAYERS - 1));
+            }
+            versus.on_tick();
+            last_tick = Instant::now();
+            continue;
+        };
+        if key == KeyCode::Esc {
+            return Ok(true);
+        }
+        for (id, key_map) in key_maps.iter().enumerate() {
+            if let Some(action) = keymap::action(key_map, key) {
+                versus.player_mut(id).apply(action);
+            }
+        }
+    }
+}
 
-                KeyCode::Char('q') => game.rotate_counter_clockwise(),
-                KeyCode::Char('e') => game.rotate_clockwise(),
+fn run_lockstep<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    mut connection: Connection,
+    mut lockstep: Lockstep,
+    tick_rate: Duration,
+    spectators: Option<&Spectators>,
+) -> Result<(), Box<dyn Error>> {
+    let mut last_tick = Instant::now();
+    let names = if lockstep.local() == 0 {
+        ["Player 1 (you)", "Player 2"]
+    } else {
+        ["Player 1", "Player 2 (you)"]
+    };
 
-                KeyCode::Char('a') => game.r#move([0, -1]),
-                KeyCode::Char('s') => game.r#move([1, 0]),
-                KeyCode::Char('d') => game.r#move([0, 1]),
+    loop {
+        let status = online_status(&connection, &lockstep);
+        terminal.draw(|frame| {
+            versus_ui::draw(frame, lockstep.versus(), names);
+            if let Some(status) = &status {
+                versus_ui::draw_status(frame, status);
+            }
+        })?;
+        publish(spectators, || {
+            Broadcast::versus(lockstep.versus(), ["Player 1", "Player 2"])
+        });
 
-                KeyCode::Char('h') => game.hold_current(),
+        if lockstep.versus().is_over() || lockstep.desync().is_some() || lockstep.peer_left() {
+            wait_end_screen(input)?;
+            return Ok(());
+        }
+        if !connection.is_connected() && connection.reconnect()? {
+            lockstep.resume(&mut connection);
+        }
 
-                _ => {}
+        let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        match input.next_key(timeout)? {
+            None => {
+                lockstep.on_tick(&mut connection);
+                connection.keep_alive();
+                last_tick = Instant::now();
+            }
+            Some(KeyCode::Esc) => {
+                lockstep.quit(&mut connection);
+                return Ok(());
             }
+            Some(KeyCode::Enter) => lockstep.ready(&mut connection),
+            Some(key) => {
+                if let Some(action) = keymap::action(&keymap::SOLO, key) {
+                    lockstep.push_action(action);
+                }
+            }
+        }
+    }
+}
+
+fn online_status(connection: &Connection, lockstep: &Lockstep) -> Option<String> {
+    if let Some((round, tick)) = lockstep.desync() {
+        return Some(format!(
+            "The games went out of sync (round {}, tick {tick}), Enter to quit",
+            round + 1
+        ));
+    }
+    if lockstep.peer_left() {
+        return Some("The other player left, Enter to quit".to_string());
+    }
+    if let Some(time_left) = connection.reconnect_time_left() {
+        return Some(format!(
+            "Connection lost, reconnecting... ({}s)",
+            time_left.as_secs()
+        ));
+    }
+    lockstep
+        .is_waiting()
+        .then(|| "Waiting for the other player...".to_string())
+}
+
+/// Keep the end screen displayed until the player goes back to the menu
+pub fn wait_end_screen(input: &mut impl Input) -> Result<bool, Box<dyn Error>> {
+    loop {
+        match input.wait_key()? {
+            KeyCode::Enter | KeyCode::Esc => return Ok(false),
+            _ => {}
         }
     }
 }
 
-fn run_menu<B: Backend>(terminal: &mut Terminal<B>) -> Result<bool, Box<dyn Error>> {
-    let mut menu = Menu::new();
+/// Returns what to play, `None` if the player wants to quit
+fn run_menu<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    can_continue: bool,
+) -> Result<Option<Choice>, Box<dyn Error>> {
+    let mut menu = Menu::new(can_continue);
 
     loop {
         terminal.draw(|frame| menu_ui::draw(frame, &menu))?;
 
-        match menu.selected() {
-            Options::Quit => return Ok(true),
-            Options::New => return Ok(false),
-            _ => { /* Don't care */ }
-        };
+        if menu.selected() == Options::Quit {
+            return Ok(None);
+        }
+        if menu.selected() == Options::Continue {
+            return Ok(Some(Choice::Continue));
+        }
+        if menu.selected() == Options::Editor {
+            return Ok(Some(Choice::Editor));
+        }
+        if let Some(mode) = menu.mode() {
+            return Ok(Some(Choice::Play(mode)));
+        }
 
-        if let Some(key) = event::read()?.as_key_press_event() {
-            match key.code {
-                KeyCode::Char('q') => return Ok(true),
-                KeyCode::Esc | KeyCode::Left => menu.back(),
-                KeyCode::Up | KeyCode::Char('w') => menu.move_up(),
-                KeyCode::Down | KeyCode::Char('s') => menu.move_down(),
-                KeyCode::Enter => menu.select(),
-                _ => {}
-            }
+        match input.wait_key()? {
+            KeyCode::Char('q') => return Ok(None),
+            KeyCode::Esc | KeyCode::Left => menu.back(),
+            KeyCode::Up | KeyCode::Char('w') => menu.move_up(),
+            KeyCode::Down | KeyCode::Char('s') => menu.move_down(),
+            KeyCode::Enter => menu.select(),
+            _ => {}
         }
     }
 }
@@ -0,0 +1,92 @@
+use crate::display::game_ui;
+use crate::display::utils::center::center;
+use crate::editor::Editor;
+use crate::tetromino_type::TetrominoType;
+use ratatui::layout::{Alignment, Constraint, Layout, Rect};
+use ratatui::style::{Color, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, BorderType, Borders, Padding, Paragraph, Wrap};
+use ratatui::{Frame, border};
+
+const HELP: [&str; 9] = [
+    "Arrows move, click to paint",
+    "Space paint, X or right click erase",
+    "IOTSZJL G: brush",
+    "N queue, H hold",
+    "C clear the board",
+    "P play from here",
+    "W save, R load",
+    "F show the fumen",
+    "Esc back to the menu",
+];
+
+/// Board being edited with the cursor, the brush and the commands, returns the area of the
+/// visible field, each cell being two columns wide
+pub fn draw(frame: &mut Frame, editor: &Editor) -> Rect {
+    let chunks = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Length((10 * 2) + 2),
+        Constraint::Fill(1),
+    ])
+    .split(frame.area());
+    let preview = editor.preview();
+    draw_left(frame, editor, chunks[0]);
+    game_ui::draw_game(frame, &preview, chunks[1], "Editor");
+    game_ui::draw_right(frame, &preview, chunks[2]);
+
+    let playfield = center(chunks[1], Constraint::Length(20), Constraint::Length(20));
+    let (row, column) = editor.cursor();
+    let cursor = Rect::new(
+        playfield.x + column as u16 * 2,
+        playfield.y + row as u16,
+        2,
+        1,
+    )
+    .intersection(playfield);
+    let color = match editor.brush() {
+        TetrominoType::G => Color::White,
+        brush => brush.color(),
+    };
+    frame.render_widget(Span::raw("[]").fg(color).bold(), cursor);
+    playfield
+}
+
+fn draw_left(frame: &mut Frame, editor: &Editor, area: Rect) {
+    let area = center(area, Constraint::Fill(1), Constraint::Fill(1));
+    let block = Block::bordered()
+        .title_alignment(Alignment::Center)
+        .borders(border!(TOP, BOTTOM, LEFT))
+        .border_type(BorderType::Rounded)
+        .title("Setup");
+
+    let setup = editor.setup();
+    let brush = editor.brush();
+    let mut lines = vec![
+        Line::from(vec![
+            Span::raw("Brush: "),
+            Span::raw("  ").bg(brush.color()),
+            Span::raw(format!(" {}", brush.letter())),
+        ]),
+        Line::from(format!("Hold: {}", setup.hold.unwrap_or('-'))),
+        Line::from(format!("Queue: {}", setup.queue)),
+        Line::from(""),
+    ];
+    lines.extend(HELP.iter().map(|help| Line::from(*help).dark_gray()));
+    lines.push(Line::from(""));
+    if let Some((prompt, text)) = editor.prompt() {
+        lines.push(Line::from(format!("{}:", prompt.as_str())).bold());
+        lines.push(Line::from(format!("{text}_")));
+        lines.push(Line::from("Enter to confirm, Esc to cancel").dark_gray());
+    }
+    match editor.message() {
+        Some(Ok(message)) => lines.push(Line::from(message.as_str()).green()),
+        Some(Err(error)) => lines.push(Line::from(error.as_str()).red()),
+        None => {}
+    }
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(block.padding(Padding::horizontal(1))),
+        area,
+    );
+}
@@ -1,25 +1,106 @@
-use crate::display::utils::center::center;
+use crate::display::utils::center::{center, center_horizontal, center_vertical};
+use crate::display::utils::stars::format_stars;
+use crate::display::utils::time::format_time;
+use crate::finesse::Judgement;
+use crate::game_mode::GameMode;
+use crate::master::SECTION_LENGTH;
+use crate::puzzle::MAX_STARS;
 use crate::tetris::Tetris;
 use crate::tetromino_type::TetrominoType;
 use ratatui::layout::{Alignment, Constraint, Flex, Layout, Rect};
 use ratatui::style::{Color, Stylize};
-use ratatui::text::{Line, Text};
-use ratatui::widgets::{Block, BorderType, Borders, Paragraph};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, BorderType, Borders, Clear, Paragraph};
 use ratatui::{Frame, border, symbols};
 
 pub fn draw(frame: &mut Frame, game: &Tetris) {
+    let board = draw_board(frame, game, frame.area(), "Tetrust");
+
+    if game.is_over() {
+        draw_end(frame, game, board);
+    }
+}
+
+/// Hold and stats, the playfield and the next tetrominoes, returns the area of the playfield
+pub fn draw_board(frame: &mut Frame, game: &Tetris, area: Rect, title: &str) -> Rect {
     let chunks = Layout::horizontal([
         Constraint::Fill(1),
-        Constraint::Length((10 * 2) + 2),
+        Constraint::Length(game.board().width() as u16 * 2 + 2),
         Constraint::Fill(1),
     ])
-    .split(frame.area());
+    .split(area);
     draw_left(frame, game, chunks[0]);
-    draw_game(frame, game, chunks[1]);
+    draw_garbage_meter(frame, game, chunks[0], chunks[1]);
+    draw_game(frame, game, chunks[1], title);
     draw_right(frame, game, chunks[2]);
+    chunks[1]
+}
+
+/// Red bar against the left side of the playfield, as high as the garbage waiting to rise
+fn draw_garbage_meter(frame: &mut Frame, game: &Tetris, left_area: Rect, board_area: Rect) {
+    let pending = game.pending_garbage();
+    if pending == 0 {
+        return;
+    }
+    let playfield = playfield(game, board_area);
+    let meter = Rect::new(
+        left_area.right().saturating_sub(2),
+        playfield.y,
+        2.min(left_area.width),
+        playfield.height,
+    );
+    let height = meter.height as usize;
+    let bar: Vec<Line> = (0..height)
+        .map(|y| {
+            if y + pending.min(height) >= height {
+                Line::from(Span::raw("  ").bg(Color::Red))
+            } else {
+                Line::from("")
+            }
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(bar), meter);
+}
+
+fn draw_end(frame: &mut Frame, game: &Tetris, area: Rect) {
+    let can_undo = game.mode().can_undo();
+    let popup = center_vertical(center_horizontal(area, 20), 8 + can_undo as u16);
+    let (title, color) = if game.is_lost() {
+        ("Game Over", Color::Red)
+    } else {
+        ("Victory!", Color::Green)
+    };
+    let reason = match (game.game_over_reason(), game.puzzle()) {
+        (Some(reason), _) => reason.as_str().to_string(),
+        (None, Some(puzzle)) => format_stars(puzzle.stars_for(game.locked_pieces())),
+        (None, None) => String::new(),
+    };
+
+    let mut lines = vec![
+        Line::from(title).fg(color).bold(),
+        Line::from(reason).fg(color),
+        Line::from(format!("Score: {}", game.score())),
+        Line::from(format!("Lines: {}", game.cleared_lines())),
+        Line::from(format!("Time: {}", format_time(game.time()))),
+        Line::from("Enter to continue").dark_gray(),
+    ];
+    if can_undo {
+        lines.push(Line::from("Z to undo").dark_gray());
+    }
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        Paragraph::new(lines).centered().block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title_alignment(Alignment::Center)
+                .title(game.mode().as_str()),
+        ),
+        popup,
+    );
 }
 
-fn draw_game(frame: &mut Frame, game: &Tetris, area: Rect) {
+pub fn draw_game(frame: &mut Frame, game: &Tetris, area: Rect, title: &str) {
     let block = Block::bordered()
         .title_alignment(Alignment::Center)
         .border_set(symbols::border::Set {
@@ -30,12 +111,21 @@ fn draw_game(frame: &mut Frame, game: &Tetris, area: Rect) {
             ..symbols::border::PLAIN
         })
         .bg(Color::DarkGray)
-        .title("Tetrust");
+        .title(title);
 
     let game_display = Text::from(game.display_map());
-    let layout = center(area, Constraint::Length(20), Constraint::Length(20));
     frame.render_widget(block, area);
-    frame.render_widget(game_display, layout);
+    frame.render_widget(game_display, playfield(game, area));
+}
+
+/// Visible cells of the board in the middle of `area`, each cell two characters wide
+fn playfield(game: &Tetris, area: Rect) -> Rect {
+    let board = game.board();
+    center(
+        area,
+        Constraint::Length(board.width() as u16 * 2),
+        Constraint::Length(board.visible_height() as u16),
+    )
 }
 
 fn draw_left(frame: &mut Frame, game: &Tetris, area: Rect) {
@@ -71,13 +161,169 @@ fn draw_left(frame: &mut Frame, game: &Tetris, area: Rect) {
         ),
     );
 
+    let stats = if game.mode() == GameMode::Master {
+        master_stats(game)
+    } else if game.mode() == GameMode::FinesseTraining {
+        training_stats(game)
+    } else if game.mode().opener().is_some() {
+        opener_stats(game)
+    } else if game.mode() == GameMode::Puzzle {
+        puzzle_stats(game)
+    } else {
+        let lines = match game.mode().line_goal() {
+            Some(goal) => format!("Lines: {}/{}", game.cleared_lines(), goal),
+            None => format!("Lines: {}", game.cleared_lines()),
+        };
+        let mut stats = vec![
+            Line::from(format!("Score: {}", game.score())),
+            Line::from(format!("Level: {}", game.level())),
+            Line::from(lines),
+            Line::from(""),
+            Line::from(format!("Attack: {}", game.total_attack())),
+            Line::from(format!("APM: {:.1}", game.apm())),
+            Line::from(format!("APP: {:.2}", game.app())),
+            Line::from(format!("Finesse faults: {}", game.finesse_faults())),
+        ];
+        if let Some(finesse) = game.last_finesse().filter(|finesse| finesse.is_fault()) {
+            stats.push(finesse_line(finesse));
+        }
+        if let Some(clear) = game.last_clear() {
+            stats.push(Line::from(clear.name()).magenta());
+        }
+        if let Some(garbage_left) = game.garbage_left() {
+            stats.push(Line::from(format!("Garbage left: {garbage_left}")));
+            stats.push(Line::from(format!("Time: {}", format_time(game.time()))));
+        }
+        if game.pending_garbage() > 0 {
+            stats.push(Line::from(format!("Garbage: {}", game.pending_garbage())).red());
+        }
+        stats
+    };
     frame.render_widget(
-        Paragraph::new(format!("Score: {}", game.score())).centered(), // .block(Block::bordered().border_type(BorderType::Rounded) )
+        Paragraph::new(stats).centered(), // .block(Block::bordered().border_type(BorderType::Rounded) )
         center(vertical_chunks[1], Constraint::Fill(1), Constraint::Fill(1)),
     );
 }
 
-fn draw_right(frame: &mut Frame, game: &Tetris, area: Rect) {
+fn training_stats(game: &Tetris) -> Vec<Line<'_>> {
+    let Some(training) = game.training() else {
+        return Vec::new();
+    };
+    let result = match game.last_finesse() {
+        Some(finesse) if finesse.is_fault() => finesse_line(finesse),
+        Some(_) if training.is_retry => Line::from("Missed the target").red(),
+        Some(_) => Line::from("Perfect!").green(),
+        None if training.is_retry => Line::from("Hard drop only").red(),
+        None => Line::from(""),
+    };
+    vec![
+        Line::from(format!(
+            "Placed: {}/{}",
+            training.placed,
+            game.locked_pieces()
+        )),
+        Line::from(format!("Faults: {}", game.finesse_faults())),
+        result,
+        Line::from(""),
+        Line::from("Drop it on the [] target").dark_gray(),
+        Line::from("with the fewest presses").dark_gray(),
+        Line::from("Z to undo, X to redo").dark_gray(),
+    ]
+}
+
+fn opener_stats(game: &Tetris) -> Vec<Line<'_>> {
+    let (Some(opener), Some(practice)) = (game.mode().opener(), game.practice()) else {
+        return Vec::new();
+    };
+    let result = if practice.is_missed {
+        Line::from("Missed the target").red()
+    } else if practice.is_done(opener) {
+        Line::from("Opener built!").green()
+    } else {
+        Line::from("")
+    };
+    vec![
+        Line::from(opener.name.as_str()).bold(),
+        Line::from(format!(
+            "Placed: {}/{}",
+            practice.placed,
+            opener.steps().len()
+        )),
+        Line::from(format!("Lines: {}", game.cleared_lines())),
+        result,
+        Line::from(""),
+        Line::from("Build it on the [] targets").dark_gray(),
+        Line::from("R to start over").dark_gray(),
+        Line::from("Z to undo, X to redo").dark_gray(),
+    ]
+}
+
+fn puzzle_stats(game: &Tetris) -> Vec<Line<'_>> {
+    let Some(puzzle) = game.puzzle() else {
+        return Vec::new();
+    };
+    let mut stats = vec![
+        Line::from(puzzle.name.as_str()).bold(),
+        Line::from(puzzle.goal.as_str()),
+        Line::from(""),
+        Line::from(format!("Tetrominoes: {}", game.locked_pieces())),
+        Line::from(format!("Lines: {}", game.cleared_lines())),
+        Line::from(format!(
+            "{} in {} or less",
+            format_stars(MAX_STARS),
+            puzzle.stars[0]
+        ))
+        .dark_gray(),
+        Line::from(format!(
+            "{} in {} or less",
+            format_stars(2),
+            puzzle.stars[1]
+        ))
+        .dark_gray(),
+    ];
+    if let Some(clear) = game.last_clear() {
+        stats.push(Line::from(clear.name()).magenta());
+    }
+    stats.push(Line::from(""));
+    stats.push(Line::from("R to start over").dark_gray());
+    stats.push(Line::from("Z to undo, X to redo").dark_gray());
+    stats
+}
+
+fn finesse_line(finesse: Judgement) -> Line<'static> {
+    Line::from(format!(
+        "{} presses, {} needed",
+        finesse.inputs, finesse.minimal
+    ))
+    .red()
+}
+
+fn master_stats(game: &Tetris) -> Vec<Line<'_>> {
+    let mut stats = vec![
+        Line::from(format!("Grade: {}", game.grade())).bold(),
+        Line::from(format!("Score: {}", game.score())),
+        Line::from(format!("Level: {}/{}", game.level(), game.section_limit())),
+        Line::from(format!("Time: {}", format_time(game.time()))),
+        Line::from(""),
+    ];
+    stats.extend(
+        game.section_times()
+            .iter()
+            .enumerate()
+            .map(|(section, ticks)| {
+                Line::from(format!(
+                    "{:03}-{:03} {}",
+                    section * SECTION_LENGTH,
+                    (section + 1) * SECTION_LENGTH - 1,
+                    format_time(*ticks)
+                ))
+                .dark_gray()
+            }),
+    );
+    stats
+}
+
+pub fn draw_right(frame: &mut Frame, game: &Tetris, area: Rect) {
     let horizontal_layout = Layout::horizontal([Constraint::Fill(1)])
         .flex(Flex::Center)
         .split(area)[0];
@@ -0,0 +1,74 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, MouseButton, MouseEvent, MouseEventKind};
+
+/// A key pressed or a cell of the screen clicked
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Press {
+    Key(KeyCode),
+    /// Pressed or dragged over with a mouse button, `right` for the right one
+    Click {
+        column: u16,
+        row: u16,
+        right: bool,
+    },
+}
+
+/// Where the key presses of a player come from
+pub trait Input {
+    /// Next key pressed within `timeout`, `None` if nothing was pressed in time
+    fn next_key(&mut self, timeout: Duration) -> io::Result<Option<KeyCode>>;
+
+    /// Block until a key is pressed
+    fn wait_key(&mut self) -> io::Result<KeyCode> {
+        loop {
+            if let Some(key) = self.next_key(Duration::from_secs(1))? {
+                return Ok(key);
+            }
+        }
+    }
+
+    /// Block until a key is pressed or the mouse clicks, inputs without a mouse only
+    /// giving keys
+    fn wait_press(&mut self) -> io::Result<Press> {
+        self.wait_key().map(Press::Key)
+    }
+}
+
+/// Keyboard and mouse of the terminal running the game
+pub struct TerminalInput;
+
+impl Input for TerminalInput {
+    fn next_key(&mut self, timeout: Duration) -> io::Result<Option<KeyCode>> {
+        let deadline = Instant::now() + timeout;
+        // Other events (mouse, resize, key releases) don't count
+        while event::poll(deadline.saturating_duration_since(Instant::now()))? {
+            if let Some(key) = event::read()?.as_key_press_event() {
+                return Ok(Some(key.code));
+            }
+        }
+        Ok(None)
+    }
+
+    fn wait_press(&mut self) -> io::Result<Press> {
+        loop {
+            match event::read()? {
+                Event::Key(key) if key.is_press() => return Ok(Press::Key(key.code)),
+                Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::Down(button) | MouseEventKind::Drag(button),
+                    column,
+                    row,
+                    ..
+                }) => {
+                    return Ok(Press::Click {
+                        column,
+                        row,
+                        right: button == MouseButton::Right,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+}
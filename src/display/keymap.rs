@@ -0,0 +1,50 @@
+use crossterm::event::KeyCode;
+
+use crate::action::Action;
+
+pub type KeyMap = [(KeyCode, Action)];
+
+pub const SOLO: [(KeyCode, Action); 7] = [
+    (KeyCode::Char('a'), Action::MoveLeft),
+    (KeyCode::Char('d'), Action::MoveRight),
+    (KeyCode::Char('s'), Action::SoftDrop),
+    (KeyCode::Char('w'), Action::HardDrop),
+    (KeyCode::Char('q'), Action::RotateCounterClockwise),
+    (KeyCode::Char('e'), Action::RotateClockwise),
+    (KeyCode::Char('h'), Action::Hold),
+];
+
+/// Start a solo game over, in the modes practicing an opener and in puzzles
+pub const RESTART: KeyCode = KeyCode::Char('r');
+
+/// Take back the last placement in the practice modes, or play it again
+pub const UNDO: KeyCode = KeyCode::Char('z');
+pub const REDO: KeyCode = KeyCode::Char('x');
+
+/// Write the state of a solo game to a JSON file, to attach to bug reports
+#[cfg(feature = "serde")]
+pub const DUMP: KeyCode = KeyCode::F(12);
+/// Same as bincode, for the tools reading many states
+#[cfg(feature = "serde")]
+pub const DUMP_BINARY: KeyCode = KeyCode::F(11);
+
+/// Left player of a local versus, same keys as solo
+pub const PLAYER_ONE: [(KeyCode, Action); 7] = SOLO;
+
+/// Right player of a local versus, arrows and numpad
+pub const PLAYER_TWO: [(KeyCode, Action); 7] = [
+    (KeyCode::Left, Action::MoveLeft),
+    (KeyCode::Right, Action::MoveRight),
+    (KeyCode::Down, Action::SoftDrop),
+    (KeyCode::Up, Action::HardDrop),
+    (KeyCode::Char('1'), Action::RotateCounterClockwise),
+    (KeyCode::Char('2'), Action::RotateClockwise),
+    (KeyCode::Char('0'), Action::Hold),
+];
+
+pub fn action(key_map: &KeyMap, key: KeyCode) -> Option<Action> {
+    key_map
+        .iter()
+        .find(|(code, _)| *code == key)
+        .map(|(_, action)| *action)
+}
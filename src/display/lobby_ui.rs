@@ -0,0 +1,81 @@
+use crate::display::utils::center::center;
+use crate::server::lobby::PlayerStatus;
+use ratatui::Frame;
+use ratatui::layout::{Alignment, Constraint, Layout};
+use ratatui::style::{Color, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, BorderType, Paragraph};
+
+pub fn draw_name_prompt(frame: &mut Frame, name: &str) {
+    let area = center(frame.area(), Constraint::Length(30), Constraint::Length(5));
+    frame.render_widget(
+        Paragraph::new(vec![
+            Line::from(format!("{name}_")).bold(),
+            Line::from(""),
+            Line::from("Enter to join the lobby").dark_gray(),
+        ])
+        .centered()
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title_alignment(Alignment::Center)
+                .title("Your name"),
+        ),
+        area,
+    );
+}
+
+pub fn draw(
+    frame: &mut Frame,
+    name: &str,
+    others: &[(usize, String, PlayerStatus)],
+    selected: usize,
+) {
+    let area = center(frame.area(), Constraint::Length(50), Constraint::Fill(1));
+    let [list, help] = Layout::vertical([Constraint::Fill(1), Constraint::Length(3)]).areas(area);
+
+    let lines: Vec<Line> = if others.is_empty() {
+        vec![Line::from("Nobody else is here yet").dark_gray().centered()]
+    } else {
+        others
+            .iter()
+            .enumerate()
+            .map(|(id, (_, name, status))| {
+                let color = match status {
+                    PlayerStatus::ChallengesYou => Color::Green,
+                    PlayerStatus::ChallengedByYou => Color::Yellow,
+                    PlayerStatus::Idle | PlayerStatus::Playing => Color::DarkGray,
+                };
+                let mut line = Line::from(vec![
+                    Span::raw(format!(" {name:<20}")),
+                    Span::raw(status.as_str()).fg(color),
+                ]);
+                if id == selected {
+                    line = line
+                        .bg(Color::from_u32(0x707070))
+                        .fg(Color::from_u32(0xffffff));
+                }
+                line
+            })
+            .collect()
+    };
+    frame.render_widget(
+        Paragraph::new(lines).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title_alignment(Alignment::Center)
+                .title(format!("Lobby - {name}")),
+        ),
+        list,
+    );
+    frame.render_widget(
+        Paragraph::new(vec![
+            Line::from("Enter: challenge or accept   p: play alone   Esc: quit"),
+            Line::from("A match starts when both players challenged each other"),
+        ])
+        .dark_gray()
+        .centered()
+        .block(Block::bordered().border_type(BorderType::Rounded)),
+        help,
+    );
+}
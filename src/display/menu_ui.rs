@@ -1,9 +1,12 @@
 use crate::display::utils::center::center;
+use crate::display::utils::stars::format_stars;
+use crate::game_mode::GameMode;
 use crate::menu;
-use crate::menu::Menu;
-use ratatui::layout::{Alignment, Constraint, Flex, Layout};
+use crate::menu::{Menu, Options};
+use crate::puzzle::{Progress, Puzzle};
+use ratatui::layout::{Alignment, Constraint, Flex, Layout, Rect};
 use ratatui::style::{Color, Stylize};
-use ratatui::text::Text;
+use ratatui::text::{Line, Text};
 use ratatui::widgets::{Block, BorderType, Borders};
 use ratatui::{Frame, border};
 
@@ -12,6 +15,11 @@ pub fn draw(frame: &mut Frame, menu: &Menu) {
 
     let horizontal_centered_layout = center(area, Constraint::Fill(1), Constraint::Fill(1));
 
+    if menu.selected() == Options::New {
+        draw_modes(frame, menu, horizontal_centered_layout);
+        return;
+    }
+
     let vertical_chunks = Layout::vertical([Constraint::Min(1); menu::OPTION_LEN])
         .flex(Flex::Center)
         .margin(1)
@@ -26,6 +34,9 @@ pub fn draw(frame: &mut Frame, menu: &Menu) {
 
     for (id, option) in menu.options().iter().enumerate() {
         let mut text: Text = Text::from(option.as_str());
+        if *option == Options::Continue && !menu.can_continue() {
+            text = text.dark_gray();
+        }
         if menu.hovered() == *option {
             text = text
                 .bg(Color::from_u32(0x707070))
@@ -34,3 +45,73 @@ pub fn draw(frame: &mut Frame, menu: &Menu) {
         frame.render_widget(text.centered(), vertical_chunks[id]);
     }
 }
+
+fn draw_modes(frame: &mut Frame, menu: &Menu, area: Rect) {
+    let vertical_chunks = Layout::vertical(vec![Constraint::Min(1); GameMode::ALL.len()])
+        .flex(Flex::Center)
+        .margin(1)
+        .split(area);
+
+    let block = Block::bordered()
+        .title_alignment(Alignment::Center)
+        .borders(border!(ALL))
+        .border_type(BorderType::Rounded)
+        .title("New Game");
+    frame.render_widget(block, area);
+
+    for (id, mode) in GameMode::ALL.iter().enumerate() {
+        let mut text: Text = Text::from(mode.as_str());
+        if menu.hovered_mode() == *mode {
+            text = text
+                .bg(Color::from_u32(0x707070))
+                .fg(Color::from_u32(0xffffff));
+        }
+        frame.render_widget(text.centered(), vertical_chunks[id]);
+    }
+}
+
+/// Puzzles of the puzzle directory with the stars earned on each, or why none could be read
+pub fn draw_puzzles(
+    frame: &mut Frame,
+    puzzles: &[Puzzle],
+    progress: &Progress,
+    hovered: usize,
+    error: Option<&str>,
+) {
+    let area = center(frame.area(), Constraint::Fill(1), Constraint::Fill(1));
+    let block = Block::bordered()
+        .title_alignment(Alignment::Center)
+        .borders(border!(ALL))
+        .border_type(BorderType::Rounded)
+        .title("Puzzles");
+    frame.render_widget(block, area);
+
+    let mut lines: Vec<Line> = puzzles
+        .iter()
+        .enumerate()
+        .map(|(id, puzzle)| {
+            let line = Line::from(format!(
+                "{} - {} {}",
+                puzzle.name,
+                puzzle.goal.as_str(),
+                format_stars(progress.stars(puzzle))
+            ));
+            if id == hovered {
+                line.bg(Color::from_u32(0x707070))
+                    .fg(Color::from_u32(0xffffff))
+            } else {
+                line
+            }
+        })
+        .collect();
+    if let Some(error) = error {
+        lines.push(Line::from(error).red());
+    } else if puzzles.is_empty() {
+        lines.push(Line::from("No puzzle found").dark_gray());
+    }
+    let height = lines.len() as u16;
+    frame.render_widget(
+        Text::from(lines).centered(),
+        center(area, Constraint::Fill(1), Constraint::Length(height)),
+    );
+}
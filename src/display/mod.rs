@@ -1,4 +1,10 @@
 pub mod crossterm;
+mod editor_ui;
 mod game_ui;
+pub mod input;
+pub mod keymap;
+pub mod lobby_ui;
 mod menu_ui;
+mod spectate_ui;
 pub mod utils;
+pub mod versus_ui;
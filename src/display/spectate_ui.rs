@@ -0,0 +1,50 @@
+use crate::display::utils::center::center;
+use crate::display::{game_ui, versus_ui};
+use crate::net::spectate::Broadcast;
+use crate::tetris::Tetris;
+use crate::versus::Versus;
+use ratatui::Frame;
+use ratatui::layout::Constraint;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Paragraph;
+
+/// The game as the player sees it, `broadcast` being `None` until the first one arrives
+pub fn draw(frame: &mut Frame, broadcast: Option<&Broadcast>, is_connected: bool) {
+    match broadcast {
+        None | Some(Broadcast::Idle) => draw_message(frame, "Waiting for a game to start..."),
+        Some(Broadcast::Solo(snapshot)) => {
+            game_ui::draw(frame, &Tetris::from_snapshot((**snapshot).clone()));
+        }
+        Some(Broadcast::Versus {
+            names,
+            wins,
+            best_of,
+            round_winner,
+            boards,
+        }) => {
+            let versus = Versus::from_snapshots(
+                (**boards).clone().map(Tetris::from_snapshot),
+                *wins,
+                *best_of,
+                *round_winner,
+            );
+            versus_ui::draw(frame, &versus, [&names[0], &names[1]]);
+        }
+    }
+    if !is_connected {
+        versus_ui::draw_status(frame, "The game was closed, Esc to quit");
+    }
+}
+
+fn draw_message(frame: &mut Frame, message: &str) {
+    let area = center(frame.area(), Constraint::Fill(1), Constraint::Length(2));
+    frame.render_widget(
+        Paragraph::new(vec![
+            Line::from(message.to_string()).bold(),
+            Line::from("Esc to quit").dark_gray(),
+        ])
+        .centered(),
+        area,
+    );
+}
@@ -8,7 +8,7 @@ pub fn center_vertical(area: Rect, height: u16) -> Rect {
 }
 
 pub fn center_horizontal(area: Rect, width: u16) -> Rect {
-    let [area] = Layout::horizontal([Constraint::Fill(width)])
+    let [area] = Layout::horizontal([Constraint::Length(width)])
         .flex(Flex::Center)
         .areas(area);
     area
@@ -1 +1,3 @@
 pub mod center;
+pub mod stars;
+pub mod time;
@@ -0,0 +1,10 @@
+use crate::puzzle::MAX_STARS;
+
+/// Format the stars earned on a puzzle out of the most it gives, as `★★☆`
+pub fn format_stars(earned: usize) -> String {
+    format!(
+        "{}{}",
+        "★".repeat(earned.min(MAX_STARS)),
+        "☆".repeat(MAX_STARS.saturating_sub(earned))
+    )
+}
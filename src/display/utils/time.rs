@@ -0,0 +1,12 @@
+use crate::tetris::FPS;
+
+/// Format a number of ticks as `minutes:seconds.centiseconds`
+pub fn format_time(ticks: usize) -> String {
+    let centiseconds = ticks * 100 / FPS;
+    format!(
+        "{}:{:02}.{:02}",
+        centiseconds / 6000,
+        centiseconds / 100 % 60,
+        centiseconds % 100
+    )
+}
@@ -0,0 +1,76 @@
+use crate::display::game_ui;
+use crate::display::utils::center::{center_horizontal, center_vertical};
+use crate::versus::{PLAYERS, Versus};
+use ratatui::Frame;
+use ratatui::layout::{Alignment, Constraint, Layout, Rect};
+use ratatui::style::{Color, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, BorderType, Clear, Paragraph};
+
+pub fn draw(frame: &mut Frame, versus: &Versus, names: [&str; PLAYERS]) {
+    let [header, boards] =
+        Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(frame.area());
+    let halves = Layout::horizontal([Constraint::Fill(1); PLAYERS]).split(boards);
+
+    let wins = versus.wins();
+    frame.render_widget(
+        Line::from(format!(
+            "{} {} - {} {}   (best of {})",
+            names[0],
+            wins[0],
+            wins[1],
+            names[1],
+            versus.best_of()
+        ))
+        .bold()
+        .centered(),
+        header,
+    );
+
+    let playfields: Vec<Rect> = (0..PLAYERS)
+        .map(|id| game_ui::draw_board(frame, versus.player(id), halves[id], names[id]))
+        .collect();
+
+    if let Some(winner) = versus.round_winner() {
+        draw_round_end(frame, versus, names, playfields[winner]);
+    }
+}
+
+fn draw_round_end(frame: &mut Frame, versus: &Versus, names: [&str; PLAYERS], area: Rect) {
+    let popup = center_vertical(center_horizontal(area, 20), 6);
+    let (title, next) = if versus.is_over() {
+        ("wins the match!", "Enter to continue")
+    } else {
+        ("wins the round", "Enter: next round")
+    };
+    let winner = versus.round_winner().unwrap_or(0);
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        Paragraph::new(vec![
+            Line::from(names[winner]).fg(Color::Green).bold(),
+            Line::from(title).fg(Color::Green),
+            Line::from(""),
+            Line::from(next).dark_gray(),
+        ])
+        .centered()
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title_alignment(Alignment::Center)
+                .title("Versus"),
+        ),
+        popup,
+    );
+}
+
+/// Message about the other player of an online match, on the bottom line
+pub fn draw_status(frame: &mut Frame, status: &str) {
+    let area = frame.area();
+    let line = Rect::new(area.x, area.bottom().saturating_sub(1), area.width, 1);
+    frame.render_widget(Clear, line);
+    frame.render_widget(
+        Line::from(status.to_string()).fg(Color::Yellow).centered(),
+        line,
+    );
+}
@@ -0,0 +1,213 @@
+//! Board editor: cells painted one by one, the queue and the hold typed in, to play from
+//! there or to save as a setup file or a fumen.
+
+use std::path::Path;
+
+use crate::board::Board;
+use crate::game_mode::GameMode;
+use crate::setup::Setup;
+use crate::tetris::{HIDDEN_ROWS, MAP_HEIGHT, MAP_WIDTH, Map, TRUE_MAP_HEIGHT, Tetris};
+use crate::tetromino_type::TetrominoType;
+
+const DEFAULT_FILE: &str = "setup.json";
+const NO_FILES: &str = "files cannot be used here, load a fumen instead";
+
+/// What the text typed in the editor is for
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Prompt {
+    Queue,
+    Hold,
+    Save,
+    /// A setup file or a fumen
+    Load,
+}
+
+pub struct Editor {
+    map: Map,
+    queue: Vec<TetrominoType>,
+    hold: TetrominoType,
+    cursor: (usize, usize), // Row and column on the visible field
+    brush: TetrominoType,
+    prompt: Option<(Prompt, String)>,
+    message: Option<Result<String, String>>, // Outcome of the last command
+    file: String,                            // Last file saved or loaded
+    files: bool,                             // Whether files may be read and written
+}
+
+impl Prompt {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Prompt::Queue => "Queue",
+            Prompt::Hold => "Hold",
+            Prompt::Save => "Save to",
+            Prompt::Load => "Load file or fumen",
+        }
+    }
+}
+
+impl Editor {
+    /// Empty board, setups only read from and written to files if `files`, otherwise as fumen
+    pub fn new(files: bool) -> Self {
+        Self {
+            map: [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT],
+            queue: Vec::new(),
+            hold: TetrominoType::E,
+            cursor: (MAP_HEIGHT - 1, 0),
+            brush: TetrominoType::G,
+            prompt: None,
+            message: None,
+            file: DEFAULT_FILE.to_string(),
+            files,
+        }
+    }
+
+    pub fn setup(&self) -> Setup {
+        Setup::new(&self.map, &self.queue, self.hold)
+    }
+
+    fn load_setup(&mut self, setup: &Setup) {
+        self.map = setup.map();
+        self.queue = setup.queue();
+        self.hold = setup.hold();
+    }
+
+    /// Game drawing the board, the hold and the queue, without any tetromino falling
+    pub fn preview(&self) -> Tetris {
+        let mut snapshot = Tetris::new(GameMode::Endless).snapshot();
+        snapshot.board = Board::from(self.map);
+        snapshot.hold = self.hold;
+        snapshot.nexts = self.queue.clone();
+        snapshot.falling = false;
+        Tetris::from_snapshot(snapshot)
+    }
+
+    /// Move the cursor by `(rows, columns)`, staying on the visible field
+    pub fn move_cursor(&mut self, rows: isize, columns: isize) {
+        self.cursor = (
+            self.cursor
+                .0
+                .saturating_add_signed(rows)
+                .min(MAP_HEIGHT - 1),
+            self.cursor
+                .1
+                .saturating_add_signed(columns)
+                .min(MAP_WIDTH - 1),
+        );
+    }
+
+    /// Paint the cell under the cursor with the brush, or empty it
+    pub fn paint(&mut self, erase: bool) {
+        let shape = if erase { TetrominoType::E } else { self.brush };
+        self.map[HIDDEN_ROWS + self.cursor.0][self.cursor.1] = shape;
+    }
+
+    /// Move the cursor to a cell of the visible field and paint it
+    pub fn paint_at(&mut self, row: usize, column: usize, erase: bool) {
+        if row < MAP_HEIGHT && column < MAP_WIDTH {
+            self.cursor = (row, column);
+            self.paint(erase);
+        }
+    }
+
+    pub fn set_brush(&mut self, brush: TetrominoType) {
+        self.brush = brush;
+    }
+
+    pub fn clear(&mut self) {
+        self.map = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+    }
+
+    /// Start typing for `prompt`, from what it currently holds
+    pub fn start_prompt(&mut self, prompt: Prompt) {
+        let text = match prompt {
+            Prompt::Queue => self.queue.iter().map(|shape| shape.letter()).collect(),
+            Prompt::Hold => self.setup().hold.map(String::from).unwrap_or_default(),
+            Prompt::Save | Prompt::Load if self.files => self.file.clone(),
+            Prompt::Save | Prompt::Load => String::new(),
+        };
+        self.prompt = Some((prompt, text));
+        self.message = None;
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        if let Some((_, text)) = self.prompt.as_mut() {
+            text.push(c);
+        }
+    }
+
+    pub fn erase_char(&mut self) {
+        if let Some((_, text)) = self.prompt.as_mut() {
+            text.pop();
+        }
+    }
+
+    pub fn cancel_prompt(&mut self) {
+        self.prompt = None;
+    }
+
+    /// Use the text typed for the prompt
+    pub fn submit_prompt(&mut self) {
+        let Some((prompt, text)) = self.prompt.take() else {
+            return;
+        };
+        let text = text.trim();
+        let is_fumen = text.contains("v115@");
+        let letters = || {
+            text.chars()
+                .map(TetrominoType::from_letter)
+                .filter(|shape| *shape != TetrominoType::E)
+        };
+        self.message = match prompt {
+            Prompt::Queue => {
+                self.queue = letters().collect();
+                None
+            }
+            Prompt::Hold => {
+                self.hold = letters().next().unwrap_or(TetrominoType::E);
+                None
+            }
+            Prompt::Save if !self.files => Some(Err(NO_FILES.into())),
+            Prompt::Load if !self.files && !is_fumen => Some(Err(NO_FILES.into())),
+            Prompt::Save => Some(
+                self.setup()
+                    .save(Path::new(text))
+                    .map(|_| format!("Saved to {text}")),
+            ),
+            Prompt::Load if is_fumen => Some(Setup::from_fumen(text).map(|setup| {
+                self.load_setup(&setup);
+                "Fumen loaded".to_string()
+            })),
+            Prompt::Load => Some(Setup::load(Path::new(text)).map(|setup| {
+                self.load_setup(&setup);
+                format!("Loaded {text}")
+            })),
+        }
+        .map(|result| result.map_err(|error| error.to_string()));
+        if self.files && matches!(prompt, Prompt::Save | Prompt::Load) && !is_fumen {
+            self.file = text.to_string();
+        }
+    }
+
+    /// Show the fumen of the board, with the hold and the queue as a comment
+    pub fn export_fumen(&mut self) {
+        self.message = Some(Ok(self.setup().to_fumen()));
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    pub fn brush(&self) -> TetrominoType {
+        self.brush
+    }
+
+    pub fn prompt(&self) -> Option<(Prompt, &str)> {
+        self.prompt
+            .as_ref()
+            .map(|(prompt, text)| (*prompt, text.as_str()))
+    }
+
+    pub fn message(&self) -> Option<&Result<String, String>> {
+        self.message.as_ref()
+    }
+}
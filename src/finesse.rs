@@ -0,0 +1,291 @@
+//! Finesse: placing each tetromino with as few key presses as possible. Like the guideline
+//! finesse tables, presses are counted on an empty board and holding a move key until the
+//! tetromino reaches the wall (DAS) is a single press. Any placement is reached in at most
+//! two moves: tapping, or going to a wall and tapping back. Tables are built for each board
+//! width, the spawn column and the walls moving with it.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::OnceLock;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::action::Action;
+use crate::board::{Board, MAX_WIDTH};
+use crate::rotation_system::RotationSystem;
+use crate::tetris::TRUE_MAP_HEIGHT;
+use crate::tetromino::{Tetromino, TetrominoTrait};
+use crate::tetromino_type::TetrominoType;
+
+// Cells of a placement with rows counted from its lowest one, the same wherever it lands
+type Key = [(i8, i8); 4];
+// Fewest presses for every placement of each tetromino type, indexed by `TetrominoType as usize`
+type Table = [Vec<(Key, usize)>; 9];
+
+// Built the first time a tetromino of each rotation system is judged on a board of each width,
+// indexed by `RotationSystem as usize` then by the width
+static TABLES: [[OnceLock<Table>; MAX_WIDTH + 1]; RotationSystem::ALL.len()] =
+    [const { [const { OnceLock::new() }; MAX_WIDTH + 1] }; RotationSystem::ALL.len()];
+
+/// Presses used to place a tetromino against the fewest needed
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Judgement {
+    pub inputs: usize,
+    pub minimal: usize,
+}
+
+impl Judgement {
+    pub fn is_fault(&self) -> bool {
+        self.inputs > self.minimal
+    }
+}
+
+/// Finesse training: where the tetromino has to lock and how the previous ones went
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Training {
+    pub shape: TetrominoType,
+    pub target: [(i8, i8); 4], // Cells, as `Tetromino::cells`
+    pub placed: usize,         // Tetrominoes locked on their target with the fewest presses
+    pub is_retry: bool,        // Whether the last tetromino missed and comes back
+}
+
+/// Presses of the current tetromino and the faults of the previous ones
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Finesse {
+    spawned: Option<Tetromino>,
+    inputs: Vec<Action>,
+    faults: usize,
+    last: Option<Judgement>,
+}
+
+impl Finesse {
+    /// Counters of a game rebuilt from a snapshot, only meant to be drawn
+    pub fn from_counters(faults: usize, last: Option<Judgement>) -> Self {
+        Self {
+            faults,
+            last,
+            ..Self::default()
+        }
+    }
+
+    /// Start counting for a tetromino that just spawned, before any rotation
+    pub fn start(&mut self, tetromino: &Tetromino) {
+        self.spawned = Some(tetromino.clone());
+        self.inputs.clear();
+    }
+
+    /// Count a key pressed while the tetromino falls
+    pub fn record(&mut self, action: Action) {
+        if action != Action::Hold && action != Action::HardDrop {
+            self.inputs.push(action);
+        }
+    }
+
+    /// Judge the tetromino that just locked on a board `width` columns wide. Soft dropped ones
+    /// are left out, they may have been tucked under the stack where the empty board tables
+    /// mean nothing.
+    pub fn judge(&mut self, locked: &Tetromino, width: usize) -> Option<Judgement> {
+        self.last = None;
+        let spawned = self.spawned.take()?;
+        if self.inputs.contains(&Action::SoftDrop) {
+            return None;
+        }
+        let key = key(locked);
+        let minimal = table(spawned.rotation_system(), width)[spawned.shape() as usize]
+            .iter()
+            .find(|(placement, _)| *placement == key)
+            .map(|(_, minimal)| *minimal)?;
+        let judgement = Judgement {
+            inputs: presses(&spawned, &self.inputs, &empty_board(width)),
+            minimal,
+        };
+        if judgement.is_fault() {
+            self.faults += 1;
+        }
+        self.last = Some(judgement);
+        Some(judgement)
+    }
+
+    pub fn faults(&self) -> usize {
+        self.faults
+    }
+
+    /// Judgement of the last locked tetromino, `None` if it was not judged
+    pub fn last(&self) -> Option<Judgement> {
+        self.last
+    }
+}
+
+/// Every spot `spawned` can be hard dropped on over an empty board `width` columns wide, the
+/// tetromino as it locks there and the fewest presses reaching it
+pub fn placements(spawned: &Tetromino, width: usize) -> Vec<(Tetromino, usize)> {
+    let board = empty_board(width);
+    let mut placements = Vec::new();
+    let mut keys = HashSet::new();
+    let mut seen = HashSet::from([(spawned.rotation(), spawned.pos().1)]);
+    let mut queue = VecDeque::from([(spawned.clone(), 0)]);
+    while let Some((tetromino, depth)) = queue.pop_front() {
+        let mut dropped = tetromino.clone();
+        while board.fits(&dropped, [1, 0]) {
+            dropped.r#move([1, 0]);
+        }
+        if keys.insert(key(&dropped)) {
+            placements.push((dropped, depth));
+        }
+
+        let mut nexts = Vec::new();
+        for direction in [-1, 1] {
+            let mut tapped = tetromino.clone();
+            if board.fits(&tapped, [0, direction]) {
+                tapped.r#move([0, direction]);
+                nexts.push(tapped.clone());
+            }
+            while board.fits(&tapped, [0, direction]) {
+                tapped.r#move([0, direction]);
+            }
+            nexts.push(tapped);
+        }
+        let mut clockwise = tetromino.clone();
+        if clockwise.rotate_clockwise(&board) {
+            nexts.push(clockwise);
+        }
+        let mut counter_clockwise = tetromino.clone();
+        if counter_clockwise.rotate_counter_clockwise(&board) {
+            nexts.push(counter_clockwise);
+        }
+        for next in nexts {
+            if seen.insert((next.rotation(), next.pos().1)) {
+                queue.push_back((next, depth + 1));
+            }
+        }
+    }
+    placements
+}
+
+fn table(rotation_system: RotationSystem, width: usize) -> &'static Table {
+    TABLES[rotation_system as usize][width].get_or_init(|| {
+        std::array::from_fn(|shape| match TetrominoType::from_u8(shape as u8) {
+            TetrominoType::E | TetrominoType::G => Vec::new(),
+            shape => placements(&Tetromino::new(shape, rotation_system, width), width)
+                .iter()
+                .map(|(tetromino, presses)| (key(tetromino), *presses))
+                .collect(),
+        })
+    })
+}
+
+/// Presses of `inputs` played from `spawned` on the empty `board`, a move going on until the
+/// wall being held
+fn presses(spawned: &Tetromino, inputs: &[Action], board: &Board) -> usize {
+    let mut tetromino = spawned.clone();
+    let mut presses = 0;
+    let mut index = 0;
+    while index < inputs.len() {
+        let action = inputs[index];
+        let run = inputs[index..]
+            .iter()
+            .take_while(|input| **input == action)
+            .count();
+        match action {
+            Action::MoveLeft | Action::MoveRight => {
+                let vector = [0, if action == Action::MoveLeft { -1 } else { 1 }];
+                for _ in 0..run {
+                    if board.fits(&tetromino, vector) {
+                        tetromino.r#move(vector);
+                    }
+                }
+                presses += if board.fits(&tetromino, vector) {
+                    run
+                } else {
+                    1
+                };
+            }
+            Action::RotateClockwise => {
+                for _ in 0..run {
+                    tetromino.rotate_clockwise(board);
+                }
+                presses += run;
+            }
+            Action::RotateCounterClockwise => {
+                for _ in 0..run {
+                    tetromino.rotate_counter_clockwise(board);
+                }
+                presses += run;
+            }
+            Action::SoftDrop | Action::HardDrop | Action::Hold => {}
+        }
+        index += run;
+    }
+    presses
+}
+
+/// Empty board of `width` columns, keys count rows from their lowest one whatever its height
+fn empty_board(width: usize) -> Board {
+    Board::new(width, TRUE_MAP_HEIGHT)
+}
+
+fn key(tetromino: &Tetromino) -> Key {
+    let cells = tetromino.cells();
+    let lowest = cells.iter().map(|(row, _)| *row).max().unwrap_or(0);
+    cells.map(|(row, column)| (row - lowest, column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Judgement of `inputs` played from the spawn on an empty board `width` columns wide
+    fn judge(shape: TetrominoType, inputs: &[Action], width: usize) -> Option<Judgement> {
+        let board = empty_board(width);
+        let spawned = Tetromino::new(shape, RotationSystem::Srs, width);
+        let mut finesse = Finesse::default();
+        finesse.start(&spawned);
+        let mut tetromino = spawned.clone();
+        for action in inputs {
+            finesse.record(*action);
+            match action {
+                Action::MoveLeft if board.fits(&tetromino, [0, -1]) => tetromino.r#move([0, -1]),
+                Action::MoveRight if board.fits(&tetromino, [0, 1]) => tetromino.r#move([0, 1]),
+                Action::RotateClockwise => {
+                    tetromino.rotate_clockwise(&board);
+                }
+                _ => {}
+            }
+        }
+        while board.fits(&tetromino, [1, 0]) {
+            tetromino.r#move([1, 0]);
+        }
+        finesse.judge(&tetromino, width)
+    }
+
+    #[test]
+    fn walls_follow_the_board_width() {
+        for width in [4, 10, 20, MAX_WIDTH] {
+            // Held until the wall, whatever the distance
+            let to_wall = vec![Action::MoveRight; width];
+            let judgement = judge(TetrominoType::O, &to_wall, width).unwrap();
+            assert_eq!((judgement.inputs, judgement.minimal), (1, 1), "{width}");
+        }
+    }
+
+    #[test]
+    fn faults_are_counted_beyond_the_standard_width() {
+        // Placements right of the standard board are in the tables
+        let inputs = [Action::MoveRight; 12];
+        let judgement = judge(TetrominoType::T, &inputs, 20).unwrap();
+        assert_eq!(judgement.minimal, 1);
+        // A whole turn before tapping once
+        let inputs = [
+            Action::RotateClockwise,
+            Action::RotateClockwise,
+            Action::RotateClockwise,
+            Action::RotateClockwise,
+            Action::MoveLeft,
+        ];
+        let judgement = judge(TetrominoType::T, &inputs, 20).unwrap();
+        assert!(judgement.is_fault());
+    }
+}
@@ -0,0 +1,501 @@
+//! Fumen, the board diagrams shared as `v115@...` strings by the fumen.zui.jp editor.
+//! Each page stores the changes to the field of the previous one, run length encoded, and
+//! the tetromino drawn on it, in a base 64 alphabet. Comments starting with `#Q=` give the
+//! hold and the queue of the board, as `#Q=[hold](current)nexts`.
+
+use std::error::Error;
+
+use crate::tetris::{MAP_WIDTH, Map, TRUE_MAP_HEIGHT};
+use crate::tetromino_type::TetrominoType;
+
+const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const VERSION: &str = "v115@";
+const FIELD_HEIGHT: usize = 23; // Rows above the garbage row, the lowest one at the bottom
+const FIELD_CELLS: usize = (FIELD_HEIGHT + 1) * MAP_WIDTH;
+// Characters a comment is written with, its values going up to 96
+const COMMENT_CHARACTERS: &str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COLOR_FLAG: usize = 4; // Guideline colors, set on the first page
+const COMMENT_FLAG: usize = 8;
+const QUIZ: &str = "#Q=";
+
+/// Board shown on a page, with the tetromino of the page drawn in, and its comment
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    pub map: Map,
+    pub comment: String,
+}
+
+/// Every page of `data`, a `v115@` string or a link ending with one
+pub fn decode(data: &str) -> Result<Vec<Page>, Box<dyn Error>> {
+    let Some((_, data)) = data.split_once(VERSION) else {
+        return Err("only v115 fumen are supported".into());
+    };
+    let mut values = Values::new(data)?;
+    let mut field = [0u8; FIELD_CELLS];
+    let mut pages = Vec::new();
+    let mut repeat = 0;
+    let mut comment = String::new(); // Kept by the next pages until one changes it
+
+    while !values.is_empty() {
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut cell = 0;
+            while cell < FIELD_CELLS {
+                let value = values.next(2)?;
+                let (diff, count) = (value / FIELD_CELLS, value % FIELD_CELLS + 1);
+                if cell + count > FIELD_CELLS || diff > 16 {
+                    return Err("invalid fumen field".into());
+                }
+                // Differences go from -8 to 8, stored shifted by 8
+                for block in &mut field[cell..cell + count] {
+                    *block = ((*block as usize + diff + 1) % 9) as u8;
+                }
+                cell += count;
+                // An unchanged field is followed by the number of next pages keeping it
+                if diff == 8 && count == FIELD_CELLS {
+                    repeat = values.next(1)?;
+                }
+            }
+        }
+
+        let value = values.next(3)?;
+        let piece = (value % 8) as u8;
+        let rotation = value / 8 % 4;
+        let location = value / 32 % FIELD_CELLS;
+        let flags = value / (32 * FIELD_CELLS);
+        let (raise, mirror, has_comment, lock) = (
+            flags & 1 != 0,
+            flags & 2 != 0,
+            flags & COMMENT_FLAG != 0,
+            flags & 16 == 0,
+        );
+        if has_comment {
+            comment = values.comment()?;
+        }
+
+        let mut shown = field;
+        if piece != 0 {
+            for (x, y) in blocks(piece, rotation)? {
+                let (x, y) = (
+                    (location % MAP_WIDTH) as i8 + x,
+                    (location / MAP_WIDTH) as i8 - y,
+                );
+                if !(0..MAP_WIDTH as i8).contains(&x) || !(0..FIELD_HEIGHT as i8).contains(&y) {
+                    return Err("fumen tetromino out of the field".into());
+                }
+                shown[y as usize * MAP_WIDTH + x as usize] = piece;
+            }
+        }
+        pages.push(Page {
+            map: to_map(&shown)?,
+            comment: comment.clone(),
+        });
+
+        if lock {
+            field = shown;
+            clear_lines(&mut field);
+            if raise {
+                field.copy_within(MAP_WIDTH.., 0);
+                field[FIELD_CELLS - MAP_WIDTH..].fill(0);
+            }
+            if mirror {
+                for row in field[..FIELD_HEIGHT * MAP_WIDTH].chunks_mut(MAP_WIDTH) {
+                    row.reverse();
+                }
+            }
+        }
+    }
+    Ok(pages)
+}
+
+/// Characters of the data turned into their base 64 values
+struct Values {
+    values: Vec<usize>,
+    index: usize,
+}
+
+impl Values {
+    fn new(data: &str) -> Result<Self, Box<dyn Error>> {
+        // Editors cut long data with question marks
+        let values = data
+            .chars()
+            .filter(|c| *c != '?')
+            .map(|c| {
+                ALPHABET
+                    .find(c)
+                    .ok_or(format!("invalid fumen character {c}"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { values, index: 0 })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.index >= self.values.len()
+    }
+
+    /// Number written with the `count` next characters, lowest digit first
+    fn next(&mut self, count: usize) -> Result<usize, Box<dyn Error>> {
+        let digits = self
+            .values
+            .get(self.index..self.index + count)
+            .ok_or("truncated fumen")?;
+        self.index += count;
+        Ok(digits
+            .iter()
+            .rev()
+            .fold(0, |number, digit| number * 64 + digit))
+    }
+
+    /// Comment written as its length, then 5 characters for every 4 of its escaped text
+    fn comment(&mut self) -> Result<String, Box<dyn Error>> {
+        let length = self.next(2)?;
+        let mut escaped = String::new();
+        for _ in 0..length.div_ceil(4) {
+            let mut value = self.next(5)?;
+            for _ in 0..4 {
+                escaped.extend(COMMENT_CHARACTERS.chars().nth(value % 96));
+                value /= 96;
+            }
+        }
+        Ok(unescape(&escaped.chars().take(length).collect::<String>()))
+    }
+}
+
+/// Fumen of a single page showing `map`, with a comment if it isn't empty
+pub fn encode(map: &Map, comment: &str) -> String {
+    let mut field = [0u8; FIELD_CELLS];
+    let offset = TRUE_MAP_HEIGHT - FIELD_HEIGHT;
+    for (y, row) in map[offset..].iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            field[y * MAP_WIDTH + x] = match cell {
+                TetrominoType::E => 0,
+                TetrominoType::I => 1,
+                TetrominoType::L => 2,
+                TetrominoType::O => 3,
+                TetrominoType::Z => 4,
+                TetrominoType::T => 5,
+                TetrominoType::J => 6,
+                TetrominoType::S => 7,
+                TetrominoType::G => 8,
+            };
+        }
+    }
+
+    let mut data = String::new();
+    // Runs of cells changed by the same difference from the empty field, shifted by 8
+    let mut cell = 0;
+    while cell < FIELD_CELLS {
+        let count = field[cell..]
+            .iter()
+            .take_while(|block| **block == field[cell])
+            .count();
+        push_value(
+            &mut data,
+            (field[cell] as usize + 8) * FIELD_CELLS + count - 1,
+            2,
+        );
+        if field[cell] == 0 && count == FIELD_CELLS {
+            push_value(&mut data, 0, 1); // No next page repeats it
+        }
+        cell += count;
+    }
+
+    let mut flags = COLOR_FLAG;
+    if !comment.is_empty() {
+        flags |= COMMENT_FLAG;
+    }
+    push_value(&mut data, flags * 32 * FIELD_CELLS, 3);
+    if !comment.is_empty() {
+        let escaped: Vec<usize> = escape(comment)
+            .chars()
+            .filter_map(|c| COMMENT_CHARACTERS.find(c))
+            .collect();
+        push_value(&mut data, escaped.len(), 2);
+        for chunk in escaped.chunks(4) {
+            let value = chunk.iter().rev().fold(0, |value, c| value * 96 + c);
+            push_value(&mut data, value, 5);
+        }
+    }
+    format!("{VERSION}{data}")
+}
+
+/// Write `value` with `count` characters, lowest digit first
+fn push_value(data: &mut String, mut value: usize, count: usize) {
+    for _ in 0..count {
+        data.extend(ALPHABET.chars().nth(value % 64));
+        value /= 64;
+    }
+}
+
+/// Comment giving the hold and the tetrominoes coming, as read by `quiz`
+pub fn quiz_comment(hold: TetrominoType, queue: &[TetrominoType]) -> String {
+    let letter = |shape: TetrominoType| match shape {
+        TetrominoType::E | TetrominoType::G => String::new(),
+        shape => shape.letter().to_string(),
+    };
+    let mut comment = format!("{QUIZ}[{}]", letter(hold));
+    if let Some((current, nexts)) = queue.split_first() {
+        comment += &format!("({})", letter(*current));
+        comment.extend(nexts.iter().map(|next| next.letter()));
+    }
+    comment
+}
+
+/// Hold and tetrominoes coming of a `#Q=[hold](current)nexts` comment
+pub fn quiz(comment: &str) -> Option<(TetrominoType, Vec<TetrominoType>)> {
+    let quiz = comment.strip_prefix(QUIZ)?.trim();
+    let (hold, queue) = match quiz.strip_prefix('[') {
+        Some(quiz) => quiz.split_once(']')?,
+        None => ("", quiz),
+    };
+    let hold = hold.chars().next().map(TetrominoType::from_letter);
+    let queue = queue
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(TetrominoType::from_letter)
+        .filter(|shape| *shape != TetrominoType::E)
+        .collect();
+    Some((hold.unwrap_or(TetrominoType::E), queue))
+}
+
+/// JavaScript `escape`, comments being stored escaped
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '@' | '*' | '_' | '+' | '-' | '.' | '/' => {
+                escaped.push(c)
+            }
+            c if (c as u32) < 256 => escaped += &format!("%{:02X}", c as u32),
+            c => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    escaped += &format!("%u{unit:04X}");
+                }
+            }
+        }
+    }
+    escaped
+}
+
+fn unescape(escaped: &str) -> String {
+    let mut units = Vec::new();
+    let mut rest = escaped;
+    while let Some(c) = rest.chars().next() {
+        let (unit, length) = match (rest.strip_prefix("%u"), rest.strip_prefix('%')) {
+            (Some(hex), _) if hex.len() >= 4 => (u16::from_str_radix(&hex[..4], 16).ok(), 6),
+            (None, Some(hex)) if hex.len() >= 2 => (u16::from_str_radix(&hex[..2], 16).ok(), 3),
+            _ => (None, 0),
+        };
+        match unit {
+            Some(unit) => {
+                units.push(unit);
+                rest = &rest[length..];
+            }
+            None => {
+                units.extend(c.encode_utf16(&mut [0; 2]).iter());
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Blocks `(x, y)` of a fumen tetromino around its stored location, `y` going up
+fn blocks(piece: u8, rotation: usize) -> Result<[(i8, i8); 4], Box<dyn Error>> {
+    let spawn = match piece {
+        1 => [(0, 0), (-1, 0), (1, 0), (2, 0)],  // I
+        2 => [(0, 0), (-1, 0), (1, 0), (1, 1)],  // L
+        3 => [(0, 0), (1, 0), (0, 1), (1, 1)],   // O
+        4 => [(0, 0), (1, 0), (0, 1), (-1, 1)],  // Z
+        5 => [(0, 0), (-1, 0), (1, 0), (0, 1)],  // T
+        6 => [(0, 0), (-1, 0), (1, 0), (-1, 1)], // J
+        7 => [(0, 0), (-1, 0), (0, 1), (1, 1)],  // S
+        _ => return Err("fumen garbage cannot be a tetromino".into()),
+    };
+    // Fumen stores the O, I, S and Z at the same location for the states covering the same
+    // cells, while the blocks turn around the SRS center: it is moved to that center
+    let (dx, dy) = match (piece, rotation) {
+        (3, 0) | (1, 0) | (4, 3) => (1, 0),
+        (3, 2) | (4, 2) | (7, 2) | (1, 3) => (0, -1),
+        (3, 3) => (1, -1),
+        (7, 1) => (-1, 0),
+        _ => (0, 0),
+    };
+    // Rotations are numbered from the upside down one, clockwise
+    Ok(spawn.map(|(x, y)| match rotation {
+        0 => (dx - x, dy - y),
+        1 => (dx + y, dy - x),
+        2 => (dx + x, dy + y),
+        _ => (dx - y, dy + x),
+    }))
+}
+
+fn clear_lines(field: &mut [u8; FIELD_CELLS]) {
+    let rows: Vec<[u8; MAP_WIDTH]> = field[..FIELD_HEIGHT * MAP_WIDTH]
+        .chunks(MAP_WIDTH)
+        .filter(|row| row.contains(&0))
+        .map(|row| row.try_into().unwrap_or([0; MAP_WIDTH]))
+        .collect();
+    let cleared = FIELD_HEIGHT - rows.len();
+    field[..FIELD_HEIGHT * MAP_WIDTH].fill(0);
+    for (y, row) in rows.iter().enumerate() {
+        let start = (y + cleared) * MAP_WIDTH;
+        field[start..start + MAP_WIDTH].copy_from_slice(row);
+    }
+}
+
+/// Field rows aligned on the bottom of the map, which has one more hidden row
+fn to_map(field: &[u8; FIELD_CELLS]) -> Result<Map, Box<dyn Error>> {
+    let mut map = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+    let offset = TRUE_MAP_HEIGHT - FIELD_HEIGHT;
+    for (y, row) in field[..FIELD_HEIGHT * MAP_WIDTH]
+        .chunks(MAP_WIDTH)
+        .enumerate()
+    {
+        for (x, block) in row.iter().enumerate() {
+            map[y + offset][x] = match block {
+                0 => TetrominoType::E,
+                1 => TetrominoType::I,
+                2 => TetrominoType::L,
+                3 => TetrominoType::O,
+                4 => TetrominoType::Z,
+                5 => TetrominoType::T,
+                6 => TetrominoType::J,
+                7 => TetrominoType::S,
+                8 => TetrominoType::G,
+                _ => return Err("invalid fumen block".into()),
+            };
+        }
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY: &str = "v115@vhAAgH";
+
+    /// Map of `rows`, the last one on the floor, `.` for empty cells
+    fn map(rows: &[&str]) -> Map {
+        let mut map = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+        let top = TRUE_MAP_HEIGHT - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, letter) in row.chars().enumerate() {
+                map[top + y][x] = TetrominoType::from_letter(letter);
+            }
+        }
+        map
+    }
+
+    /// Fumen of an empty field with `piece` drawn in at column `x` of field row `row`
+    fn piece_page(piece: u8, rotation: usize, x: usize, row: usize) -> String {
+        // The empty field, without its page value
+        let mut data = EMPTY[..EMPTY.len() - 3].to_string();
+        let location = row * MAP_WIDTH + x;
+        let value = piece as usize + 8 * rotation + 32 * location;
+        push_value(&mut data, value + COLOR_FLAG * 32 * FIELD_CELLS, 3);
+        data
+    }
+
+    #[test]
+    fn empty_field() {
+        let pages = decode(EMPTY).unwrap();
+        assert_eq!(
+            pages,
+            [Page {
+                map: map(&[]),
+                comment: String::new()
+            }]
+        );
+        assert_eq!(encode(&map(&[]), ""), EMPTY);
+        // Links work as well as the data alone
+        assert_eq!(
+            decode(&format!("https://fumen.zui.jp/?{EMPTY}")).unwrap(),
+            pages
+        );
+    }
+
+    #[test]
+    fn pco_opener() {
+        let pages = decode("v115@9gRpCeQ4DeRpBtAeR4CeilBtg0Q4Ceglzhi0LeAgH").unwrap();
+        let expected = map(&["OO...S....", "OOZZ.SS...", "LLLZZJS...", "LIIIIJJJ.."]);
+        assert_eq!(pages.last().unwrap().map, expected);
+    }
+
+    #[test]
+    fn round_trip() {
+        let board = map(&["......T...", "GGGG.TTIII", "ZZ.OO.SSGJ", "LZZOOSSJJJ"]);
+        for comment in ["", "#Q=[T](I)LOSZJ", "100% = ok?", "été ⊂ 世界"] {
+            let pages = decode(&encode(&board, comment)).unwrap();
+            assert_eq!(
+                pages,
+                [Page {
+                    map: board,
+                    comment: comment.to_string()
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn quiz_comments() {
+        let (hold, queue) = (TetrominoType::T, [TetrominoType::I, TetrominoType::O]);
+        let comment = quiz_comment(hold, &queue);
+        assert_eq!(comment, "#Q=[T](I)O");
+        assert_eq!(quiz(&comment), Some((hold, queue.to_vec())));
+        assert_eq!(
+            quiz("#Q=(S)Z"),
+            Some((TetrominoType::E, vec![TetrominoType::S, TetrominoType::Z]))
+        );
+        assert_eq!(quiz("no quiz"), None);
+    }
+
+    /// Piece, rotations, field row of its location in column 4, rows drawn down to the floor
+    type Case = (u8, [usize; 2], usize, &'static [&'static str]);
+
+    #[test]
+    fn pieces_cover_the_same_cells_in_both_symmetric_states() {
+        const I: &[&str] = &["....I.....", "....I.....", "....I.....", "....I....."];
+        let cases: [Case; 8] = [
+            (3, [2, 0], 21, &["....OO....", "....OO...."]),
+            (3, [1, 3], 21, &["....OO....", "....OO...."]),
+            (1, [2, 0], 22, &["...IIII..."]),
+            (1, [1, 3], 20, I),
+            (7, [2, 0], 21, &["....SS....", "...SS....."]),
+            (7, [3, 1], 21, &["...S......", "...SS.....", "....S....."]),
+            (4, [2, 0], 21, &["...ZZ.....", "....ZZ...."]),
+            (4, [1, 3], 21, &[".....Z....", "....ZZ....", "....Z....."]),
+        ];
+        for (piece, rotations, row, rows) in cases {
+            for rotation in rotations {
+                let page = &decode(&piece_page(piece, rotation, 4, row)).unwrap()[0];
+                assert_eq!(page.map, map(rows), "piece {piece} rotation {rotation}");
+            }
+        }
+    }
+
+    #[test]
+    fn other_pieces_turn_around_their_center() {
+        let cases: [(u8, usize, &[&str]); 4] = [
+            (5, 2, &["....T.....", "...TTT....", ".........."]),
+            (5, 0, &["...TTT....", "....T....."]),
+            (2, 1, &["....L.....", "....L.....", "....LL...."]),
+            (6, 3, &["....J.....", "....J.....", "...JJ....."]),
+        ];
+        for (piece, rotation, rows) in cases {
+            let page = &decode(&piece_page(piece, rotation, 4, 21)).unwrap()[0];
+            assert_eq!(page.map, map(rows), "piece {piece} rotation {rotation}");
+        }
+    }
+
+    #[test]
+    fn refuse_invalid_data() {
+        assert!(decode("v110@vhAAgH").is_err());
+        assert!(decode("v115@vhAAg").is_err());
+        assert!(decode("v115@vh!AgH").is_err());
+        // A tetromino across the wall
+        assert!(decode(&piece_page(1, 2, 9, 22)).is_err());
+    }
+}
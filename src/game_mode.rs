@@ -0,0 +1,236 @@
+use crate::garbage::{DEFAULT_CHANGE_PROBABILITY, HolePattern};
+use crate::master;
+use crate::opener::{self, Opener};
+use crate::rotation_system::RotationSystem;
+use crate::tetris::{FPS, LOCK_DELAY, MAP_HEIGHT};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Gravity of 20G, the fastest of the curves: on the standard board the tetromino falls
+/// through the whole playfield in a single frame
+const MAX_GRAVITY: f64 = MAP_HEIGHT as f64;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GameMode {
+    Endless,
+    Marathon150,
+    Marathon200,
+    Master,
+    Survival,
+    CheeseRace10,
+    CheeseRace18,
+    CheeseRace100,
+    /// Empty board where each tetromino has to be placed on a target with the fewest presses
+    FinesseTraining,
+    /// The first bag is dealt in the order of an opener, which has to be built as expected
+    OpenerTki,
+    OpenerPco,
+    /// Handcrafted boards and queues, picked from the puzzle directory
+    Puzzle,
+    Versus,
+    VersusCpu,
+    /// Declared last for the modes before to keep their index in the bincode state dumps
+    OpenerDt,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Delays {
+    /// Ticks between a lock and the next spawn (ARE)
+    pub are: usize,
+    /// ARE used after a line clear instead of `are`
+    pub line_are: usize,
+    /// Ticks the tetromino can stay on the ground before locking
+    pub lock: usize,
+    /// Ticks during which cleared lines stay empty before the stack falls
+    pub line_clear: usize,
+}
+
+/// Options chosen by the player instead of the ones of the game mode
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameSettings {
+    pub are: Option<usize>,
+    pub line_clear: Option<usize>,
+    pub garbage_pattern: Option<HolePattern>,
+    pub garbage_change_probability: Option<f64>,
+    /// Columns of the board
+    pub width: Option<usize>,
+    /// Visible rows of the board
+    pub height: Option<usize>,
+    /// Rotation system played instead of the one of the game mode
+    pub rotation_system: Option<RotationSystem>,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 15] = [
+        GameMode::Marathon150,
+        GameMode::Marathon200,
+        GameMode::Endless,
+        GameMode::Master,
+        GameMode::Survival,
+        GameMode::CheeseRace10,
+        GameMode::CheeseRace18,
+        GameMode::CheeseRace100,
+        GameMode::FinesseTraining,
+        GameMode::OpenerTki,
+        GameMode::OpenerPco,
+        GameMode::OpenerDt,
+        GameMode::Puzzle,
+        GameMode::Versus,
+        GameMode::VersusCpu,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "Endless",
+            GameMode::Marathon150 => "Marathon (150 lines)",
+            GameMode::Marathon200 => "Marathon (200 lines)",
+            GameMode::Master => "Master (20G)",
+            GameMode::Survival => "Survival",
+            GameMode::CheeseRace10 => "Cheese race (10 lines)",
+            GameMode::CheeseRace18 => "Cheese race (18 lines)",
+            GameMode::CheeseRace100 => "Cheese race (100 lines)",
+            GameMode::FinesseTraining => "Finesse training",
+            GameMode::OpenerTki => "Opener (TKI)",
+            GameMode::OpenerPco => "Opener (PCO)",
+            GameMode::OpenerDt => "Opener (DT cannon)",
+            GameMode::Puzzle => "Puzzles",
+            GameMode::Versus => "Versus (local)",
+            GameMode::VersusCpu => "Versus (CPU)",
+        }
+    }
+
+    pub fn rotation_system(&self) -> RotationSystem {
+        match self {
+            GameMode::Master => RotationSystem::Ars,
+            _ => RotationSystem::Srs,
+        }
+    }
+
+    /// Number of cleared lines needed to win, `None` if the game never ends by itself
+    pub fn line_goal(&self) -> Option<usize> {
+        match self {
+            GameMode::Marathon150 => Some(150),
+            GameMode::Marathon200 => Some(200),
+            _ => None,
+        }
+    }
+
+    /// Number of garbage lines to dig through to win
+    pub fn cheese_lines(&self) -> Option<usize> {
+        match self {
+            GameMode::CheeseRace10 => Some(10),
+            GameMode::CheeseRace18 => Some(18),
+            GameMode::CheeseRace100 => Some(100),
+            _ => None,
+        }
+    }
+
+    /// Opener practiced from the start of the game
+    pub fn opener(&self) -> Option<&'static Opener> {
+        match self {
+            GameMode::OpenerTki => Some(opener::built_in("tki")),
+            GameMode::OpenerPco => Some(opener::built_in("pco")),
+            GameMode::OpenerDt => Some(opener::built_in("dt")),
+            _ => None,
+        }
+    }
+
+    /// Practice modes, where placements can be undone. Never the ranked ones, whose scores
+    /// and times have to be played in one go.
+    pub fn can_undo(&self) -> bool {
+        matches!(
+            self,
+            GameMode::FinesseTraining
+                | GameMode::OpenerTki
+                | GameMode::OpenerPco
+                | GameMode::OpenerDt
+                | GameMode::Puzzle
+        )
+    }
+
+    /// Modes played on a board of any size. Not the ones built around the standard board,
+    /// whose targets and setups would not fit another one.
+    pub fn is_resizable(&self) -> bool {
+        !matches!(
+            self,
+            GameMode::FinesseTraining
+                | GameMode::OpenerTki
+                | GameMode::OpenerPco
+                | GameMode::OpenerDt
+                | GameMode::Puzzle
+        )
+    }
+
+    /// Highest reachable level, 0 based like `Tetris::level`
+    pub fn level_cap(&self) -> Option<usize> {
+        self.line_goal().map(|goal| goal / 10 - 1)
+    }
+
+    /// Gravity in cells per frame for a 0 based level on a board of `height` rows, following
+    /// the guideline curve:
+    ///      (0.8 - (level * 0.007)).powf(level) SECONDS per row
+    /// 20G drops the tetromino through the whole board in a single frame, whatever its height.
+    pub fn gravity(&self, level: usize, height: usize) -> f64 {
+        let gravity = if *self == GameMode::Master {
+            master::gravity(level)
+        } else {
+            let seconds_per_row = (0.8 - (level as f64 * 0.007)).powf(level as f64);
+            if !seconds_per_row.is_finite() || seconds_per_row <= 0.0 {
+                MAX_GRAVITY
+            } else {
+                1.0 / (seconds_per_row * FPS as f64)
+            }
+        };
+        if gravity >= MAX_GRAVITY {
+            return height as f64;
+        }
+        gravity.min(height as f64)
+    }
+
+    pub fn delays(&self, level: usize) -> Delays {
+        match self {
+            GameMode::Master => master::delays(level),
+            _ => Delays {
+                are: 0,
+                line_are: 0,
+                lock: LOCK_DELAY,
+                line_clear: 0,
+            },
+        }
+    }
+
+    pub fn garbage_pattern(&self) -> (HolePattern, f64) {
+        match self {
+            GameMode::Survival => (HolePattern::Messy, DEFAULT_CHANGE_PROBABILITY),
+            GameMode::CheeseRace10 | GameMode::CheeseRace18 | GameMode::CheeseRace100 => {
+                (HolePattern::Cheese, 1.0)
+            }
+            _ => (HolePattern::Clean, DEFAULT_CHANGE_PROBABILITY),
+        }
+    }
+
+    /// Ticks between two garbage lines sent by the game itself
+    pub fn garbage_interval(&self) -> Option<usize> {
+        match self {
+            GameMode::Survival => Some(4 * FPS),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::TRUE_MAP_HEIGHT;
+
+    #[test]
+    fn top_gravity_drops_through_any_board() {
+        for height in [8, TRUE_MAP_HEIGHT, 64] {
+            assert_eq!(GameMode::Marathon150.gravity(100, height), height as f64);
+            assert_eq!(GameMode::Master.gravity(500, height), height as f64);
+        }
+        assert!(GameMode::Marathon150.gravity(0, 64) < 1.0);
+    }
+}
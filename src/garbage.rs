@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::tetris::MAP_WIDTH;
+
+/// Chance for the hole to move, used unless the game mode asks for something else
+pub const DEFAULT_CHANGE_PROBABILITY: f64 = 0.3;
+
+/// How the hole column of garbage lines changes
+#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HolePattern {
+    /// Every line of an attack shares the same hole, which may move between attacks
+    Clean,
+    /// The hole may move on every line
+    Messy,
+    /// Every line has its hole in a different column than the line above
+    Cheese,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GarbageQueue {
+    pattern: HolePattern,
+    change_probability: f64,
+    pending: VecDeque<usize>, // Lines of each received attack, oldest first
+    width: usize,             // Columns of the board the lines rise in
+    hole: usize,
+    rng: ChaCha12Rng,
+}
+
+impl GarbageQueue {
+    pub fn new(pattern: HolePattern, change_probability: f64, seed: u64) -> Self {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        Self {
+            pattern,
+            change_probability: change_probability.clamp(0.0, 1.0),
+            pending: VecDeque::new(),
+            width: MAP_WIDTH,
+            hole: rng.random_range(0..MAP_WIDTH),
+            rng,
+        }
+    }
+
+    pub fn set_pattern(&mut self, pattern: HolePattern, change_probability: f64) {
+        self.pattern = pattern;
+        self.change_probability = change_probability.clamp(0.0, 1.0);
+    }
+
+    /// Make the lines as wide as a board of `width` columns
+    pub fn set_width(&mut self, width: usize) {
+        self.width = width;
+        self.hole %= width;
+    }
+
+    pub fn queue(&mut self, lines: usize) {
+        if lines > 0 {
+            self.pending.push_back(lines);
+        }
+    }
+
+    /// Cancel pending garbage with an outgoing attack, oldest garbage first.
+    /// Returns what is left of the attack once nothing is pending anymore.
+    pub fn cancel(&mut self, mut attack: usize) -> usize {
+        while attack > 0 {
+            let Some(lines) = self.pending.front_mut() else {
+                break;
+            };
+            let cancelled = attack.min(*lines);
+            *lines -= cancelled;
+            attack -= cancelled;
+            if *lines == 0 {
+                self.pending.pop_front();
+            }
+        }
+        attack
+    }
+
+    /// Total of garbage lines waiting to be inserted
+    pub fn pending(&self) -> usize {
+        self.pending.iter().sum()
+    }
+
+    /// Empty the queue, returning the hole column of each line to insert from top to bottom
+    pub fn take_holes(&mut self) -> Vec<usize> {
+        let attacks: Vec<usize> = self.pending.drain(..).collect();
+        let mut holes = Vec::new();
+        for lines in attacks {
+            holes.append(&mut self.holes(lines));
+        }
+        holes
+    }
+
+    /// Hole columns for `lines` new garbage lines, from top to bottom
+    pub fn holes(&mut self, lines: usize) -> Vec<usize> {
+        let mut holes = Vec::with_capacity(lines);
+
+        if self.pattern == HolePattern::Clean && self.rng.random_bool(self.change_probability) {
+            self.move_hole();
+        }
+        for _ in 0..lines {
+            match self.pattern {
+                HolePattern::Clean => {}
+                HolePattern::Messy => {
+                    if self.rng.random_bool(self.change_probability) {
+                        self.move_hole();
+                    }
+                }
+                HolePattern::Cheese => self.move_hole(),
+            }
+            holes.push(self.hole);
+        }
+        holes
+    }
+
+    fn move_hole(&mut self) {
+        let shift = self.rng.random_range(1..self.width);
+        self.hole = (self.hole + shift) % self.width;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_the_oldest_attacks_first() {
+        let mut garbage = GarbageQueue::new(HolePattern::Clean, 0.0, 1);
+        garbage.queue(2);
+        garbage.queue(0);
+        garbage.queue(3);
+        assert_eq!(garbage.cancel(1), 0);
+        assert_eq!(garbage.pending, [1, 3]);
+        assert_eq!(garbage.cancel(2), 0);
+        assert_eq!(garbage.pending, [2]);
+        // What is left once nothing is pending is sent
+        assert_eq!(garbage.cancel(5), 3);
+        assert_eq!(garbage.pending(), 0);
+        assert_eq!(garbage.cancel(4), 4);
+    }
+
+    #[test]
+    fn holes_follow_the_pattern() {
+        let mut clean = GarbageQueue::new(HolePattern::Clean, 0.0, 1);
+        clean.queue(2);
+        clean.queue(3);
+        let holes = clean.take_holes();
+        assert_eq!(holes.len(), 5);
+        assert!(holes.iter().all(|hole| *hole == holes[0]));
+        assert_eq!(clean.pending(), 0);
+
+        // Certain to move, a clean hole moves between attacks and a messy one on every line
+        let mut clean = GarbageQueue::new(HolePattern::Clean, 1.0, 1);
+        let (first, second) = (clean.holes(3), clean.holes(3));
+        assert!(first.iter().all(|hole| *hole == first[0]));
+        assert!(second.iter().all(|hole| *hole == second[0]));
+        assert_ne!(first[0], second[0]);
+        let mut messy = GarbageQueue::new(HolePattern::Messy, 1.0, 1);
+        let holes = messy.holes(20);
+        assert!(holes.windows(2).all(|pair| pair[0] != pair[1]));
+        let mut messy = GarbageQueue::new(HolePattern::Messy, 0.0, 1);
+        let holes = messy.holes(20);
+        assert!(holes.iter().all(|hole| *hole == holes[0]));
+
+        let mut cheese = GarbageQueue::new(HolePattern::Cheese, 0.0, 1);
+        cheese.set_width(4);
+        let holes = cheese.holes(20);
+        assert!(holes.iter().all(|hole| *hole < 4));
+        assert!(holes.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+}
@@ -0,0 +1,58 @@
+//! Undo and redo of placements: a copy of the game is kept every time a tetromino spawns
+//! after a lock, to go back to any of them and forward again.
+
+use crate::tetris::Tetris;
+
+pub struct History {
+    games: Vec<Tetris>, // Game at each spawn, the first one before any lock
+    position: usize,    // Game the player is at
+}
+
+impl History {
+    pub fn new(game: &Tetris) -> Self {
+        Self {
+            games: vec![game.clone()],
+            position: 0,
+        }
+    }
+
+    /// Keep `game` once its next tetromino spawns after a lock, forgetting the placements
+    /// undone before
+    pub fn update(&mut self, game: &Tetris) {
+        if !game.is_falling() || game.locked_pieces() == self.games[self.position].locked_pieces() {
+            return;
+        }
+        self.games.truncate(self.position + 1);
+        self.games.push(game.clone());
+        self.position += 1;
+    }
+
+    /// Go back to the spawn of the tetromino locked last, returns whether there was one
+    pub fn undo(&mut self, game: &mut Tetris) -> bool {
+        if game.locked_pieces() != self.games[self.position].locked_pieces() {
+            // Locked without anything spawning since, as when the lock ended the game
+            self.games.truncate(self.position + 1);
+        } else if self.position == 0 {
+            return false;
+        } else {
+            self.position -= 1;
+        }
+        *game = self.games[self.position].clone();
+        true
+    }
+
+    /// Play again the last placement undone, returns whether there was one
+    pub fn redo(&mut self, game: &mut Tetris) -> bool {
+        if self.position + 1 >= self.games.len() {
+            return false;
+        }
+        self.position += 1;
+        *game = self.games[self.position].clone();
+        true
+    }
+
+    /// Forget every placement, `game` having started over
+    pub fn reset(&mut self, game: &Tetris) {
+        *self = Self::new(game);
+    }
+}
@@ -1,27 +1,251 @@
+mod action;
+mod ai;
+mod attack;
+mod board;
 mod display;
+mod editor;
+mod finesse;
+mod fnv;
+mod fumen;
+mod game_mode;
+mod garbage;
+mod history;
+mod master;
 mod menu;
+mod net;
+mod opener;
+mod pc;
+mod placement;
+mod puzzle;
+mod rotation_system;
+#[cfg(feature = "serde")]
+mod save;
+mod server;
+mod setup;
+#[cfg(feature = "serde")]
+mod state;
+#[cfg(feature = "serde")]
+mod tbp;
 mod tetris;
 mod tetromino;
 mod tetromino_type;
+mod toml;
+mod versus;
 
 use std::error::Error;
+use std::path::PathBuf;
 use std::time::Duration;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+use crate::ai::{Bot, Difficulty};
+use crate::display::crossterm::Config;
+use crate::game_mode::GameSettings;
+use crate::garbage::HolePattern;
+use crate::net::spectate::{Spectator, Spectators};
+use crate::rotation_system::RotationSystem;
 
 /// Demo
 #[derive(Debug, Parser)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// time in ms between two ticks.
     /// will maybe be removed as user shouldn't be able to edit it
     #[arg(short, long, default_value_t = 16)] //62.5 fps
     tick_rate: u64,
+
+    /// ticks between a lock and the next spawn (ARE), defaults to the game mode one
+    #[arg(long)]
+    are: Option<usize>,
+
+    /// ticks during which cleared lines are animated before the stack falls,
+    /// defaults to the game mode one
+    #[arg(long)]
+    line_clear_delay: Option<usize>,
+
+    /// how the hole of received garbage lines moves, defaults to the game mode one
+    #[arg(long, value_enum)]
+    garbage_pattern: Option<HolePattern>,
+
+    /// chance between 0 and 1 for the garbage hole to move, defaults to the game mode one
+    #[arg(long)]
+    garbage_change_probability: Option<f64>,
+
+    /// columns of the board, from 4 for combo drills to 32, ignored by finesse training,
+    /// openers and puzzles
+    #[arg(long, value_parser = clap::value_parser!(u8).range(4..=32))]
+    width: Option<u8>,
+
+    /// visible rows of the board, ignored by finesse training, openers and puzzles
+    #[arg(long, value_parser = clap::value_parser!(u8).range(4..=60))]
+    height: Option<u8>,
+
+    /// rotation system of every game, defaults to the game mode one (ARS in Master, SRS
+    /// everywhere else)
+    #[arg(long, value_enum)]
+    rotation_system: Option<RotationSystem>,
+
+    /// TOML file of a custom rotation system played instead, see the README for its format
+    #[arg(long, value_name = "FILE", conflicts_with = "rotation_system")]
+    rotation_file: Option<PathBuf>,
+
+    /// number of rounds of a versus match, the first player winning most of them wins the match
+    #[arg(long, default_value_t = 3)]
+    best_of: usize,
+
+    /// how well the computer plays in versus
+    #[arg(long, value_enum, default_value_t = Difficulty::Medium)]
+    cpu_difficulty: Difficulty,
+
+    /// pieces per second placed by the computer, defaults to the difficulty one
+    #[arg(long)]
+    cpu_pps: Option<f64>,
+
+    /// command starting an external bot speaking the Tetris Bot Protocol, played instead of
+    /// the built-in computer, e.g. `--external-bot cold-clear-2`
+    #[arg(long, value_name = "COMMAND")]
+    external_bot: Option<String>,
+
+    /// the computer plays the solo games chosen in the menu, Esc to stop watching it
+    #[arg(long)]
+    demo: bool,
+
+    /// show where to put each tetromino for a perfect clear within 4 lines, when the next
+    /// tetrominoes allow one
+    #[arg(long)]
+    pc_hints: bool,
+
+    /// directory of the puzzle files, where the stars earned are saved too
+    #[arg(long, value_name = "DIR", default_value = puzzle::DEFAULT_DIRECTORY)]
+    puzzles: PathBuf,
+
+    /// file where the solo game in progress is saved, to be continued from the menu
+    #[cfg(feature = "serde")]
+    #[arg(long, value_name = "FILE", default_value = save::DEFAULT_FILE)]
+    save: PathBuf,
+
+    /// let other terminals watch the games with the spectate subcommand, on this port or 7879
+    #[arg(
+        long,
+        global = true,
+        value_name = "PORT",
+        num_args = 0..=1,
+        default_missing_value = "7879"
+    )]
+    spectators: Option<u16>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// wait for another terminal to join a versus match over the network
+    Host {
+        #[arg(short, long, default_value_t = net::DEFAULT_PORT)]
+        port: u16,
+    },
+    /// join a versus match hosted on another terminal, at `address` or `address:port`
+    Join { address: String },
+    /// serve games to telnet clients, with a lobby where they can challenge each other
+    Serve {
+        #[arg(short, long, default_value_t = server::DEFAULT_PORT)]
+        port: u16,
+    },
+    /// watch the games of a terminal started with --spectators, at `address` or `address:port`
+    Spectate {
+        #[arg(default_value = "localhost")]
+        address: String,
+    },
+    /// print the perfect clears of the first page of a fumen, the tetrominoes of `queue`
+    /// coming in order, e.g. `solve v115@... TILJSZO`
+    Solve {
+        fumen: String,
+        queue: String,
+        /// tetromino already held
+        #[arg(long)]
+        hold: Option<char>,
+        /// lines the perfect clear has to fit in
+        #[arg(long, default_value_t = pc::DEFAULT_HEIGHT)]
+        height: usize,
+        /// most solutions printed
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
     let tick_rate = Duration::from_millis(cli.tick_rate);
+    let rotation_system = match &cli.rotation_file {
+        Some(path) => Some(RotationSystem::load(path)?),
+        None => cli.rotation_system,
+    };
+    let settings = GameSettings {
+        are: cli.are,
+        line_clear: cli.line_clear_delay,
+        garbage_pattern: cli.garbage_pattern,
+        garbage_change_probability: cli.garbage_change_probability,
+        width: cli.width.map(usize::from),
+        height: cli.height.map(usize::from),
+        rotation_system,
+    };
+    let is_server = matches!(cli.command, Some(Command::Serve { .. }));
+
+    let spectators = match cli.spectators {
+        // Every client of the server plays its own games, there would be no single one to watch
+        Some(port) if !is_server => Some(Spectators::listen(port)?),
+        _ => None,
+    };
+    let config = Config {
+        tick_rate,
+        settings,
+        best_of: cli.best_of,
+        bot: Bot::new(cli.cpu_difficulty, cli.cpu_pps),
+        external_bot: cli.external_bot,
+        demo: cli.demo,
+        pc_hints: cli.pc_hints,
+        puzzles: cli.puzzles,
+        // Telnet clients would read and write the files of the server
+        files: !is_server,
+        #[cfg(feature = "serde")]
+        save: (!is_server).then_some(cli.save),
+        spectators: spectators.clone(),
+    };
+
+    match cli.command {
+        Some(Command::Host { port }) => {
+            println!("Waiting for a player to join on port {port}...");
+            let (connection, lockstep) = net::host(port, cli.best_of, settings)?;
+            display::crossterm::run_online(tick_rate, connection, lockstep, spectators)?;
+            return Ok(());
+        }
+        Some(Command::Join { address }) => {
+            let (connection, lockstep) = net::join(&address)?;
+            display::crossterm::run_online(tick_rate, connection, lockstep, spectators)?;
+            return Ok(());
+        }
+        Some(Command::Serve { port }) => {
+            server::serve(port, config)?;
+            return Ok(());
+        }
+        Some(Command::Spectate { address }) => {
+            display::crossterm::run_spectator(Spectator::connect(&address)?)?;
+            return Ok(());
+        }
+        Some(Command::Solve {
+            fumen,
+            queue,
+            hold,
+            height,
+            limit,
+        }) => {
+            let rotation_system = rotation_system.unwrap_or(RotationSystem::Srs);
+            pc::print_solutions(&fumen, &queue, hold, rotation_system, height, limit)?;
+            return Ok(());
+        }
+        None => {}
+    }
 
-    display::crossterm::run(tick_rate)?;
+    display::crossterm::run(&config)?;
     Ok(())
 }
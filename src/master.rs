@@ -0,0 +1,169 @@
+//! Tables of the 20G Master mode, modelled on Tetris The Grand Master.
+//! Levels here are TGM internal levels, going from 0 to 999.
+
+use crate::game_mode::Delays;
+use crate::tetris::FPS;
+
+pub const MAX_LEVEL: usize = 999;
+pub const SECTION_LENGTH: usize = 100;
+
+/// Internal gravity is expressed in 1/256 of cell per frame
+const GRAVITY_DENOMINATOR: f64 = 256.0;
+
+// (from level, internal gravity)
+const GRAVITY: [(usize, usize); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+// (from level, delays)
+const DELAYS: [(usize, Delays); 6] = [
+    (
+        0,
+        Delays {
+            are: 25,
+            line_are: 25,
+            lock: 30,
+            line_clear: 40,
+        },
+    ),
+    (
+        500,
+        Delays {
+            are: 25,
+            line_are: 25,
+            lock: 30,
+            line_clear: 25,
+        },
+    ),
+    (
+        600,
+        Delays {
+            are: 25,
+            line_are: 16,
+            lock: 30,
+            line_clear: 16,
+        },
+    ),
+    (
+        700,
+        Delays {
+            are: 16,
+            line_are: 12,
+            lock: 30,
+            line_clear: 12,
+        },
+    ),
+    (
+        800,
+        Delays {
+            are: 12,
+            line_are: 6,
+            lock: 30,
+            line_clear: 6,
+        },
+    ),
+    (
+        900,
+        Delays {
+            are: 12,
+            line_are: 6,
+            lock: 17,
+            line_clear: 6,
+        },
+    ),
+];
+
+// (minimum score, grade)
+const GRADES: [(usize, &str); 18] = [
+    (0, "9"),
+    (400, "8"),
+    (800, "7"),
+    (1400, "6"),
+    (2000, "5"),
+    (3500, "4"),
+    (5500, "3"),
+    (8000, "2"),
+    (12000, "1"),
+    (16000, "S1"),
+    (22000, "S2"),
+    (30000, "S3"),
+    (40000, "S4"),
+    (52000, "S5"),
+    (66000, "S6"),
+    (82000, "S7"),
+    (100000, "S8"),
+    (120000, "S9"),
+];
+
+// (level, minimum score, maximum time in ticks) to keep a chance at the GM grade
+pub const GM_REQUIREMENTS: [(usize, usize, usize); 3] = [
+    (300, 12000, (4 * 60 + 15) * FPS),
+    (500, 40000, (7 * 60 + 30) * FPS),
+    (MAX_LEVEL, 126000, (13 * 60 + 30) * FPS),
+];
+
+fn lookup<T: Copy>(table: &[(usize, T)], level: usize) -> T {
+    table
+        .iter()
+        .rev()
+        .find(|(from, _)| *from <= level)
+        .map(|(_, value)| *value)
+        .unwrap_or(table[0].1)
+}
+
+/// Gravity in cells per frame
+pub fn gravity(level: usize) -> f64 {
+    lookup(&GRAVITY, level) as f64 / GRAVITY_DENOMINATOR
+}
+
+pub fn delays(level: usize) -> Delays {
+    lookup(&DELAYS, level)
+}
+
+pub fn grade(score: usize) -> &'static str {
+    lookup(&GRADES, score)
+}
+
+/// The level can't go past a section stop (x99 and 998) by spawning pieces, only by clearing lines
+pub fn is_section_stop(level: usize) -> bool {
+    level % SECTION_LENGTH == SECTION_LENGTH - 1 || level == MAX_LEVEL - 1
+}
+
+/// Level the player has to reach to finish the current section
+pub fn section_limit(level: usize) -> usize {
+    ((level / SECTION_LENGTH + 1) * SECTION_LENGTH).min(MAX_LEVEL)
+}
+
+/// TGM scoring, `level` being the level before the cleared lines are counted
+pub fn score(level: usize, lines: usize, soft_drop: usize, combo: usize, bravo: bool) -> usize {
+    ((level + lines).div_ceil(4) + soft_drop) * lines * combo * if bravo { 4 } else { 1 }
+}
@@ -1,9 +1,14 @@
-pub const OPTION_LEN: usize = 3;
+use crate::game_mode::GameMode;
+
+pub const OPTION_LEN: usize = 5;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum Options {
     None,
+    /// The saved game, only when there is one
+    Continue,
     New,
+    Editor,
     Scores,
     Quit,
 }
@@ -12,7 +17,9 @@ impl Options {
     pub fn as_str(&self) -> &'static str {
         match self {
             Options::None => "NONE",
+            Options::Continue => "Continue",
             Options::New => "New Game",
+            Options::Editor => "Board Editor",
             Options::Scores => "Scores (WIP)",
             Options::Quit => "Quit",
         }
@@ -23,20 +30,42 @@ pub struct Menu {
     options: [Options; OPTION_LEN],
     hovered: Options,
     selected: Options,
+    hovered_mode: usize,
+    mode: Option<GameMode>,
+    can_continue: bool,
 }
 
 impl Menu {
-    pub fn new() -> Menu {
+    pub fn new(can_continue: bool) -> Menu {
         Self {
-            hovered: Options::New,
+            hovered: if can_continue {
+                Options::Continue
+            } else {
+                Options::New
+            },
             selected: Options::None,
-            options: [Options::New, Options::Scores, Options::Quit],
+            options: [
+                Options::Continue,
+                Options::New,
+                Options::Editor,
+                Options::Scores,
+                Options::Quit,
+            ],
+            hovered_mode: 0,
+            mode: None,
+            can_continue,
         }
     }
 
     pub fn move_down(&mut self) {
+        if self.selected == Options::New {
+            self.hovered_mode = (self.hovered_mode + 1).min(GameMode::ALL.len() - 1);
+            return;
+        }
         match self.hovered {
-            Options::New => self.hovered = Options::Scores,
+            Options::Continue => self.hovered = Options::New,
+            Options::New => self.hovered = Options::Editor,
+            Options::Editor => self.hovered = Options::Scores,
             Options::Scores => self.hovered = Options::Quit,
             Options::Quit => {}
             Options::None => panic!("You shouldn't hover NONE"),
@@ -44,21 +73,32 @@ impl Menu {
     }
 
     pub fn move_up(&mut self) {
+        if self.selected == Options::New {
+            self.hovered_mode = self.hovered_mode.saturating_sub(1);
+            return;
+        }
         match self.hovered {
+            Options::Continue => {}
+            Options::New if self.can_continue => self.hovered = Options::Continue,
             Options::New => {}
-            Options::Scores => self.hovered = Options::New,
+            Options::Editor => self.hovered = Options::New,
+            Options::Scores => self.hovered = Options::Editor,
             Options::Quit => self.hovered = Options::Scores,
             Options::None => panic!("You shouldn't hover NONE"),
         };
     }
 
     pub fn select(&mut self) {
+        if self.selected == Options::New {
+            self.mode = Some(GameMode::ALL[self.hovered_mode]);
+            return;
+        }
         self.selected = self.hovered;
     }
 
     pub fn back(&mut self) {
         match self.selected {
-            Options::Scores => self.selected = Options::None,
+            Options::New | Options::Editor | Options::Scores => self.selected = Options::None,
             _ => self.selected = Options::Quit,
         }
     }
@@ -67,11 +107,25 @@ impl Menu {
         self.selected
     }
 
-    pub fn options(&self) -> [Options; 3] {
+    pub fn options(&self) -> [Options; OPTION_LEN] {
         self.options
     }
 
+    /// Whether there is a saved game to continue
+    pub fn can_continue(&self) -> bool {
+        self.can_continue
+    }
+
     pub fn hovered(&self) -> Options {
         self.hovered
     }
+
+    pub fn hovered_mode(&self) -> GameMode {
+        GameMode::ALL[self.hovered_mode]
+    }
+
+    /// Game mode chosen in the New Game sub menu
+    pub fn mode(&self) -> Option<GameMode> {
+        self.mode
+    }
 }
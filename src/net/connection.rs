@@ -0,0 +1,400 @@
+use std::io::{self, Read};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::net::protocol::{Message, VERSION, invalid};
+
+/// Time without hearing from the peer before the connection is considered lost
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest time without sending anything, the peer would think we are gone otherwise
+const KEEP_ALIVE: Duration = Duration::from_secs(1);
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+/// Time for a new connection to say hello before it is turned away
+const HELLO_TIMEOUT: Duration = Duration::from_secs(1);
+/// More than any hello, a connection sending this much without one is turned away
+const MAX_HELLO: usize = 64;
+/// Give up on the match when the peer is gone for longer than this
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+enum Role {
+    /// Waits for the peer to come back on the listener
+    Host(TcpListener),
+    /// Connects again to the host
+    Join(SocketAddr),
+}
+
+/// TCP link to the other peer, whose messages are read by a background thread
+pub struct Connection {
+    role: Role,
+    stream: Option<TcpStream>, // None while disconnected
+    incoming: Receiver<Message>,
+    last_sent: Instant,
+    lost_at: Option<Instant>,
+    token: Option<u64>,       // Token of the match, once it started
+    newcomers: Vec<Newcomer>, // Connections to the host not done saying hello
+}
+
+/// Connection made to the host while the peer is gone, read without blocking until it said
+/// hello
+struct Newcomer {
+    stream: TcpStream,
+    received: Vec<u8>,
+    since: Instant,
+}
+
+impl Connection {
+    /// Wait for a peer to connect on `listener`, whatever connects without saying hello is
+    /// turned away. Fails when the peer plays another version of the protocol.
+    pub fn host(listener: TcpListener) -> io::Result<Self> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            if let Some(stream) = greet(stream)? {
+                return Self::new(Role::Host(listener), stream);
+            }
+        }
+    }
+
+    pub fn join(address: SocketAddr) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(address)?;
+        let hello = Message::Hello {
+            version: VERSION,
+            token: None,
+        };
+        hello.write_to(&mut stream)?;
+        Self::new(Role::Join(address), stream)
+    }
+
+    fn new(role: Role, stream: TcpStream) -> io::Result<Self> {
+        let (stream, incoming) = Self::start(stream, Vec::new())?;
+        Ok(Self {
+            role,
+            stream: Some(stream),
+            incoming,
+            last_sent: Instant::now(),
+            lost_at: None,
+            token: None,
+            newcomers: Vec::new(),
+        })
+    }
+
+    /// Token sent in the welcome, the only one with which the joining peer may come back
+    pub fn set_token(&mut self, token: u64) {
+        self.token = Some(token);
+    }
+
+    /// Spawn the thread reading `stream` after the bytes already `received` from it, the
+    /// channel closes when the connection is lost
+    fn start(stream: TcpStream, received: Vec<u8>) -> io::Result<(TcpStream, Receiver<Message>)> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        stream.set_write_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = io::Cursor::new(received).chain(stream.try_clone()?);
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(message) = Message::read_from(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok((stream, incoming))
+    }
+
+    /// Wait for the first message of the peer, used before the match starts
+    pub fn wait_message(&mut self) -> io::Result<Message> {
+        self.incoming
+            .recv_timeout(READ_TIMEOUT)
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "the peer did not answer"))
+    }
+
+    pub fn send(&mut self, message: &Message) {
+        let Some(stream) = self.stream.as_mut() else {
+            return;
+        };
+        if message.write_to(stream).is_err() {
+            self.lose();
+        }
+        self.last_sent = Instant::now();
+    }
+
+    /// Ping the peer when nothing was sent for a while
+    pub fn keep_alive(&mut self) {
+        if self.last_sent.elapsed() >= KEEP_ALIVE {
+            self.send(&Message::Ping);
+        }
+    }
+
+    /// Next message already received, if any
+    pub fn receive(&mut self) -> Option<Message> {
+        self.stream.as_ref()?;
+        match self.incoming.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.lose();
+                None
+            }
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn lose(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            // Wakes the reading thread up if it is still waiting
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        self.lost_at = Some(Instant::now());
+    }
+
+    /// Try once to connect again without blocking for long, returns whether it worked.
+    /// Fails when the peer has been gone for too long.
+    pub fn reconnect(&mut self) -> io::Result<bool> {
+        if self.is_connected() {
+            return Ok(true);
+        }
+        if self
+            .lost_at
+            .is_some_and(|lost_at| lost_at.elapsed() >= RECONNECT_TIMEOUT)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "connection lost with the other player",
+            ));
+        }
+        let (stream, received) = match &self.role {
+            Role::Host(listener) => {
+                listener.set_nonblocking(true)?;
+                loop {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            stream.set_nonblocking(true)?;
+                            self.newcomers.push(Newcomer {
+                                stream,
+                                received: Vec::new(),
+                                since: Instant::now(),
+                            });
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                        Err(e) => return Err(e),
+                    }
+                }
+                match self.peer_back() {
+                    Some(peer) => peer,
+                    None => return Ok(false),
+                }
+            }
+            Role::Join(address) => match TcpStream::connect_timeout(address, CONNECT_TIMEOUT) {
+                Ok(mut stream) => {
+                    let hello = Message::Hello {
+                        version: VERSION,
+                        token: self.token,
+                    };
+                    if hello.write_to(&mut stream).is_err() {
+                        return Ok(false);
+                    }
+                    (stream, Vec::new())
+                }
+                Err(_) => return Ok(false),
+            },
+        };
+        let (stream, incoming) = Self::start(stream, received)?;
+        self.stream = Some(stream);
+        self.incoming = incoming;
+        self.lost_at = None;
+        self.newcomers.clear();
+        Ok(true)
+    }
+
+    /// The newcomer that said hello with the token of the match, the others being turned away
+    /// once they said something else or took too long
+    fn peer_back(&mut self) -> Option<(TcpStream, Vec<u8>)> {
+        let mut peer = None;
+        for mut newcomer in std::mem::take(&mut self.newcomers) {
+            match newcomer.hello() {
+                Ok(Some(Message::Hello { version, token }))
+                    if version == VERSION && token.is_some() && token == self.token =>
+                {
+                    peer = Some((newcomer.stream, newcomer.received));
+                    break;
+                }
+                Ok(None) if newcomer.since.elapsed() < HELLO_TIMEOUT => {
+                    self.newcomers.push(newcomer)
+                }
+                _ => {
+                    let _ = newcomer.stream.shutdown(Shutdown::Both);
+                }
+            }
+        }
+        peer
+    }
+
+    /// Time left before giving up on the peer, `None` while connected
+    pub fn reconnect_time_left(&self) -> Option<Duration> {
+        self.lost_at
+            .map(|lost_at| RECONNECT_TIMEOUT.saturating_sub(lost_at.elapsed()))
+    }
+}
+
+impl Newcomer {
+    /// Read what was sent so far, the hello once it is whole. What came after it is left in
+    /// `received`.
+    fn hello(&mut self) -> io::Result<Option<Message>> {
+        let mut buffer = [0; MAX_HELLO];
+        loop {
+            if let [high, low, ..] = self.received[..]
+                && self.received.len() >= 2 + u16::from_be_bytes([high, low]) as usize
+            {
+                let mut reader = self.received.as_slice();
+                let hello = Message::read_from(&mut reader)?;
+                self.received = reader.to_vec();
+                return Ok(Some(hello));
+            }
+            let room = MAX_HELLO.saturating_sub(self.received.len());
+            if room == 0 {
+                return Err(invalid("too long to say hello".to_string()));
+            }
+            match self.stream.read(&mut buffer[..room]) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(read) => self.received.extend(&buffer[..read]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Read the hello of a peer joining a match, `None` when it is not such a peer at all
+fn greet(mut stream: TcpStream) -> io::Result<Option<TcpStream>> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HELLO_TIMEOUT))?;
+    match Message::read_from(&mut stream) {
+        Ok(Message::Hello {
+            version,
+            token: None,
+        }) if version == VERSION => Ok(Some(stream)),
+        Ok(Message::Hello { version, .. }) if version != VERSION => {
+            let _ = Message::Quit.write_to(&mut stream);
+            Err(invalid(format!(
+                "the other player uses version {version} of the protocol instead of {VERSION}"
+            )))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::thread;
+
+    use super::*;
+
+    const TOKEN: u64 = 0x5eed;
+
+    /// Host and joining ends of a connection on the loopback, in a match of `TOKEN`
+    fn pair() -> (Connection, Connection) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || Connection::host(listener).unwrap());
+        let mut join = Connection::join(address).unwrap();
+        let mut host = host.join().unwrap();
+        host.set_token(TOKEN);
+        join.set_token(TOKEN);
+        (host, join)
+    }
+
+    /// Wait a little for a message to come
+    fn receive(connection: &mut Connection) -> Option<Message> {
+        let start = Instant::now();
+        while start.elapsed() < READ_TIMEOUT {
+            if let Some(message) = connection.receive() {
+                return Some(message);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        None
+    }
+
+    fn hello(address: SocketAddr, version: u16, token: Option<u64>) -> TcpStream {
+        let mut stream = TcpStream::connect(address).unwrap();
+        Message::Hello { version, token }
+            .write_to(&mut stream)
+            .unwrap();
+        stream
+    }
+
+    #[test]
+    fn refuse_other_versions() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || Connection::host(listener).err());
+        let mut stream = hello(address, VERSION + 1, None);
+        assert_eq!(Message::read_from(&mut stream).unwrap(), Message::Quit);
+        let error = host.join().unwrap().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn only_the_peer_takes_back_its_place() {
+        let (mut host, mut join) = pair();
+        host.lose();
+        join.lose();
+        let address = match &join.role {
+            Role::Join(address) => *address,
+            Role::Host(_) => unreachable!(),
+        };
+
+        // Neither a silent connection, one speaking another version, a new player nor one
+        // of another match get the slot
+        let mut silent = TcpStream::connect(address).unwrap();
+        silent.write_all(&[0]).unwrap();
+        let _strangers = [
+            hello(address, VERSION + 1, Some(TOKEN)),
+            hello(address, VERSION, None),
+            hello(address, VERSION, Some(TOKEN + 1)),
+        ];
+        thread::sleep(Duration::from_millis(50));
+        let start = Instant::now();
+        assert!(!host.reconnect().unwrap());
+        // Waiting for the silent one is not done in the game loop
+        assert!(start.elapsed() < HELLO_TIMEOUT / 10);
+        assert!(!host.is_connected());
+        assert_eq!(host.newcomers.len(), 1);
+
+        assert!(join.reconnect().unwrap());
+        // Sent with the hello, before the host read it
+        join.send(&Message::Ready { round: 2 });
+        let start = Instant::now();
+        while !host.reconnect().unwrap() {
+            assert!(start.elapsed() < READ_TIMEOUT);
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(receive(&mut host), Some(Message::Ready { round: 2 }));
+        host.send(&Message::Ping);
+        assert_eq!(receive(&mut join), Some(Message::Ping));
+        assert!(host.newcomers.is_empty());
+    }
+
+    #[test]
+    fn newcomers_saying_nothing_are_dropped() {
+        let (mut host, _join) = pair();
+        host.lose();
+        let address = match &host.role {
+            Role::Host(listener) => listener.local_addr().unwrap(),
+            Role::Join(_) => unreachable!(),
+        };
+        let _silent = TcpStream::connect(address).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(!host.reconnect().unwrap());
+        assert_eq!(host.newcomers.len(), 1);
+        thread::sleep(HELLO_TIMEOUT);
+        assert!(!host.reconnect().unwrap());
+        assert!(host.newcomers.is_empty());
+    }
+}
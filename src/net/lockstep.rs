@@ -0,0 +1,341 @@
+use std::collections::BTreeMap;
+
+use crate::action::Action;
+use crate::net::connection::Connection;
+use crate::net::protocol::Message;
+use crate::tetris::FPS;
+use crate::versus::{PLAYERS, Versus};
+
+/// Ticks between a key press and the tick it is applied on, for the input to reach the peer in time
+const INPUT_DELAY: u32 = 3;
+/// Ticks between two comparisons of the match hashes
+const HASH_INTERVAL: u32 = FPS as u32;
+/// Most ticks played at once to catch up with a peer that is ahead
+const MAX_CATCH_UP: usize = 2;
+/// Ticks without being able to play before telling the player the peer is late
+const STALL_NOTICE: usize = FPS / 2;
+
+/// Both peers play the same seeded match and only send their inputs to each other.
+/// A tick is played once the inputs of both players for it are known.
+pub struct Lockstep {
+    versus: Versus,
+    local: usize, // Board played on this side
+    round: u16,
+    tick: u32, // Next tick to play in the round
+    // Sent inputs, kept to be sent again after a reconnection
+    local_inputs: BTreeMap<u32, Vec<Action>>,
+    remote_inputs: BTreeMap<u32, Vec<Action>>,
+    pending: Vec<Action>, // Local actions not sent yet
+    local_ready: bool,
+    remote_ready: bool,
+    local_hashes: BTreeMap<u32, u64>,
+    remote_hashes: BTreeMap<u32, u64>,
+    stalled_ticks: usize,
+    desync: Option<(u16, u32)>,
+    peer_left: bool,
+}
+
+impl Lockstep {
+    pub fn new(versus: Versus, local: usize) -> Self {
+        Self {
+            versus,
+            local,
+            round: 0,
+            tick: 0,
+            local_inputs: BTreeMap::new(),
+            remote_inputs: BTreeMap::new(),
+            pending: Vec::new(),
+            local_ready: false,
+            remote_ready: false,
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            stalled_ticks: 0,
+            desync: None,
+            peer_left: false,
+        }
+    }
+
+    /// Queue an action of the local player, it is played a few ticks later on both sides
+    pub fn push_action(&mut self, action: Action) {
+        self.pending.push(action);
+    }
+
+    /// Read what the peer sent and play the next ticks if possible, to be called on every tick
+    pub fn on_tick(&mut self, connection: &mut Connection) {
+        self.receive(connection);
+        if self.desync.is_some() || self.peer_left || self.versus.round_winner().is_some() {
+            self.stalled_ticks = 0;
+            return;
+        }
+
+        let mut played = 0;
+        while played < MAX_CATCH_UP && (played == 0 || self.is_behind()) && self.step(connection) {
+            played += 1;
+        }
+        if played == 0 {
+            self.stalled_ticks += 1;
+        } else {
+            self.stalled_ticks = 0;
+        }
+    }
+
+    /// The peer already sent inputs for ticks far ahead of ours
+    fn is_behind(&self) -> bool {
+        self.remote_inputs
+            .last_key_value()
+            .is_some_and(|(tick, _)| *tick > self.tick + INPUT_DELAY)
+    }
+
+    /// Send the local inputs of a future tick, then play the current one if the peer's are known
+    fn step(&mut self, connection: &mut Connection) -> bool {
+        let target = self.tick + INPUT_DELAY;
+        if !self.local_inputs.contains_key(&target) {
+            let actions = std::mem::take(&mut self.pending);
+            connection.send(&Message::Inputs {
+                round: self.round,
+                tick: target,
+                actions: actions.clone(),
+            });
+            self.local_inputs.insert(target, actions);
+        }
+
+        // Nobody has inputs for the first ticks of a round
+        let remote = if self.tick < INPUT_DELAY {
+            Vec::new()
+        } else {
+            match self.remote_inputs.remove(&self.tick) {
+                Some(actions) => actions,
+                None => return false,
+            }
+        };
+        let local = self
+            .local_inputs
+            .get(&self.tick)
+            .cloned()
+            .unwrap_or_default();
+        // Both sides apply the actions in the order of the boards
+        for id in 0..PLAYERS {
+            let actions = if id == self.local { &local } else { &remote };
+            for action in actions {
+                self.versus.player_mut(id).apply(*action);
+            }
+        }
+        self.versus.on_tick();
+
+        if self.tick.is_multiple_of(HASH_INTERVAL) {
+            let hash = self.versus.state_hash();
+            connection.send(&Message::Hash {
+                round: self.round,
+                tick: self.tick,
+                hash,
+            });
+            self.local_hashes.insert(self.tick, hash);
+            self.check_hash(self.tick);
+        }
+        self.tick += 1;
+        // The peer is never more than the input delay behind, older inputs won't be asked again
+        self.local_inputs = self
+            .local_inputs
+            .split_off(&self.tick.saturating_sub(INPUT_DELAY));
+        true
+    }
+
+    fn receive(&mut self, connection: &mut Connection) {
+        while let Some(message) = connection.receive() {
+            match message {
+                Message::Inputs {
+                    round,
+                    tick,
+                    actions,
+                } if round == self.round && tick >= self.tick => {
+                    self.remote_inputs.insert(tick, actions);
+                }
+                Message::Hash { round, tick, hash } if round == self.round => {
+                    self.remote_hashes.insert(tick, hash);
+                    self.check_hash(tick);
+                }
+                Message::Ready { round } if round == self.round => {
+                    self.remote_ready = true;
+                    self.start_next_round();
+                }
+                Message::Resume { round, tick } => self.send_again(connection, round, tick),
+                Message::Quit => self.peer_left = true,
+                _ => {}
+            }
+        }
+    }
+
+    fn check_hash(&mut self, tick: u32) {
+        if let (Some(local), Some(remote)) =
+            (self.local_hashes.get(&tick), self.remote_hashes.get(&tick))
+        {
+            if local != remote {
+                self.desync = Some((self.round, tick));
+            }
+            self.local_hashes.remove(&tick);
+            self.remote_hashes.remove(&tick);
+        }
+    }
+
+    /// The local player wants to play the next round, it starts once the peer wants it too
+    pub fn ready(&mut self, connection: &mut Connection) {
+        if self.versus.round_winner().is_none() || self.versus.is_over() || self.local_ready {
+            return;
+        }
+        self.local_ready = true;
+        connection.send(&Message::Ready { round: self.round });
+        self.start_next_round();
+    }
+
+    fn start_next_round(&mut self) {
+        if !self.local_ready || !self.remote_ready || self.versus.round_winner().is_none() {
+            return;
+        }
+        self.versus.next_round();
+        self.round += 1;
+        self.tick = 0;
+        self.local_inputs.clear();
+        self.remote_inputs.clear();
+        self.pending.clear();
+        self.local_hashes.clear();
+        self.remote_hashes.clear();
+        self.local_ready = false;
+        self.remote_ready = false;
+    }
+
+    /// Ask the peer for everything it sent since we were last in sync, after a reconnection
+    pub fn resume(&mut self, connection: &mut Connection) {
+        connection.send(&Message::Resume {
+            round: self.round,
+            tick: self.tick,
+        });
+    }
+
+    /// Send what the peer may have missed while the connection was lost
+    fn send_again(&mut self, connection: &mut Connection, round: u16, tick: u32) {
+        if round > self.round {
+            return;
+        }
+        let from = if round < self.round {
+            // The peer missed the message that started this round
+            connection.send(&Message::Ready { round });
+            0
+        } else {
+            tick
+        };
+        for (tick, actions) in self.local_inputs.range(from..) {
+            connection.send(&Message::Inputs {
+                round: self.round,
+                tick: *tick,
+                actions: actions.clone(),
+            });
+        }
+        if self.local_ready {
+            connection.send(&Message::Ready { round: self.round });
+        }
+    }
+
+    /// Tell the peer we leave the match
+    pub fn quit(&mut self, connection: &mut Connection) {
+        connection.send(&Message::Quit);
+    }
+
+    pub fn versus(&self) -> &Versus {
+        &self.versus
+    }
+
+    pub fn local(&self) -> usize {
+        self.local
+    }
+
+    /// The local player is waiting for the peer, either late or not ready for the next round
+    pub fn is_waiting(&self) -> bool {
+        self.stalled_ticks >= STALL_NOTICE || (self.local_ready && !self.remote_ready)
+    }
+
+    /// Round and tick at which the two sides stopped playing the same match
+    pub fn desync(&self) -> Option<(u16, u32)> {
+        self.desync
+    }
+
+    pub fn peer_left(&self) -> bool {
+        self.peer_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::game_mode::GameSettings;
+    use crate::net::{host_on, join};
+
+    #[test]
+    fn loopback_match_stays_in_sync() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || host_on(listener, 3, GameSettings::default()).unwrap());
+        // Something else connects first, it is not taken for the peer
+        let mut stranger = TcpStream::connect(address).unwrap();
+        Message::Ping.write_to(&mut stranger).unwrap();
+        let (mut join_connection, mut join) = join(&address.to_string()).unwrap();
+        let (mut host_connection, mut host) = host.join().unwrap();
+        assert_eq!((host.local(), join.local()), (0, 1));
+
+        // Long enough to compare hashes a few times, unless a round ends before: by a player
+        // topping out, or both at once which starts the round over
+        let ticks = 3 * HASH_INTERVAL + 10;
+        let is_done = |peer: &Lockstep| {
+            peer.tick >= ticks || peer.versus.round_winner().is_some() || peer.versus.round() > 0
+        };
+        let actions = [
+            Action::MoveLeft,
+            Action::MoveLeft,
+            Action::HardDrop,
+            Action::RotateClockwise,
+            Action::MoveRight,
+            Action::HardDrop,
+            Action::Hold,
+            Action::HardDrop,
+        ];
+        let start = Instant::now();
+        let mut frame = 0;
+        while !is_done(&host) || !is_done(&join) {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "the peers stopped playing"
+            );
+            for (peer, connection) in [
+                (&mut host, &mut host_connection),
+                (&mut join, &mut join_connection),
+            ] {
+                if !is_done(peer) {
+                    if frame % 4 == 0 {
+                        peer.push_action(actions[frame / 4 % actions.len()]);
+                    }
+                    peer.on_tick(connection);
+                }
+            }
+            frame += 1;
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(host.versus().round(), join.versus().round());
+        assert_eq!(host.tick, join.tick);
+        assert_eq!(host.desync(), None);
+        assert_eq!(join.desync(), None);
+        assert_eq!(host.versus().state_hash(), join.versus().state_hash());
+        // The inputs of each side were played on both, unless the round started over, leaving
+        // empty boards
+        if host.versus().round() > 0 {
+            return;
+        }
+        assert!(host.tick > HASH_INTERVAL);
+        for id in 0..PLAYERS {
+            assert!(host.versus().player(id).locked_pieces() > 0);
+        }
+    }
+}
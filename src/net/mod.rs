@@ -0,0 +1,89 @@
+//! Versus over TCP. The host picks the seed and the settings of the match, then both peers
+//! run the same deterministic match, only sending each other their inputs. The host also
+//! gives a token, which the joining peer shows to take its place back after a disconnection.
+//! Spectators get the boards themselves, see `spectate`.
+
+pub mod connection;
+pub mod lockstep;
+mod protocol;
+pub mod spectate;
+
+use std::error::Error;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+
+use rand::Rng;
+
+use crate::game_mode::GameSettings;
+use crate::net::connection::Connection;
+use crate::net::lockstep::Lockstep;
+use crate::net::protocol::Message;
+use crate::rotation_system::RotationSystem;
+use crate::versus::Versus;
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// Wait for a player to join on `port` and start the match, the host plays the first board
+pub fn host(
+    port: u16,
+    best_of: usize,
+    settings: GameSettings,
+) -> Result<(Connection, Lockstep), Box<dyn Error>> {
+    if settings.rotation_system == Some(RotationSystem::Custom) {
+        return Err("custom rotation systems cannot be played online".into());
+    }
+    host_on(TcpListener::bind(("0.0.0.0", port))?, best_of, settings)
+}
+
+/// `host` on a listener already bound
+fn host_on(
+    listener: TcpListener,
+    best_of: usize,
+    settings: GameSettings,
+) -> Result<(Connection, Lockstep), Box<dyn Error>> {
+    let mut connection = Connection::host(listener)?;
+    let (seed, token) = rand::rng().random();
+    let best_of = best_of.clamp(1, u8::MAX as usize) as u8;
+    connection.send(&Message::Welcome {
+        seed,
+        best_of,
+        settings,
+        token,
+    });
+    connection.set_token(token);
+    let versus = Versus::with_seed(best_of as usize, settings, seed);
+    Ok((connection, Lockstep::new(versus, 0)))
+}
+
+/// Join the match hosted at `address`, the port can be left out
+pub fn join(address: &str) -> Result<(Connection, Lockstep), Box<dyn Error>> {
+    let mut connection = Connection::join(resolve(address, DEFAULT_PORT)?)?;
+    match connection.wait_message()? {
+        Message::Welcome {
+            seed,
+            best_of,
+            settings,
+            token,
+        } => {
+            connection.set_token(token);
+            let versus = Versus::with_seed(best_of as usize, settings, seed);
+            Ok((connection, Lockstep::new(versus, 1)))
+        }
+        Message::Quit => {
+            Err("the host refused the connection, check both use the same version".into())
+        }
+        message => Err(format!("unexpected message from the host: {message:?}").into()),
+    }
+}
+
+/// `default_port` is used when `address` has none
+fn resolve(address: &str, default_port: u16) -> Result<SocketAddr, Box<dyn Error>> {
+    let address = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:{default_port}")
+    };
+    address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("could not resolve {address}").into())
+}
@@ -0,0 +1,436 @@
+//! Messages exchanged by the two peers of an online match.
+//! Each message is a big endian u16 length followed by a tag byte and the fields of the message.
+
+use std::io::{self, Read, Write};
+
+use crate::action::Action;
+use crate::board::MAX_WIDTH;
+use crate::game_mode::GameSettings;
+use crate::garbage::HolePattern;
+use crate::rotation_system::RotationSystem;
+
+/// Bumped whenever a message changes, peers with different versions refuse to play
+pub const VERSION: u16 = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// First message of the joining peer, with the token of the match it comes back to
+    /// after losing the connection
+    Hello { version: u16, token: Option<u64> },
+    /// Answer of the host, everything needed to create the same match on both sides, and the
+    /// token proving a reconnecting peer is the one that left
+    Welcome {
+        seed: u64,
+        best_of: u8,
+        settings: GameSettings,
+        token: u64,
+    },
+    /// Actions of the sender to apply on `tick` of `round`
+    Inputs {
+        round: u16,
+        tick: u32,
+        actions: Vec<Action>,
+    },
+    /// Hash of the whole match right after `tick` of `round`
+    Hash { round: u16, tick: u32, hash: u64 },
+    /// The sender is done with `round` and wants to play the next one
+    Ready { round: u16 },
+    /// Sent after a reconnection, the sender still needs the inputs from `tick` of `round`
+    Resume { round: u16, tick: u32 },
+    /// The sender left the match
+    Quit,
+    /// Nothing to say, only keeps the connection from timing out
+    Ping,
+}
+
+impl Message {
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut body = Vec::new();
+        match self {
+            Message::Hello { version, token } => {
+                body.push(0);
+                body.extend(version.to_be_bytes());
+                write_option(&mut body, *token);
+            }
+            Message::Welcome {
+                seed,
+                best_of,
+                settings,
+                token,
+            } => {
+                body.push(1);
+                body.extend(seed.to_be_bytes());
+                body.push(*best_of);
+                write_settings(&mut body, settings);
+                body.extend(token.to_be_bytes());
+            }
+            Message::Inputs {
+                round,
+                tick,
+                actions,
+            } => {
+                body.push(2);
+                body.extend(round.to_be_bytes());
+                body.extend(tick.to_be_bytes());
+                body.push(actions.len().min(u8::MAX as usize) as u8);
+                body.extend(actions.iter().take(u8::MAX as usize).map(action_to_u8));
+            }
+            Message::Hash { round, tick, hash } => {
+                body.push(3);
+                body.extend(round.to_be_bytes());
+                body.extend(tick.to_be_bytes());
+                body.extend(hash.to_be_bytes());
+            }
+            Message::Ready { round } => {
+                body.push(4);
+                body.extend(round.to_be_bytes());
+            }
+            Message::Resume { round, tick } => {
+                body.push(5);
+                body.extend(round.to_be_bytes());
+                body.extend(tick.to_be_bytes());
+            }
+            Message::Quit => body.push(6),
+            Message::Ping => body.push(7),
+        }
+        writer.write_all(&(body.len() as u16).to_be_bytes())?;
+        writer.write_all(&body)?;
+        writer.flush()
+    }
+
+    /// Blocks until a whole message is read
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut length = [0; 2];
+        reader.read_exact(&mut length)?;
+        let mut body = vec![0; u16::from_be_bytes(length) as usize];
+        reader.read_exact(&mut body)?;
+
+        let mut body = Body(&body);
+        let message = match body.u8()? {
+            0 => Message::Hello {
+                version: body.u16()?,
+                // Older versions end with the version, to be told they cannot play
+                token: match body.0.is_empty() {
+                    true => None,
+                    false => body.option()?,
+                },
+            },
+            1 => Message::Welcome {
+                seed: body.u64()?,
+                best_of: body.u8()?,
+                settings: read_settings(&mut body)?,
+                token: body.u64()?,
+            },
+            2 => {
+                let round = body.u16()?;
+                let tick = body.u32()?;
+                let count = body.u8()?;
+                let actions = (0..count)
+                    .map(|_| action_from_u8(body.u8()?))
+                    .collect::<io::Result<Vec<Action>>>()?;
+                Message::Inputs {
+                    round,
+                    tick,
+                    actions,
+                }
+            }
+            3 => Message::Hash {
+                round: body.u16()?,
+                tick: body.u32()?,
+                hash: body.u64()?,
+            },
+            4 => Message::Ready { round: body.u16()? },
+            5 => Message::Resume {
+                round: body.u16()?,
+                tick: body.u32()?,
+            },
+            6 => Message::Quit,
+            7 => Message::Ping,
+            tag => return Err(invalid(format!("unknown message {tag}"))),
+        };
+        Ok(message)
+    }
+}
+
+pub(super) fn invalid(error: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+// Cursor over the body of a message
+pub(super) struct Body<'a>(pub(super) &'a [u8]);
+
+impl Body<'_> {
+    pub(super) fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        if self.0.len() < N {
+            return Err(invalid("message too short".to_string()));
+        }
+        let (bytes, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(bytes.try_into().unwrap_or([0; N]))
+    }
+
+    pub(super) fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub(super) fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_be_bytes(self.take()?))
+    }
+
+    pub(super) fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.take()?))
+    }
+
+    pub(super) fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.take()?))
+    }
+
+    /// Value written by `write_option`
+    pub(super) fn option(&mut self) -> io::Result<Option<u64>> {
+        let is_some = self.u8()? != 0;
+        let value = self.u64()?;
+        Ok(is_some.then_some(value))
+    }
+}
+
+pub(super) fn write_option(body: &mut Vec<u8>, value: Option<u64>) {
+    body.push(value.is_some() as u8);
+    body.extend(value.unwrap_or(0).to_be_bytes());
+}
+
+fn write_settings(body: &mut Vec<u8>, settings: &GameSettings) {
+    write_option(body, settings.are.map(|are| are as u64));
+    write_option(body, settings.line_clear.map(|delay| delay as u64));
+    write_option(
+        body,
+        settings.garbage_pattern.map(|pattern| match pattern {
+            HolePattern::Clean => 0,
+            HolePattern::Messy => 1,
+            HolePattern::Cheese => 2,
+        }),
+    );
+    write_option(
+        body,
+        settings
+            .garbage_change_probability
+            .map(|probability| probability.to_bits()),
+    );
+    write_option(body, settings.width.map(|width| width as u64));
+    write_option(body, settings.height.map(|height| height as u64));
+    write_option(body, settings.rotation_system.map(|system| system as u64));
+}
+
+fn read_settings(body: &mut Body) -> io::Result<GameSettings> {
+    let are = body.option()?.map(|are| are as usize);
+    let line_clear = body.option()?.map(|delay| delay as usize);
+    let garbage_pattern = match body.option()? {
+        None => None,
+        Some(0) => Some(HolePattern::Clean),
+        Some(1) => Some(HolePattern::Messy),
+        Some(2) => Some(HolePattern::Cheese),
+        Some(pattern) => return Err(invalid(format!("unknown hole pattern {pattern}"))),
+    };
+    let garbage_change_probability = body.option()?.map(f64::from_bits);
+    let width = body.option()?.map(|width| width as usize);
+    let height = body.option()?.map(|height| height as usize);
+    // A custom rotation system only exists on the terminal that loaded it
+    let rotation_system = match body.option()? {
+        None => None,
+        Some(system) => match RotationSystem::ALL.get(system as usize) {
+            Some(RotationSystem::Custom) | None => {
+                return Err(invalid(format!("unknown rotation system {system}")));
+            }
+            Some(system) => Some(*system),
+        },
+    };
+    // The same bounds as the command line, a board outside them couldn't be created
+    if !width.is_none_or(|width| (4..=MAX_WIDTH).contains(&width))
+        || !height.is_none_or(|height| (4..=60).contains(&height))
+    {
+        return Err(invalid("board size out of range".to_string()));
+    }
+    Ok(GameSettings {
+        are,
+        line_clear,
+        garbage_pattern,
+        garbage_change_probability,
+        width,
+        height,
+        rotation_system,
+    })
+}
+
+fn action_to_u8(action: &Action) -> u8 {
+    match action {
+        Action::MoveLeft => 0,
+        Action::MoveRight => 1,
+        Action::SoftDrop => 2,
+        Action::HardDrop => 3,
+        Action::RotateClockwise => 4,
+        Action::RotateCounterClockwise => 5,
+        Action::Hold => 6,
+    }
+}
+
+fn action_from_u8(value: u8) -> io::Result<Action> {
+    Ok(match value {
+        0 => Action::MoveLeft,
+        1 => Action::MoveRight,
+        2 => Action::SoftDrop,
+        3 => Action::HardDrop,
+        4 => Action::RotateClockwise,
+        5 => Action::RotateCounterClockwise,
+        6 => Action::Hold,
+        _ => return Err(invalid(format!("unknown action {value}"))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: &Message) -> Message {
+        let mut bytes = Vec::new();
+        message.write_to(&mut bytes).unwrap();
+        let mut reader = bytes.as_slice();
+        let read = Message::read_from(&mut reader).unwrap();
+        assert!(reader.is_empty(), "{message:?} was not read whole");
+        read
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        let messages = [
+            Message::Hello {
+                version: VERSION,
+                token: None,
+            },
+            Message::Hello {
+                version: VERSION,
+                token: Some(u64::MAX),
+            },
+            Message::Welcome {
+                seed: u64::MAX - 5,
+                best_of: 7,
+                settings: GameSettings::default(),
+                token: 9,
+            },
+            Message::Welcome {
+                seed: 42,
+                best_of: 1,
+                settings: GameSettings {
+                    are: Some(0),
+                    line_clear: Some(40),
+                    garbage_pattern: Some(HolePattern::Cheese),
+                    garbage_change_probability: Some(0.3),
+                    width: Some(MAX_WIDTH),
+                    height: Some(4),
+                    rotation_system: Some(RotationSystem::Nrs),
+                },
+                token: 0,
+            },
+            Message::Inputs {
+                round: 2,
+                tick: 123_456,
+                actions: vec![
+                    Action::MoveLeft,
+                    Action::MoveRight,
+                    Action::SoftDrop,
+                    Action::HardDrop,
+                    Action::RotateClockwise,
+                    Action::RotateCounterClockwise,
+                    Action::Hold,
+                ],
+            },
+            Message::Inputs {
+                round: 0,
+                tick: 0,
+                actions: Vec::new(),
+            },
+            Message::Hash {
+                round: 1,
+                tick: 60,
+                hash: 0x0123_4567_89ab_cdef,
+            },
+            Message::Ready { round: 3 },
+            Message::Resume {
+                round: 4,
+                tick: u32::MAX,
+            },
+            Message::Quit,
+            Message::Ping,
+        ];
+        for message in messages {
+            assert_eq!(round_trip(&message), message);
+        }
+    }
+
+    #[test]
+    fn messages_follow_each_other() {
+        let mut bytes = Vec::new();
+        Message::Ready { round: 1 }.write_to(&mut bytes).unwrap();
+        Message::Ping.write_to(&mut bytes).unwrap();
+        let mut reader = bytes.as_slice();
+        assert_eq!(
+            Message::read_from(&mut reader).unwrap(),
+            Message::Ready { round: 1 }
+        );
+        assert_eq!(Message::read_from(&mut reader).unwrap(), Message::Ping);
+    }
+
+    #[test]
+    fn refuse_malformed_frames() {
+        let invalid_data = |bytes: &[u8]| {
+            let error = Message::read_from(&mut &bytes[..]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{bytes:?}");
+        };
+        // Body shorter than its fields
+        invalid_data(&[0, 2, 3, 0]);
+        invalid_data(&[0, 0]);
+        // Unknown tag, action and rotation system
+        invalid_data(&[0, 1, 99]);
+        invalid_data(&[0, 9, 2, 0, 0, 0, 0, 0, 0, 1, 7]);
+        let mut bytes = Vec::new();
+        Message::Welcome {
+            seed: 0,
+            best_of: 3,
+            settings: GameSettings {
+                rotation_system: Some(RotationSystem::Custom),
+                ..GameSettings::default()
+            },
+            token: 0,
+        }
+        .write_to(&mut bytes)
+        .unwrap();
+        invalid_data(&bytes);
+    }
+
+    #[test]
+    fn hello_of_older_versions() {
+        // Version 3 wrote nothing after the version
+        let bytes = [0, 3, 0, 0, 3];
+        assert_eq!(
+            Message::read_from(&mut &bytes[..]).unwrap(),
+            Message::Hello {
+                version: 3,
+                token: None
+            }
+        );
+    }
+
+    #[test]
+    fn refuse_short_frames() {
+        let mut bytes = Vec::new();
+        Message::Hash {
+            round: 0,
+            tick: 0,
+            hash: 0,
+        }
+        .write_to(&mut bytes)
+        .unwrap();
+        for length in 0..bytes.len() {
+            let error = Message::read_from(&mut &bytes[..length]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+}
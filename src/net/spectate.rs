@@ -0,0 +1,504 @@
+//! Read-only stream of the games played, for a second terminal or a projector.
+//! The player sends a snapshot of the boards after every update, spectators only draw them.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::attack::{ClearInfo, TSpin};
+use crate::board::{Board, MAX_WIDTH};
+use crate::finesse::{Judgement, Training};
+use crate::game_mode::GameMode;
+use crate::net::protocol::{Body, invalid, write_option};
+use crate::net::resolve;
+use crate::opener::Practice;
+use crate::puzzle::{Goal, Puzzle};
+use crate::rotation_system::RotationSystem;
+use crate::setup::Setup;
+use crate::tetris::{GameOverReason, HIDDEN_ROWS, Snapshot, Tetris};
+use crate::tetromino::Tetromino;
+use crate::tetromino_type::TetrominoType;
+use crate::versus::{PLAYERS, Versus};
+
+pub const DEFAULT_PORT: u16 = 7879;
+
+/// How often waiting spectators are accepted when nothing is published
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+/// A spectator too slow to read for this long is dropped rather than slowing the game down
+const WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// What the spectators see
+#[derive(Clone, Debug)]
+pub enum Broadcast {
+    /// The player is in the menu
+    Idle,
+    Solo(Box<Snapshot>),
+    Versus {
+        names: [String; PLAYERS],
+        wins: [usize; PLAYERS],
+        best_of: usize,
+        round_winner: Option<usize>,
+        boards: Box<[Snapshot; PLAYERS]>,
+    },
+}
+
+impl Broadcast {
+    pub fn solo(game: &Tetris) -> Self {
+        Broadcast::Solo(Box::new(game.snapshot()))
+    }
+
+    pub fn versus(versus: &Versus, names: [&str; PLAYERS]) -> Self {
+        Broadcast::Versus {
+            names: names.map(str::to_string),
+            wins: versus.wins(),
+            best_of: versus.best_of(),
+            round_winner: versus.round_winner(),
+            boards: Box::new([0, 1].map(|id| versus.player(id).snapshot())),
+        }
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut body = Vec::new();
+        match self {
+            Broadcast::Idle => body.push(0),
+            Broadcast::Solo(snapshot) => {
+                body.push(1);
+                write_snapshot(&mut body, snapshot);
+            }
+            Broadcast::Versus {
+                names,
+                wins,
+                best_of,
+                round_winner,
+                boards,
+            } => {
+                body.push(2);
+                for name in names {
+                    write_string(&mut body, name);
+                }
+                for wins in wins {
+                    body.push(*wins as u8);
+                }
+                body.push(*best_of as u8);
+                write_option(&mut body, round_winner.map(|winner| winner as u64));
+                for snapshot in boards.iter() {
+                    write_snapshot(&mut body, snapshot);
+                }
+            }
+        }
+        writer.write_all(&(body.len() as u16).to_be_bytes())?;
+        writer.write_all(&body)?;
+        writer.flush()
+    }
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut length = [0; 2];
+        reader.read_exact(&mut length)?;
+        let mut body = vec![0; u16::from_be_bytes(length) as usize];
+        reader.read_exact(&mut body)?;
+
+        let mut body = Body(&body);
+        Ok(match body.u8()? {
+            0 => Broadcast::Idle,
+            1 => Broadcast::Solo(Box::new(read_snapshot(&mut body)?)),
+            2 => {
+                let mut names = [String::new(), String::new()];
+                for name in names.iter_mut() {
+                    *name = read_string(&mut body)?;
+                }
+                let wins = [body.u8()? as usize, body.u8()? as usize];
+                let best_of = body.u8()? as usize;
+                let round_winner = body.option()?.map(|winner| winner as usize % PLAYERS);
+                let boards = Box::new([read_snapshot(&mut body)?, read_snapshot(&mut body)?]);
+                Broadcast::Versus {
+                    names,
+                    wins,
+                    best_of,
+                    round_winner,
+                    boards,
+                }
+            }
+            tag => return Err(invalid(format!("unknown broadcast {tag}"))),
+        })
+    }
+}
+
+fn write_snapshot(body: &mut Vec<u8>, snapshot: &Snapshot) {
+    let mode = GameMode::ALL
+        .iter()
+        .position(|mode| *mode == snapshot.mode)
+        .unwrap_or(0);
+    body.push(mode as u8);
+    body.push(snapshot.board.width() as u8);
+    body.push(snapshot.board.height() as u8);
+    body.extend(snapshot.board.lines().flatten().map(|cell| *cell as u8));
+
+    let current = &snapshot.current;
+    body.push(current.shape() as u8);
+    body.push(current.pos().0 as u8);
+    body.push(current.pos().1 as u8);
+    body.push(current.rotation());
+    body.push(current.rotation_system() as u8);
+    body.push(snapshot.falling as u8);
+
+    body.push(snapshot.hold as u8);
+    body.push(snapshot.nexts.len() as u8);
+    body.extend(snapshot.nexts.iter().map(|next| *next as u8));
+    for counter in [
+        snapshot.score,
+        snapshot.level,
+        snapshot.cleared_lines,
+        snapshot.cleared_garbage,
+        snapshot.pending_garbage,
+        snapshot.tick,
+        snapshot.total_attack,
+        snapshot.locked_pieces,
+        snapshot.finesse_faults,
+    ] {
+        body.extend((counter as u64).to_be_bytes());
+    }
+    write_option(body, snapshot.last_finesse.map(|last| last.inputs as u64));
+    write_option(body, snapshot.last_finesse.map(|last| last.minimal as u64));
+    write_option(
+        body,
+        snapshot.training.map(|training| training.placed as u64),
+    );
+    if let Some(training) = snapshot.training {
+        body.push(training.shape as u8);
+        for (row, column) in training.target {
+            body.extend([row as u8, column as u8]);
+        }
+        body.push(training.is_retry as u8);
+    }
+    write_option(
+        body,
+        snapshot.practice.map(|practice| practice.placed as u64),
+    );
+    if let Some(practice) = snapshot.practice {
+        body.push(practice.is_missed as u8);
+    }
+    // Spectators only see the name, the goal and the stars of a puzzle
+    body.push(snapshot.puzzle.is_some() as u8);
+    if let Some(puzzle) = &snapshot.puzzle {
+        write_string(body, &puzzle.name);
+        let (goal, count) = match puzzle.goal {
+            Goal::Lines(lines) => (0, lines),
+            Goal::TSpinTriple => (1, 0),
+            Goal::PerfectClear => (2, 0),
+            Goal::Survive(pieces) => (3, pieces),
+        };
+        body.push(goal);
+        for count in [count, puzzle.stars[0], puzzle.stars[1]] {
+            body.extend((count as u64).to_be_bytes());
+        }
+    }
+
+    body.push(snapshot.last_clear.is_some() as u8);
+    let clear = snapshot.last_clear.unwrap_or(ClearInfo {
+        lines: 0,
+        t_spin: TSpin::None,
+        perfect_clear: false,
+        combo: None,
+        back_to_back: false,
+    });
+    body.push(clear.lines as u8);
+    body.push(match clear.t_spin {
+        TSpin::None => 0,
+        TSpin::Mini => 1,
+        TSpin::Full => 2,
+    });
+    body.push(clear.perfect_clear as u8);
+    write_option(body, clear.combo.map(|combo| combo as u64));
+    body.push(clear.back_to_back as u8);
+
+    body.push(match snapshot.game_over {
+        None => 0,
+        Some(GameOverReason::BlockOut) => 1,
+        Some(GameOverReason::LockOut) => 2,
+        Some(GameOverReason::TopOut) => 3,
+        Some(GameOverReason::OutOfPieces) => 4,
+    });
+    body.push(snapshot.is_won as u8);
+    body.push(snapshot.section_times.len() as u8);
+    for time in &snapshot.section_times {
+        body.extend((*time as u64).to_be_bytes());
+    }
+    body.push(snapshot.gm_qualified as u8);
+}
+
+fn read_snapshot(body: &mut Body) -> io::Result<Snapshot> {
+    let mode = body.u8()? as usize;
+    let mode = *GameMode::ALL
+        .get(mode)
+        .ok_or_else(|| invalid(format!("unknown game mode {mode}")))?;
+    let (width, height) = (body.u8()? as usize, body.u8()? as usize);
+    if !(4..=MAX_WIDTH).contains(&width) || height <= HIDDEN_ROWS {
+        return Err(invalid(format!("{width} by {height} is not a board size")));
+    }
+    let mut board = Board::new(width, height);
+    for row in 0..height {
+        for column in 0..width {
+            board.set(row, column, TetrominoType::from_u8(body.u8()?));
+        }
+    }
+
+    let shape = TetrominoType::from_u8(body.u8()?);
+    let pos = (body.u8()? as i8, body.u8()? as i8);
+    let rotation = body.u8()?;
+    // Without the file of a custom rotation system, its tetrominoes are drawn like SRS ones
+    let rotation_system = body.u8()? as usize;
+    let rotation_system = *RotationSystem::ALL
+        .get(rotation_system)
+        .ok_or_else(|| invalid(format!("unknown rotation system {rotation_system}")))?;
+    let current = Tetromino::placed(shape, rotation_system, rotation, pos);
+    let falling = body.u8()? != 0;
+
+    let hold = TetrominoType::from_u8(body.u8()?);
+    let nexts = (0..body.u8()?)
+        .map(|_| Ok(TetrominoType::from_u8(body.u8()?)))
+        .collect::<io::Result<Vec<TetrominoType>>>()?;
+    let mut counters = [0; 9];
+    for counter in counters.iter_mut() {
+        *counter = body.u64()? as usize;
+    }
+    let [
+        score,
+        level,
+        cleared_lines,
+        cleared_garbage,
+        pending_garbage,
+        tick,
+        total_attack,
+        locked_pieces,
+        finesse_faults,
+    ] = counters;
+    let last_finesse = match (body.option()?, body.option()?) {
+        (Some(inputs), Some(minimal)) => Some(Judgement {
+            inputs: inputs as usize,
+            minimal: minimal as usize,
+        }),
+        _ => None,
+    };
+    let training = match body.option()? {
+        Some(placed) => {
+            let shape = TetrominoType::from_u8(body.u8()?);
+            let mut target = [(0, 0); 4];
+            for cell in target.iter_mut() {
+                *cell = (body.u8()? as i8, body.u8()? as i8);
+            }
+            Some(Training {
+                shape,
+                target,
+                placed: placed as usize,
+                is_retry: body.u8()? != 0,
+            })
+        }
+        None => None,
+    };
+    let practice = match body.option()? {
+        Some(placed) => Some(Practice {
+            placed: placed as usize,
+            is_missed: body.u8()? != 0,
+        }),
+        None => None,
+    };
+    let puzzle = match body.u8()? {
+        0 => None,
+        _ => {
+            let name = read_string(body)?;
+            let goal = body.u8()?;
+            let [count, three_stars, two_stars] = [body.u64()?, body.u64()?, body.u64()?];
+            let goal = match goal {
+                0 => Goal::Lines(count as usize),
+                1 => Goal::TSpinTriple,
+                2 => Goal::PerfectClear,
+                _ => Goal::Survive(count as usize),
+            };
+            Some(Puzzle {
+                name,
+                goal,
+                stars: [three_stars as usize, two_stars as usize],
+                setup: Setup::default(),
+            })
+        }
+    };
+
+    let has_clear = body.u8()? != 0;
+    let clear = ClearInfo {
+        lines: body.u8()? as usize,
+        t_spin: match body.u8()? {
+            1 => TSpin::Mini,
+            2 => TSpin::Full,
+            _ => TSpin::None,
+        },
+        perfect_clear: body.u8()? != 0,
+        combo: body.option()?.map(|combo| combo as usize),
+        back_to_back: body.u8()? != 0,
+    };
+
+    let game_over = match body.u8()? {
+        1 => Some(GameOverReason::BlockOut),
+        2 => Some(GameOverReason::LockOut),
+        3 => Some(GameOverReason::TopOut),
+        4 => Some(GameOverReason::OutOfPieces),
+        _ => None,
+    };
+    let is_won = body.u8()? != 0;
+    let section_times = (0..body.u8()?)
+        .map(|_| Ok(body.u64()? as usize))
+        .collect::<io::Result<Vec<usize>>>()?;
+    let gm_qualified = body.u8()? != 0;
+
+    Ok(Snapshot {
+        mode,
+        board,
+        current,
+        falling,
+        hold,
+        nexts,
+        score,
+        level,
+        cleared_lines,
+        cleared_garbage,
+        pending_garbage,
+        tick,
+        total_attack,
+        locked_pieces,
+        finesse_faults,
+        last_finesse,
+        training,
+        practice,
+        puzzle,
+        last_clear: has_clear.then_some(clear),
+        game_over,
+        is_won,
+        section_times,
+        gm_qualified,
+    })
+}
+
+/// At most 255 bytes of `string`, after its length
+fn write_string(body: &mut Vec<u8>, string: &str) {
+    let bytes = &string.as_bytes()[..string.len().min(u8::MAX as usize)];
+    body.push(bytes.len() as u8);
+    body.extend(bytes);
+}
+
+fn read_string(body: &mut Body) -> io::Result<String> {
+    let length = body.u8()? as usize;
+    let bytes = (0..length)
+        .map(|_| body.u8())
+        .collect::<io::Result<Vec<u8>>>()?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Sends what the player sees to everyone connected to the port.
+/// A background thread does the writing, publishing never blocks the game.
+#[derive(Clone)]
+pub struct Spectators {
+    frames: Sender<Vec<u8>>,
+}
+
+impl Spectators {
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let (frames, receiver) = mpsc::channel();
+        thread::spawn(move || Self::serve(listener, receiver));
+        Ok(Self { frames })
+    }
+
+    pub fn publish(&self, broadcast: &Broadcast) {
+        let mut frame = Vec::new();
+        if broadcast.write_to(&mut frame).is_ok() {
+            // The thread only stops if the listener fails, spectators are not worth stopping the game
+            let _ = self.frames.send(frame);
+        }
+    }
+
+    fn serve(listener: TcpListener, frames: Receiver<Vec<u8>>) {
+        let mut spectators: Vec<TcpStream> = Vec::new();
+        let mut last_frame: Option<Vec<u8>> = None;
+        loop {
+            let mut frame = match frames.recv_timeout(ACCEPT_INTERVAL) {
+                Ok(frame) => Some(frame),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            // Only the latest frame matters when the spectators are late
+            loop {
+                match frames.try_recv() {
+                    Ok(newer) => frame = Some(newer),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+
+            while let Ok((mut stream, _)) = listener.accept() {
+                let ready = stream.set_nonblocking(false).is_ok()
+                    && stream.set_nodelay(true).is_ok()
+                    && stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok();
+                // Newcomers see the game right away, even if nothing changes
+                if ready
+                    && last_frame
+                        .as_ref()
+                        .is_none_or(|last| stream.write_all(last).is_ok())
+                {
+                    spectators.push(stream);
+                }
+            }
+
+            if let Some(frame) = frame {
+                spectators.retain_mut(|stream| stream.write_all(&frame).is_ok());
+                last_frame = Some(frame);
+            }
+        }
+    }
+}
+
+/// Connection to a game being streamed
+pub struct Spectator {
+    broadcasts: Receiver<Broadcast>,
+    is_connected: bool,
+}
+
+impl Spectator {
+    /// Connect to the player at `address`, the port can be left out
+    pub fn connect(address: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut stream = TcpStream::connect(resolve(address, DEFAULT_PORT)?)?;
+        let (sender, broadcasts) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(broadcast) = Broadcast::read_from(&mut stream) {
+                if sender.send(broadcast).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            broadcasts,
+            is_connected: true,
+        })
+    }
+
+    /// Most recent broadcast since the last call
+    pub fn latest(&mut self) -> Option<Broadcast> {
+        let mut latest = None;
+        loop {
+            match self.broadcasts.try_recv() {
+                Ok(broadcast) => latest = Some(broadcast),
+                Err(TryRecvError::Empty) => return latest,
+                Err(TryRecvError::Disconnected) => {
+                    self.is_connected = false;
+                    return latest;
+                }
+            }
+        }
+    }
+
+    /// False once the player closed the game
+    pub fn is_connected(&self) -> bool {
+        self.is_connected
+    }
+}
@@ -0,0 +1,272 @@
+//! Openers: setups built with the first tetrominoes of a game, dealt in a known order.
+//! They are read from `openers.toml`, where each step is the board once a tetromino of the
+//! opener locked, and the practice checks every tetromino locks where its step expects it.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::OnceLock;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::fumen;
+use crate::tetris::{MAP_WIDTH, Map, TRUE_MAP_HEIGHT};
+use crate::tetromino_type::TetrominoType;
+use crate::toml::{self, Value};
+
+/// A tetromino of the opener and the cells `(row, column)` of the map it locks on, in
+/// reading order
+pub type Step = (TetrominoType, [(i8, i8); 4]);
+
+const DEFINITIONS: &str = include_str!("../openers.toml");
+
+static BUILT_IN: OnceLock<BTreeMap<String, Opener>> = OnceLock::new();
+
+#[derive(Debug)]
+pub struct Opener {
+    pub name: String,
+    /// Tetrominoes dealt first, in order, before the random bags
+    bag: Vec<TetrominoType>,
+    /// Where each tetromino of the opener locks, in the order they lock
+    steps: Vec<Step>,
+}
+
+/// How far the player got in the opener
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Practice {
+    pub placed: usize,   // Tetrominoes locked where the opener expects them
+    pub is_missed: bool, // Whether a tetromino locked anywhere else
+}
+
+/// Opener of the table `id` of `openers.toml`
+pub fn built_in(id: &str) -> &'static Opener {
+    BUILT_IN
+        .get_or_init(|| parse(DEFINITIONS).expect("built-in openers are valid"))
+        .get(id)
+        .expect("built-in openers are all defined")
+}
+
+/// Openers of a TOML file by table name, with a `name`, a `bag` of letters, and either
+/// `steps`, a list of boards written as rows of letters, or a `fumen` with a page per step
+fn parse(text: &str) -> Result<BTreeMap<String, Opener>, Box<dyn Error>> {
+    let mut document = toml::parse(text)?;
+    if let Some(key) = document.remove("").unwrap_or_default().keys().next() {
+        return Err(format!("unknown key {key}").into());
+    }
+    document
+        .into_iter()
+        .map(|(id, keys)| {
+            let opener = Opener::parse(keys).map_err(|error| format!("[{id}] {error}"))?;
+            Ok((id, opener))
+        })
+        .collect()
+}
+
+impl Opener {
+    pub fn bag(&self) -> &[TetrominoType] {
+        &self.bag
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    fn parse(mut keys: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> {
+        let mut string = |key: &str| match keys.remove(key) {
+            Some(Value::String(string)) => Ok(string),
+            _ => Err(format!("{key} has to be a string")),
+        };
+        let name = string("name")?;
+        let bag: Vec<TetrominoType> = string("bag")?
+            .chars()
+            .map(TetrominoType::from_letter)
+            .collect();
+        if bag.contains(&TetrominoType::E) {
+            return Err("bag has to be tetromino letters".into());
+        }
+        let boards = match (keys.remove("steps"), keys.remove("fumen")) {
+            (Some(steps), None) => {
+                diagrams(&steps).ok_or("steps have to be lists of rows of letters")?
+            }
+            (None, Some(Value::String(data))) => fumen::decode(&data)?
+                .into_iter()
+                .map(|page| page.map)
+                .collect(),
+            _ => return Err("needs either steps or a fumen string".into()),
+        };
+        if let Some(key) = keys.keys().next() {
+            return Err(format!("unknown key {key}").into());
+        }
+        Ok(Self {
+            name,
+            bag,
+            steps: steps(&boards)?,
+        })
+    }
+}
+
+impl Practice {
+    /// Whether every tetromino of `opener` locked where it should
+    pub fn is_done(&self, opener: &Opener) -> bool {
+        self.placed >= opener.steps().len()
+    }
+
+    /// Where the next tetromino has to lock, `None` after a miss or once the opener is built
+    pub fn step(&self, opener: &Opener) -> Option<Step> {
+        if self.is_missed {
+            return None;
+        }
+        opener.steps().get(self.placed).copied()
+    }
+}
+
+/// Boards written as rows of letters, the last one on the floor
+fn diagrams(steps: &Value) -> Option<Vec<Map>> {
+    let Value::Array(steps) = steps else {
+        return None;
+    };
+    steps
+        .iter()
+        .map(|step| {
+            let Value::Array(rows) = step else {
+                return None;
+            };
+            let mut map = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+            let top = TRUE_MAP_HEIGHT.checked_sub(rows.len())?;
+            for (y, row) in rows.iter().enumerate() {
+                let Value::String(row) = row else {
+                    return None;
+                };
+                if row.chars().count() > MAP_WIDTH {
+                    return None;
+                }
+                for (x, letter) in row.chars().enumerate() {
+                    map[top + y][x] = TetrominoType::from_letter(letter);
+                }
+            }
+            Some(map)
+        })
+        .collect()
+}
+
+/// The tetromino each board adds to the one before, once the full lines of that one are
+/// cleared
+fn steps(boards: &[Map]) -> Result<Vec<Step>, Box<dyn Error>> {
+    let mut previous = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+    let mut steps = Vec::new();
+    for (index, board) in boards.iter().enumerate() {
+        let mut added = Vec::new();
+        for (y, (row, before)) in board.iter().zip(&previous).enumerate() {
+            for (x, (block, was)) in row.iter().zip(before).enumerate() {
+                match (*was, *block) {
+                    (TetrominoType::E, TetrominoType::E) => {}
+                    (TetrominoType::E, shape) => added.push((shape, (y as i8, x as i8))),
+                    (_, TetrominoType::E) => {
+                        return Err(format!("step {} removes blocks", index + 1).into());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let shape = added.first().map_or(TetrominoType::E, |(shape, _)| *shape);
+        let cells: Vec<(i8, i8)> = added
+            .iter()
+            .filter(|(block, _)| *block == shape)
+            .map(|(_, cell)| *cell)
+            .collect();
+        match cells.try_into() {
+            Ok(cells) if added.len() == 4 && shape != TetrominoType::G => {
+                steps.push((shape, cells));
+            }
+            _ => return Err(format!("step {} has to add one tetromino", index + 1).into()),
+        }
+        let rows: Vec<[TetrominoType; MAP_WIDTH]> = board
+            .iter()
+            .filter(|row| row.contains(&TetrominoType::E))
+            .copied()
+            .collect();
+        previous = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+        previous[TRUE_MAP_HEIGHT - rows.len()..].copy_from_slice(&rows);
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_openers() {
+        let openers = parse(DEFINITIONS).unwrap();
+        for (id, name, bag, steps) in [
+            ("tki", "TKI", 7, 7),
+            ("pco", "PCO", 7, 6),
+            ("dt", "DT cannon", 14, 14),
+        ] {
+            let opener = &openers[id];
+            assert_eq!(opener.name, name);
+            assert_eq!((opener.bag.len(), opener.steps.len()), (bag, steps));
+        }
+        // Steps lock in the order they come, the shapes repeating in the second bag
+        let shapes: String = openers["dt"]
+            .steps
+            .iter()
+            .map(|(shape, _)| shape.letter())
+            .collect();
+        assert_eq!(shapes, "ILZJSOILZOJSTT");
+        // The held T of the DT cannon goes in once the T-Spin Double cleared its two lines
+        let (_, triple) = openers["dt"].steps[13];
+        assert_eq!(triple, [(21, 1), (22, 1), (22, 2), (23, 1)]);
+    }
+
+    #[test]
+    fn steps_are_what_each_board_adds() {
+        let text = r#"
+            [line]
+            name = "Line"
+            bag = "IIOI"
+            steps = [
+                ["IIII......"],
+                ["IIIIIIII.."],
+                ["........OO", "IIIIIIIIOO"],
+                # The full line of the step before is gone
+                ["IIII....OO"],
+            ]
+        "#;
+        let openers = parse(text).unwrap();
+        let steps = &openers["line"].steps;
+        assert_eq!(steps[2].0, TetrominoType::O);
+        assert_eq!(
+            steps[3],
+            (TetrominoType::I, [(23, 0), (23, 1), (23, 2), (23, 3)])
+        );
+
+        for (steps, error) in [
+            (
+                r#"[["IIII......"], ["....IIII.."]]"#,
+                "step 2 removes blocks",
+            ),
+            (
+                r#"[["IIII......"], ["IIIIOO....", "IIIIOO...."]]"#,
+                "step 2 has to add one tetromino",
+            ),
+            (r#"[["IIIIII...."]]"#, "step 1 has to add one tetromino"),
+            (
+                r#"["IIII......"]"#,
+                "steps have to be lists of rows of letters",
+            ),
+        ] {
+            let text = format!("[bad]\nname = \"Bad\"\nbag = \"IO\"\nsteps = {steps}");
+            assert_eq!(
+                parse(&text).unwrap_err().to_string(),
+                format!("[bad] {error}")
+            );
+        }
+        let text = "[bad]\nname = \"Bad\"\nbag = \"IO\"\nfumen = \"v115@vhAAgH\"";
+        assert_eq!(
+            parse(text).unwrap_err().to_string(),
+            "[bad] step 1 has to add one tetromino"
+        );
+    }
+}
@@ -0,0 +1,429 @@
+//! Perfect clear solver: a depth first search over the placements of the coming tetrominoes,
+//! holding or not, looking for the ones emptying the whole board without the stack ever
+//! going above a given number of lines. Perfect clears of fewer lines are looked for first.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::board::Board;
+use crate::fumen;
+use crate::placement;
+use crate::rotation_system::RotationSystem;
+use crate::tetris::{MAP_WIDTH, Map, TRUE_MAP_HEIGHT, Tetris};
+use crate::tetromino::Tetromino;
+use crate::tetromino_type::TetrominoType;
+
+/// Lines a perfect clear usually fits in
+pub const DEFAULT_HEIGHT: usize = 4;
+// Boards explored before giving up, the hints searching again for every tetromino
+const HINT_BUDGET: usize = 20_000;
+const SOLVE_BUDGET: usize = 500_000;
+
+/// A tetromino of a solution and its cells `(row, column)` on the board the search started
+/// from, as if no line had been cleared on the way
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Step {
+    pub shape: TetrominoType,
+    pub cells: [(usize, usize); 4],
+}
+
+// Filled cells of the rows inside the perfect clear, the held tetromino and the next one to come
+type Key = (Vec<u32>, u8, usize);
+// Tetrominoes of a solution and their cells, sorted to compare solutions whatever their order
+type Layout = Vec<(u8, [(usize, usize); 4])>;
+
+struct Search<'a> {
+    rotation_system: RotationSystem,
+    height: usize,
+    queue: &'a [TetrominoType],
+    limit: usize,
+    budget: usize,
+    failed: HashSet<Key>,
+    solutions: Vec<Vec<Step>>,
+    layouts: HashSet<Layout>,
+}
+
+/// Different perfect clears of `map` within `height` lines, `queue` being played in order
+/// after the `hold`, at most `limit` of them, the ones clearing fewer lines first
+pub fn solve(
+    map: &Map,
+    hold: TetrominoType,
+    queue: &[TetrominoType],
+    rotation_system: RotationSystem,
+    height: usize,
+    limit: usize,
+    budget: usize,
+) -> Vec<Vec<Step>> {
+    let mut search = Search {
+        rotation_system,
+        height: 0,
+        queue,
+        limit,
+        budget,
+        failed: HashSet::new(),
+        solutions: Vec::new(),
+        layouts: HashSet::new(),
+    };
+    let board = Board::from(*map);
+    let rows: [usize; TRUE_MAP_HEIGHT] = std::array::from_fn(|row| row);
+    let tetrominoes = queue.len() + (hold != TetrominoType::E) as usize;
+    // Every line up to the top of the stack has to be cleared. The boards that failed are
+    // known to fail whatever the height, as their key only holds the lines left to clear.
+    let stack = (0..TRUE_MAP_HEIGHT)
+        .find(|row| board.row(*row) != 0)
+        .map_or(0, |top| TRUE_MAP_HEIGHT - top);
+    for lines in stack.max(1)..=height.min(TRUE_MAP_HEIGHT) {
+        search.height = lines;
+        if search.is_possible(&board, 0, tetrominoes) {
+            search.search(board.clone(), rows, hold, 0, 0, &mut Vec::new());
+        }
+        if search.solutions.len() >= limit {
+            break;
+        }
+    }
+    search.solutions
+}
+
+/// Perfect clears of the board in a game, with the tetrominoes it shows coming. Only boards of
+/// the standard size are solved.
+pub fn solve_game(game: &Tetris, height: usize, limit: usize, budget: usize) -> Vec<Vec<Step>> {
+    let Some(map) = game.board().to_map() else {
+        return Vec::new();
+    };
+    let mut queue = vec![game.current().shape()];
+    queue.extend(game.nexts());
+    solve(
+        &map,
+        game.hold(),
+        &queue,
+        game.current().rotation_system(),
+        height,
+        limit,
+        budget,
+    )
+}
+
+impl Search<'_> {
+    /// Look for solutions from the board once `next` tetrominoes of the queue came, `rows`
+    /// giving the row of the starting board each row of `board` was. Returns whether any was found.
+    fn search(
+        &mut self,
+        board: Board,
+        rows: [usize; TRUE_MAP_HEIGHT],
+        hold: TetrominoType,
+        next: usize,
+        cleared: usize,
+        path: &mut Vec<Step>,
+    ) -> bool {
+        // An empty board to start with is no perfect clear yet
+        if board.is_empty() && !path.is_empty() {
+            self.add_solution(path);
+            return true;
+        }
+        if self.solutions.len() >= self.limit || self.budget == 0 {
+            return false;
+        }
+        self.budget -= 1;
+        let floor = TRUE_MAP_HEIGHT - (self.height - cleared);
+        let filled = (floor..TRUE_MAP_HEIGHT).map(|row| board.row(row)).collect();
+        let key = (filled, hold as u8, next);
+        if self.failed.contains(&key) {
+            return false;
+        }
+
+        // Tetromino played, held tetromino and next tetromino to come after it
+        let Some(current) = self.queue.get(next).copied() else {
+            return false;
+        };
+        let mut choices = vec![(current, hold, next + 1)];
+        match hold {
+            TetrominoType::E => {
+                if let Some(after) = self.queue.get(next + 1) {
+                    choices.push((*after, current, next + 2));
+                }
+            }
+            held if held != current => choices.push((held, current, next + 1)),
+            _ => {}
+        }
+
+        let mut found = false;
+        for (shape, hold, next) in choices {
+            let remaining = self.queue.len() - next + (hold != TetrominoType::E) as usize;
+            let mut start = Tetromino::new(shape, self.rotation_system, MAP_WIDTH);
+            // Tetrominoes drop one row as they spawn
+            if board.fits(&start, [1, 0]) {
+                start.r#move([1, 0]);
+            }
+            let mut locked = HashSet::new();
+            for placement in placement::placements_on(&board, start) {
+                let cells = placement.cells();
+                if !locked.insert(cells) || cells.iter().any(|(row, _)| *row < floor) {
+                    continue;
+                }
+                let mut after = board.clone();
+                for (row, column) in cells {
+                    after.set(row, column, shape);
+                }
+                let full_lines = after.full_lines();
+                let lines = full_lines.len();
+                let sources = after.collapse(&full_lines);
+                let after_rows = std::array::from_fn(|row| rows[sources[row]]);
+                if !self.is_possible(&after, cleared + lines, remaining) {
+                    continue;
+                }
+                path.push(Step {
+                    shape,
+                    cells: cells.map(|(row, column)| (rows[row], column)),
+                });
+                found |= self.search(after, after_rows, hold, next, cleared + lines, path);
+                path.pop();
+                if self.solutions.len() >= self.limit {
+                    return found;
+                }
+            }
+        }
+        if !found {
+            self.failed.insert(key);
+        }
+        found
+    }
+
+    /// Whether `remaining` tetrominoes could fill the holes left, every empty area closed by
+    /// the stack and the walls taking a whole number of them
+    fn is_possible(&self, board: &Board, cleared: usize, remaining: usize) -> bool {
+        let Some(height) = self.height.checked_sub(cleared) else {
+            return false;
+        };
+        let floor = TRUE_MAP_HEIGHT - height;
+        if (0..floor).any(|row| board.row(row) != 0) {
+            return false;
+        }
+        let mut seen = [[false; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+        let mut empty = 0;
+        for row in floor..TRUE_MAP_HEIGHT {
+            for column in 0..MAP_WIDTH {
+                if board.get(row, column) != TetrominoType::E || seen[row][column] {
+                    continue;
+                }
+                let mut area = 0;
+                let mut stack = vec![(row, column)];
+                seen[row][column] = true;
+                while let Some((row, column)) = stack.pop() {
+                    area += 1;
+                    let neighbours = [
+                        (row.wrapping_sub(1), column),
+                        (row + 1, column),
+                        (row, column.wrapping_sub(1)),
+                        (row, column + 1),
+                    ];
+                    for (row, column) in neighbours {
+                        if (floor..TRUE_MAP_HEIGHT).contains(&row)
+                            && column < MAP_WIDTH
+                            && board.get(row, column) == TetrominoType::E
+                            && !seen[row][column]
+                        {
+                            seen[row][column] = true;
+                            stack.push((row, column));
+                        }
+                    }
+                }
+                if area % 4 != 0 {
+                    return false;
+                }
+                empty += area;
+            }
+        }
+        empty <= remaining * 4
+    }
+
+    fn add_solution(&mut self, path: &[Step]) {
+        let mut layout: Layout = path
+            .iter()
+            .map(|step| (step.shape as u8, step.cells))
+            .collect();
+        layout.sort();
+        if self.layouts.insert(layout) {
+            self.solutions.push(path.to_vec());
+        }
+    }
+}
+
+/// Perfect clear searches run beside a game, one for every tetromino, showing where the
+/// first tetromino of a solution goes
+pub struct Hints {
+    // Locked tetrominoes, current and held ones when the last search started
+    searched: Option<(usize, TetrominoType, TetrominoType)>,
+    search: Option<Receiver<Option<Step>>>,
+}
+
+impl Hints {
+    pub fn new() -> Self {
+        Self {
+            searched: None,
+            search: None,
+        }
+    }
+
+    /// To be called every tick: starts a search when a tetromino spawns or is held, and shows
+    /// its result once found
+    pub fn update(&mut self, game: &mut Tetris) {
+        let state = (game.locked_pieces(), game.current().shape(), game.hold());
+        if game.is_falling() && self.searched != Some(state) {
+            self.searched = Some(state);
+            game.show_hint(None);
+            let board = game.clone();
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let solutions = solve_game(&board, DEFAULT_HEIGHT, 1, HINT_BUDGET);
+                // The game may be gone already
+                let _ = sender.send(solutions.first().map(|solution| solution[0]));
+            });
+            self.search = Some(receiver);
+        }
+        if let Some(hint) = self
+            .search
+            .as_ref()
+            .and_then(|search| search.try_recv().ok())
+        {
+            self.search = None;
+            game.show_hint(
+                hint.map(|step| (step.shape, step.cells.map(|(r, c)| (r as i8, c as i8)))),
+            );
+        }
+    }
+
+    /// Forget the searches of a game that was replaced
+    pub fn reset(&mut self) {
+        self.searched = None;
+        self.search = None;
+    }
+}
+
+/// Print the perfect clears of the first page of `fumen`, `queue` being the letters of the
+/// tetrominoes coming in order and turning with `rotation_system`
+pub fn print_solutions(
+    fumen: &str,
+    queue: &str,
+    hold: Option<char>,
+    rotation_system: RotationSystem,
+    height: usize,
+    limit: usize,
+) -> Result<(), Box<dyn Error>> {
+    let map = fumen::decode(fumen)?
+        .first()
+        .ok_or("the fumen has no page")?
+        .map;
+    let queue: Vec<TetrominoType> = queue.chars().map(TetrominoType::from_letter).collect();
+    let hold = hold
+        .map(TetrominoType::from_letter)
+        .unwrap_or(TetrominoType::E);
+    if queue.contains(&TetrominoType::E) {
+        return Err("the queue can only have the letters IJLOSTZ".into());
+    }
+
+    let solutions = solve(
+        &map,
+        hold,
+        &queue,
+        rotation_system,
+        height,
+        limit,
+        SOLVE_BUDGET,
+    );
+    if solutions.is_empty() {
+        println!("No perfect clear within {height} lines");
+        return Ok(());
+    }
+    for (index, solution) in solutions.iter().enumerate() {
+        let order: String = solution.iter().map(|step| step.shape.letter()).collect();
+        println!("{}. {order}", index + 1);
+        let mut board = map.map(|row| row.map(|cell| cell.letter()));
+        for row in board.iter_mut().flatten() {
+            if *row != '.' {
+                *row = '#';
+            }
+        }
+        for step in solution {
+            for (row, column) in step.cells {
+                board[row][column] = step.shape.letter();
+            }
+        }
+        for row in &board[TRUE_MAP_HEIGHT - height.min(TRUE_MAP_HEIGHT)..] {
+            println!("   {}", row.iter().collect::<String>());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TetrominoType::{G, I, O, T};
+
+    /// Board whose last rows are `rows`, `#` for garbage and `.` for empty cells
+    fn board(rows: &[&str]) -> Map {
+        let mut map = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+        for (line, row) in map[TRUE_MAP_HEIGHT - rows.len()..].iter_mut().zip(rows) {
+            for (cell, letter) in line.iter_mut().zip(row.chars()) {
+                if letter == '#' {
+                    *cell = G;
+                }
+            }
+        }
+        map
+    }
+
+    fn solve_all(map: &Map, queue: &[TetrominoType], height: usize) -> Vec<Vec<Step>> {
+        solve(
+            map,
+            TetrominoType::E,
+            queue,
+            RotationSystem::Srs,
+            height,
+            10,
+            SOLVE_BUDGET,
+        )
+    }
+
+    #[test]
+    fn two_lines_within_four() {
+        let map = board(&["####..####", "####..####"]);
+        for height in [2, 4] {
+            let solutions = solve_all(&map, &[O], height);
+            assert_eq!(solutions.len(), 1, "{height} lines");
+            assert_eq!(solutions[0][0].cells, [(22, 4), (22, 5), (23, 4), (23, 5)]);
+        }
+        assert!(solve_all(&map, &[O], 1).is_empty());
+    }
+
+    #[test]
+    fn four_lines() {
+        let map = board(&["#########.", "#########.", "#########.", "#########."]);
+        let solutions = solve_all(&map, &[I], 4);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0][0].cells, [(20, 9), (21, 9), (22, 9), (23, 9)]);
+        // Held first, then played
+        let solutions = solve(&map, I, &[T], RotationSystem::Srs, 4, 10, SOLVE_BUDGET);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0][0].shape, I);
+    }
+
+    #[test]
+    fn impossible_queues() {
+        let map = board(&["#########.", "#########.", "#########.", "#########."]);
+        assert!(solve_all(&map, &[O, T], 4).is_empty());
+        assert!(solve_all(&map, &[I], 3).is_empty());
+        // Nothing to clear and nothing to play is no perfect clear
+        assert!(solve_all(&board(&[]), &[], 4).is_empty());
+    }
+
+    #[test]
+    fn empty_board() {
+        // Two I on each line and an O beside them
+        let solutions = solve_all(&board(&[]), &[I, I, I, I, O], 2);
+        assert!(!solutions.is_empty());
+        assert!(solutions.iter().all(|solution| solution.len() == 5));
+    }
+}
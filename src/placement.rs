@@ -0,0 +1,221 @@
+//! Every spot the current tetromino can lock on, found with a breadth first search over the
+//! moves, the rotations and the soft drops. Gravity is ignored: the actions are assumed to be
+//! played faster than the tetromino falls.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::action::Action;
+use crate::board::Board;
+use crate::tetris::Tetris;
+use crate::tetromino::{Tetromino, TetrominoTrait};
+
+// Actions explored from every position, the hold and the hard drop excepted
+const STEPS: [Action; 5] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::RotateClockwise,
+    Action::RotateCounterClockwise,
+];
+
+// Rotation state and position of a tetromino, and whether it would lock as a spin
+type Key = (u8, (i8, i8), bool);
+// A tetromino reached by the search, whether it would lock as a spin, and the node it was
+// reached from with the action played there
+type Node = (Tetromino, bool, Option<(usize, Action)>);
+
+/// Where a hard drop locks the tetromino and the shortest way to get there
+#[derive(Clone, Debug)]
+pub struct Placement {
+    cells: [(usize, usize); 4],
+    // Asked by the external bots, which come with the serde feature
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    is_spin: bool,
+    actions: Vec<Action>,
+}
+
+impl Placement {
+    /// Map coordinates `(row, column)` of the four blocks, in reading order
+    pub fn cells(&self) -> [(usize, usize); 4] {
+        self.cells
+    }
+
+    /// Whether the last action before locking is a rotation, as needed by T-Spins
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub fn is_spin(&self) -> bool {
+        self.is_spin
+    }
+
+    /// Fewest actions reaching the placement, ending with a hard drop
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+}
+
+/// Every distinct placement of the current tetromino, the closest ones first.
+/// Empty when the tetromino cannot be controlled.
+pub fn placements(game: &Tetris) -> Vec<Placement> {
+    if !game.is_falling() {
+        return Vec::new();
+    }
+    placements_on(game.board(), game.current().clone())
+}
+
+/// Every distinct placement on `board` of a tetromino starting at `start`
+pub fn placements_on(board: &Board, start: Tetromino) -> Vec<Placement> {
+    let mut placements = Vec::new();
+    let mut locked = HashSet::new();
+    let mut seen: HashSet<Key> = HashSet::new();
+    let mut nodes: Vec<Node> = Vec::new();
+    seen.insert((start.rotation(), start.pos(), false));
+    nodes.push((start, false, None));
+    let mut queue = VecDeque::from([0]);
+
+    while let Some(index) = queue.pop_front() {
+        let (tetromino, is_spin, _) = &nodes[index];
+        let mut drop = 0;
+        while board.fits(tetromino, [drop + 1, 0]) {
+            drop += 1;
+        }
+        // Blocks are never above the map, the tetromino spawns inside it and never goes up
+        let cells = tetromino
+            .cells()
+            .map(|(row, column)| ((row + drop) as usize, column as usize));
+        if locked.insert((cells, *is_spin)) {
+            let is_spin = *is_spin;
+            let mut actions = path(&nodes, index);
+            actions.push(Action::HardDrop);
+            placements.push(Placement {
+                cells,
+                is_spin,
+                actions,
+            });
+        }
+
+        for action in STEPS {
+            let Some(next) = step(board, &nodes[index].0, action, &seen) else {
+                continue;
+            };
+            // Falling even one row loses the spin, so it only matters once on the ground
+            let is_spin = (action == Action::RotateClockwise
+                || action == Action::RotateCounterClockwise)
+                && !board.fits(&next, [1, 0]);
+            if seen.insert((next.rotation(), next.pos(), is_spin)) {
+                nodes.push((next, is_spin, Some((index, action))));
+                queue.push_back(nodes.len() - 1);
+            }
+        }
+    }
+    placements
+}
+
+/// Actions leading from the start to the node at `index`
+fn path(nodes: &[Node], mut index: usize) -> Vec<Action> {
+    let mut actions = Vec::new();
+    while let Some((parent, action)) = nodes[index].2 {
+        actions.push(action);
+        index = parent;
+    }
+    actions.reverse();
+    actions
+}
+
+/// `tetromino` once `action` is played, `None` if it did not move or the move leads to a
+/// position already `seen`
+fn step(
+    board: &Board,
+    tetromino: &Tetromino,
+    action: Action,
+    seen: &HashSet<Key>,
+) -> Option<Tetromino> {
+    let vector = match action {
+        Action::MoveLeft => [0, -1],
+        Action::MoveRight => [0, 1],
+        Action::SoftDrop => [1, 0],
+        Action::RotateClockwise | Action::RotateCounterClockwise => {
+            let mut next = tetromino.clone();
+            let is_rotated = if action == Action::RotateClockwise {
+                next.rotate_clockwise(board)
+            } else {
+                next.rotate_counter_clockwise(board)
+            };
+            // The O tetromino rotates in place
+            return (is_rotated && next.cells() != tetromino.cells()).then_some(next);
+        }
+        Action::HardDrop | Action::Hold => return None,
+    };
+    let (row, column) = tetromino.pos();
+    let pos = (row + vector[0], column + vector[1]);
+    if seen.contains(&(tetromino.rotation(), pos, false)) || !board.fits(tetromino, vector) {
+        return None;
+    }
+    let mut next = tetromino.clone();
+    next.r#move(vector);
+    Some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_mode::GameMode;
+    use crate::rotation_system::RotationSystem;
+    use crate::setup::Setup;
+    use crate::tetris::MAP_WIDTH;
+    use crate::tetromino_type::TetrominoType;
+
+    #[test]
+    fn every_spot_of_an_empty_board() {
+        let board = Board::default();
+        for (letter, expected) in [
+            ('I', 17),
+            ('O', 9),
+            ('T', 34),
+            ('L', 34),
+            ('J', 34),
+            ('S', 17),
+            ('Z', 17),
+        ] {
+            let start = Tetromino::new(
+                TetrominoType::from_letter(letter),
+                RotationSystem::Srs,
+                MAP_WIDTH,
+            );
+            let placements = placements_on(&board, start);
+            let spots: HashSet<_> = placements.iter().map(Placement::cells).collect();
+            assert_eq!(spots.len(), expected, "{letter}");
+            // Everything lies on the floor, reached by ending with a hard drop
+            for placement in &placements {
+                assert!(placement.cells().iter().any(|(row, _)| *row == 23));
+                assert_eq!(placement.actions().last(), Some(&Action::HardDrop));
+            }
+        }
+    }
+
+    #[test]
+    fn t_spin_slot() {
+        let setup = Setup {
+            board: vec![
+                "..G.......".to_string(),
+                "...GGGGGGG".to_string(),
+                "G.GGGGGGGG".to_string(),
+            ],
+            queue: "T".to_string(),
+            hold: None,
+        };
+        let mut game = Tetris::with_setup(GameMode::Endless, &setup);
+        // The overhang keeps the T from dropping in, it has to turn into the slot
+        let slot = [(22, 0), (22, 1), (22, 2), (23, 1)];
+        let found: Vec<Placement> = placements(&game)
+            .into_iter()
+            .filter(|placement| placement.cells() == slot)
+            .collect();
+        assert_eq!(found.len(), 1);
+        assert!(found[0].is_spin());
+
+        for action in found[0].actions() {
+            game.apply(*action);
+        }
+        let clear = game.last_clear().unwrap();
+        assert_eq!(clear.name(), "T-Spin Double");
+    }
+}
@@ -0,0 +1,162 @@
+//! Puzzles: handcrafted challenges read from a directory of JSON files. Each one is a
+//! setup, the starting board and the tetrominoes dealt in order, and a goal to reach with them.
+//!
+//! ```json
+//! {
+//!     "name": "Four at once",
+//!     "goal": { "lines": 4 },
+//!     "queue": "LJOI",
+//!     "stars": [4, 4],
+//!     "board": [
+//!         "GGGGGGGGG.",
+//!         "GGGGGGGGG."
+//!     ]
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+#[cfg(feature = "serde")]
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "serde")]
+use std::path::PathBuf;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::setup::Setup;
+
+pub const DEFAULT_DIRECTORY: &str = "puzzles";
+// Best stars of every puzzle solved, kept in the puzzle directory
+#[cfg(feature = "serde")]
+const PROGRESS_FILE: &str = ".progress.json";
+pub const MAX_STARS: usize = 3;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Goal {
+    /// Clear this many lines
+    Lines(usize),
+    TSpinTriple,
+    PerfectClear,
+    /// Lock this many tetrominoes without topping out
+    Survive(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    /// Most tetrominoes used for three stars, then for two, any solution getting one
+    pub stars: [usize; 2],
+    /// Nothing comes once the queue is played
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub setup: Setup,
+}
+
+impl Goal {
+    pub fn as_str(&self) -> String {
+        match self {
+            Goal::Lines(1) => "Clear a line".to_string(),
+            Goal::Lines(lines) => format!("Clear {lines} lines"),
+            Goal::TSpinTriple => "T-Spin Triple".to_string(),
+            Goal::PerfectClear => "Perfect clear".to_string(),
+            Goal::Survive(pieces) => format!("Survive {pieces} tetrominoes"),
+        }
+    }
+}
+
+impl Puzzle {
+    /// Stars earned by solving the puzzle with `pieces` tetrominoes
+    pub fn stars_for(&self, pieces: usize) -> usize {
+        match pieces {
+            pieces if pieces <= self.stars[0] => MAX_STARS,
+            pieces if pieces <= self.stars[1] => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Every `.json` puzzle of `directory`, ordered by file name
+#[cfg(feature = "serde")]
+pub fn load(directory: &Path) -> Result<Vec<Puzzle>, Box<dyn Error>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(directory)
+        .map_err(|error| {
+            format!(
+                "cannot read the puzzles of {}: {error}",
+                directory.display()
+            )
+        })?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
+            let puzzle: Puzzle = serde_json::from_str(&fs::read_to_string(path)?)
+                .map_err(|error| format!("invalid puzzle {}: {error}", path.display()))?;
+            if puzzle.setup.queue().is_empty() {
+                return Err(format!("the puzzle {} deals no tetromino", path.display()).into());
+            }
+            Ok(puzzle)
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn load(_directory: &Path) -> Result<Vec<Puzzle>, Box<dyn Error>> {
+    Err("puzzles are JSON files, read only with the serde feature".into())
+}
+
+/// Best stars earned on each puzzle, by name, saved whenever it improves
+pub struct Progress {
+    #[cfg(feature = "serde")]
+    path: PathBuf,
+    stars: HashMap<String, usize>,
+}
+
+impl Progress {
+    /// Progress saved in `directory`, nothing solved if there is none yet
+    #[cfg(feature = "serde")]
+    pub fn load(directory: &Path) -> Self {
+        let path = directory.join(PROGRESS_FILE);
+        let stars = fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { path, stars }
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn load(_directory: &Path) -> Self {
+        Self {
+            stars: HashMap::new(),
+        }
+    }
+
+    /// Stars of the best solution of `puzzle`, 0 if it was never solved
+    pub fn stars(&self, puzzle: &Puzzle) -> usize {
+        self.stars.get(&puzzle.name).copied().unwrap_or(0)
+    }
+
+    /// Keep `stars` if they beat the best solution of `puzzle`
+    pub fn record(&mut self, puzzle: &Puzzle, stars: usize) -> Result<(), Box<dyn Error>> {
+        if stars <= self.stars(puzzle) {
+            return Ok(());
+        }
+        self.stars.insert(puzzle.name.clone(), stars);
+        #[cfg(feature = "serde")]
+        fs::write(&self.path, serde_json::to_string_pretty(&self.stars)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,643 @@
+//! Rotation systems as data: for every tetromino the cells of its four rotation states and
+//! the offsets tried, in order, when a turn is blocked. The built-in ones are written below,
+//! a custom one can be loaded from a TOML file.
+
+use std::error::Error;
+use std::path::Path;
+use std::sync::{LazyLock, OnceLock};
+
+use crate::tetromino::Layout;
+use crate::tetromino_type::TetrominoType;
+use crate::toml::{self, Value};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RotationSystem {
+    /// Guideline rotation, pieces spawn flat side down and rotate around their center
+    Srs,
+    /// SRS with mirrored I kicks, turning left and right alike
+    SrsPlus,
+    /// Arika rotation system (TGM), pieces spawn flat side up and stay bottom aligned
+    Ars,
+    /// Nintendo rotation system (NES), right handed and without any kick
+    Nrs,
+    /// SRS shapes turning only where they are
+    NoKicks,
+    /// Loaded from a file with `RotationSystem::load`
+    #[value(skip)]
+    Custom,
+}
+
+// Offsets (rows, columns) tried in order when turning from each rotation state
+type Kicks = [Vec<(i8, i8)>; 4];
+
+/// Everything a rotation system decides about one tetromino
+#[derive(Clone, Debug)]
+struct Piece {
+    layouts: [Layout; 4],
+    clockwise: Kicks,
+    counter_clockwise: Kicks,
+    center_column: bool, // Blocked in the middle column of its box, it doesn't kick (ARS)
+}
+
+/// Pieces of a rotation system, indexed by `shape as usize - 1`
+#[derive(Clone, Debug)]
+pub struct Definition {
+    pieces: [Piece; 7],
+}
+
+// Tetromino types in the order of their values, the empty cell and the garbage left out
+const SHAPES: [TetrominoType; 7] = [
+    TetrominoType::I,
+    TetrominoType::L,
+    TetrominoType::J,
+    TetrominoType::O,
+    TetrominoType::Z,
+    TetrominoType::S,
+    TetrominoType::T,
+];
+
+// Cells of a tetromino in each rotation state inside its box, as (y, x) from the top left
+type States = [[(i8, i8); 4]; 4];
+
+const ARS_I: States = [
+    [(1, 0), (1, 1), (1, 2), (1, 3)],
+    [(0, 2), (1, 2), (2, 2), (3, 2)],
+    [(1, 0), (1, 1), (1, 2), (1, 3)],
+    [(0, 2), (1, 2), (2, 2), (3, 2)],
+];
+const ARS_L: States = [
+    [(1, 0), (1, 1), (1, 2), (2, 0)],
+    [(0, 0), (0, 1), (1, 1), (2, 1)],
+    [(1, 2), (2, 0), (2, 1), (2, 2)],
+    [(0, 1), (1, 1), (2, 1), (2, 2)],
+];
+const ARS_J: States = [
+    [(1, 0), (1, 1), (1, 2), (2, 2)],
+    [(0, 1), (1, 1), (2, 0), (2, 1)],
+    [(1, 0), (2, 0), (2, 1), (2, 2)],
+    [(0, 1), (0, 2), (1, 1), (2, 1)],
+];
+const ARS_O: States = [[(1, 1), (1, 2), (2, 1), (2, 2)]; 4];
+const ARS_Z: States = [
+    [(1, 0), (1, 1), (2, 1), (2, 2)],
+    [(0, 2), (1, 1), (1, 2), (2, 1)],
+    [(1, 0), (1, 1), (2, 1), (2, 2)],
+    [(0, 2), (1, 1), (1, 2), (2, 1)],
+];
+const ARS_S: States = [
+    [(1, 1), (1, 2), (2, 0), (2, 1)],
+    [(0, 0), (1, 0), (1, 1), (2, 1)],
+    [(1, 1), (1, 2), (2, 0), (2, 1)],
+    [(0, 0), (1, 0), (1, 1), (2, 1)],
+];
+const ARS_T: States = [
+    [(1, 0), (1, 1), (1, 2), (2, 1)],
+    [(0, 1), (1, 0), (1, 1), (2, 1)],
+    [(1, 1), (2, 0), (2, 1), (2, 2)],
+    [(0, 1), (1, 1), (1, 2), (2, 1)],
+];
+
+// NES pieces turn around a fixed block, Z, S and I only having two states
+const NRS_I: States = [
+    [(2, 0), (2, 1), (2, 2), (2, 3)],
+    [(0, 2), (1, 2), (2, 2), (3, 2)],
+    [(2, 0), (2, 1), (2, 2), (2, 3)],
+    [(0, 2), (1, 2), (2, 2), (3, 2)],
+];
+const NRS_L: States = [
+    [(1, 0), (1, 1), (1, 2), (2, 0)],
+    [(0, 0), (0, 1), (1, 1), (2, 1)],
+    [(0, 2), (1, 0), (1, 1), (1, 2)],
+    [(0, 1), (1, 1), (2, 1), (2, 2)],
+];
+const NRS_J: States = [
+    [(1, 0), (1, 1), (1, 2), (2, 2)],
+    [(0, 1), (1, 1), (2, 0), (2, 1)],
+    [(0, 0), (1, 0), (1, 1), (1, 2)],
+    [(0, 1), (0, 2), (1, 1), (2, 1)],
+];
+const NRS_O: States = [[(1, 0), (1, 1), (2, 0), (2, 1)]; 4];
+const NRS_Z: States = [
+    [(1, 0), (1, 1), (2, 1), (2, 2)],
+    [(0, 2), (1, 1), (1, 2), (2, 1)],
+    [(1, 0), (1, 1), (2, 1), (2, 2)],
+    [(0, 2), (1, 1), (1, 2), (2, 1)],
+];
+const NRS_S: States = [
+    [(1, 1), (1, 2), (2, 0), (2, 1)],
+    [(0, 1), (1, 1), (1, 2), (2, 2)],
+    [(1, 1), (1, 2), (2, 0), (2, 1)],
+    [(0, 1), (1, 1), (1, 2), (2, 2)],
+];
+const NRS_T: States = [
+    [(1, 0), (1, 1), (1, 2), (2, 1)],
+    [(0, 1), (1, 0), (1, 1), (2, 1)],
+    [(0, 1), (1, 0), (1, 1), (1, 2)],
+    [(0, 1), (1, 1), (1, 2), (2, 1)],
+];
+
+// Guideline kicks, for each rotation state turned from, rows going down
+const SRS_CLOCKWISE: [&[(i8, i8)]; 4] = [
+    &[(0, 0), (0, -1), (-1, -1), (2, 0), (2, -1)],
+    &[(0, 0), (0, 1), (1, 1), (-2, 0), (-2, 1)],
+    &[(0, 0), (0, 1), (-1, 1), (2, 0), (2, 1)],
+    &[(0, 0), (0, -1), (1, -1), (-2, 0), (-2, -1)],
+];
+const SRS_COUNTER_CLOCKWISE: [&[(i8, i8)]; 4] = [
+    &[(0, 0), (0, 1), (-1, 1), (2, 0), (2, 1)],
+    &[(0, 0), (0, 1), (1, 1), (-2, 0), (-2, 1)],
+    &[(0, 0), (0, -1), (-1, -1), (2, 0), (2, -1)],
+    &[(0, 0), (0, -1), (1, -1), (-2, 0), (-2, -1)],
+];
+const SRS_I_CLOCKWISE: [&[(i8, i8)]; 4] = [
+    &[(0, 0), (0, -2), (0, 1), (1, -2), (-2, 1)],
+    &[(0, 0), (0, -1), (0, 2), (-2, -1), (1, 2)],
+    &[(0, 0), (0, 2), (0, -1), (-1, 2), (2, -1)],
+    &[(0, 0), (0, 1), (0, -2), (2, 1), (-1, -2)],
+];
+const SRS_I_COUNTER_CLOCKWISE: [&[(i8, i8)]; 4] = [
+    &[(0, 0), (0, -1), (0, 2), (-2, -1), (1, 2)],
+    &[(0, 0), (0, 2), (0, -1), (-1, 2), (2, -1)],
+    &[(0, 0), (0, 1), (0, -2), (2, 1), (-1, -2)],
+    &[(0, 0), (0, -2), (0, 1), (1, -2), (-2, 1)],
+];
+const SRS_PLUS_I_CLOCKWISE: [&[(i8, i8)]; 4] = [
+    &[(0, 0), (0, 1), (0, -2), (1, -2), (-2, 1)],
+    &[(0, 0), (0, -1), (0, 2), (-2, -1), (1, 2)],
+    &[(0, 0), (0, 2), (0, -1), (-1, 2), (2, -1)],
+    &[(0, 0), (0, 1), (0, -2), (2, 1), (-1, -2)],
+];
+const SRS_PLUS_I_COUNTER_CLOCKWISE: [&[(i8, i8)]; 4] = [
+    &[(0, 0), (0, -1), (0, 2), (1, 2), (-2, -1)],
+    &[(0, 0), (0, -1), (0, 2), (2, -1), (-1, 2)],
+    &[(0, 0), (0, -2), (0, 1), (-1, -2), (2, 1)],
+    &[(0, 0), (0, 1), (0, -2), (-2, 1), (1, -2)],
+];
+const ARS_KICKS: [&[(i8, i8)]; 4] = [&[(0, 0), (0, 1), (0, -1)]; 4];
+const NO_KICKS: [&[(i8, i8)]; 4] = [&[(0, 0)]; 4];
+
+static BUILT_IN: LazyLock<[Definition; 5]> = LazyLock::new(|| {
+    [
+        RotationSystem::Srs,
+        RotationSystem::SrsPlus,
+        RotationSystem::Ars,
+        RotationSystem::Nrs,
+        RotationSystem::NoKicks,
+    ]
+    .map(Definition::built_in)
+});
+static CUSTOM: OnceLock<Definition> = OnceLock::new();
+
+impl RotationSystem {
+    /// Every rotation system, in the order they are numbered over the network
+    pub const ALL: [RotationSystem; 6] = [
+        RotationSystem::Srs,
+        RotationSystem::SrsPlus,
+        RotationSystem::Ars,
+        RotationSystem::Nrs,
+        RotationSystem::NoKicks,
+        RotationSystem::Custom,
+    ];
+
+    /// Cells and kicks of the rotation system. Without a custom one loaded, `Custom` plays
+    /// like SRS, so saves and spectators of a custom game still show something.
+    pub fn definition(&self) -> &'static Definition {
+        match self {
+            RotationSystem::Custom => CUSTOM.get().unwrap_or(&BUILT_IN[0]),
+            built_in => &BUILT_IN[*built_in as usize],
+        }
+    }
+
+    /// Read a custom rotation system from a TOML file, once per run
+    pub fn load(path: &Path) -> Result<RotationSystem, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read {}: {error}", path.display()))?;
+        let definition = Definition::parse(&text)
+            .map_err(|error| format!("invalid rotation system {}: {error}", path.display()))?;
+        CUSTOM
+            .set(definition)
+            .map_err(|_| "a custom rotation system is already loaded")?;
+        Ok(RotationSystem::Custom)
+    }
+}
+
+impl Definition {
+    /// Blocks of `shape` in one rotation state, relative to its position
+    pub fn layout(&self, shape: TetrominoType, rotation: u8) -> &Layout {
+        &self.pieces[shape as usize - 1].layouts[rotation as usize]
+    }
+
+    /// Offsets tried in order when `shape` turns from `rotation`
+    pub fn kicks(&self, shape: TetrominoType, rotation: u8, clockwise: bool) -> &[(i8, i8)] {
+        let piece = &self.pieces[shape as usize - 1];
+        let kicks = if clockwise {
+            &piece.clockwise
+        } else {
+            &piece.counter_clockwise
+        };
+        &kicks[rotation as usize]
+    }
+
+    /// Whether `shape` refuses to kick when its turn is blocked in its middle column first
+    pub fn has_center_column(&self, shape: TetrominoType) -> bool {
+        self.pieces[shape as usize - 1].center_column
+    }
+
+    fn built_in(rotation_system: RotationSystem) -> Self {
+        let pieces = SHAPES.map(|shape| {
+            let (states, clockwise, counter_clockwise) = match (rotation_system, shape) {
+                (RotationSystem::Ars, TetrominoType::I | TetrominoType::O) => {
+                    (ars_states(shape), NO_KICKS, NO_KICKS)
+                }
+                (RotationSystem::Ars, _) => (ars_states(shape), ARS_KICKS, ARS_KICKS),
+                (RotationSystem::Nrs, _) => (nrs_states(shape), NO_KICKS, NO_KICKS),
+                (RotationSystem::NoKicks | RotationSystem::Custom, _) | (_, TetrominoType::O) => {
+                    (srs_states(shape), NO_KICKS, NO_KICKS)
+                }
+                (RotationSystem::Srs, TetrominoType::I) => {
+                    (srs_states(shape), SRS_I_CLOCKWISE, SRS_I_COUNTER_CLOCKWISE)
+                }
+                (RotationSystem::SrsPlus, TetrominoType::I) => (
+                    srs_states(shape),
+                    SRS_PLUS_I_CLOCKWISE,
+                    SRS_PLUS_I_COUNTER_CLOCKWISE,
+                ),
+                _ => (srs_states(shape), SRS_CLOCKWISE, SRS_COUNTER_CLOCKWISE),
+            };
+            Piece {
+                layouts: states.map(Layout::new),
+                clockwise: clockwise.map(<[(i8, i8)]>::to_vec),
+                counter_clockwise: counter_clockwise.map(<[(i8, i8)]>::to_vec),
+                center_column: rotation_system == RotationSystem::Ars
+                    && matches!(
+                        shape,
+                        TetrominoType::L | TetrominoType::J | TetrominoType::T
+                    ),
+            }
+        });
+        Self { pieces }
+    }
+
+    /// Rotation system of a TOML file: an optional `base` naming the built-in system the
+    /// file starts from, SRS by default, then a table for each tetromino it changes, with
+    /// `states`, `clockwise`, `counter_clockwise` and `center_column` keys
+    fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut document = toml::parse(text)?;
+        let top = document.remove("").unwrap_or_default();
+        let mut definition = match top.get("base") {
+            None => BUILT_IN[0].clone(),
+            // The custom system is the one being read
+            Some(Value::String(base)) if base.eq_ignore_ascii_case("custom") => {
+                return Err("base has to be a built-in rotation system".into());
+            }
+            Some(Value::String(base)) => {
+                let base = <RotationSystem as clap::ValueEnum>::from_str(base, true)
+                    .map_err(|_| format!("unknown base {base}"))?;
+                base.definition().clone()
+            }
+            Some(_) => return Err("base has to be the name of a rotation system".into()),
+        };
+        if let Some(key) = top.keys().find(|key| *key != "base") {
+            return Err(format!("unknown key {key}").into());
+        }
+
+        for (name, keys) in document {
+            let shape = match name.chars().collect::<Vec<char>>()[..] {
+                [letter] => TetrominoType::from_letter(letter),
+                _ => TetrominoType::E,
+            };
+            if shape == TetrominoType::E {
+                return Err(format!("[{name}] is not a tetromino").into());
+            }
+            let piece = &mut definition.pieces[shape as usize - 1];
+            for (key, value) in keys {
+                let error = |what: &str| format!("{key} of [{name}] {what}");
+                match key.as_str() {
+                    "states" => {
+                        let states = four(&value, |state| {
+                            let cells = pairs(state, 0..=3)?;
+                            cells.try_into().ok()
+                        })
+                        .ok_or_else(|| error("has to be 4 states of 4 cells from 0 to 3"))?;
+                        piece.layouts = states.map(Layout::new);
+                    }
+                    "clockwise" | "counter_clockwise" => {
+                        let kicks = four(&value, |kicks| pairs(kicks, -4..=4))
+                            .ok_or_else(|| error("has to be 4 lists of offsets from -4 to 4"))?;
+                        if key == "clockwise" {
+                            piece.clockwise = kicks;
+                        } else {
+                            piece.counter_clockwise = kicks;
+                        }
+                    }
+                    "center_column" => match value {
+                        Value::Boolean(center_column) => piece.center_column = center_column,
+                        _ => return Err(error("has to be true or false").into()),
+                    },
+                    _ => return Err(error("is not a known key").into()),
+                }
+            }
+        }
+        Ok(definition)
+    }
+}
+
+/// Values of an array of four arrays, read with `read`
+fn four<T>(value: &Value, read: impl Fn(&Value) -> Option<T>) -> Option<[T; 4]> {
+    let Value::Array(values) = value else {
+        return None;
+    };
+    let values: Vec<T> = values.iter().map(read).collect::<Option<_>>()?;
+    values.try_into().ok()
+}
+
+/// Pairs `[a, b]` of an array, each number inside `range`
+fn pairs(value: &Value, range: std::ops::RangeInclusive<i64>) -> Option<Vec<(i8, i8)>> {
+    let Value::Array(values) = value else {
+        return None;
+    };
+    values
+        .iter()
+        .map(|pair| match pair {
+            Value::Array(pair) => match pair[..] {
+                [Value::Integer(a), Value::Integer(b)]
+                    if range.contains(&a) && range.contains(&b) =>
+                {
+                    Some((a as i8, b as i8))
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// SRS states, each clockwise turn rotating the spawn cells around the center of their box
+fn srs_states(shape: TetrominoType) -> States {
+    let (spawn, size) = match shape {
+        TetrominoType::E | TetrominoType::G => panic!("Empty tetromino shouldn't be here"),
+        TetrominoType::I => ([(1, 0), (1, 1), (1, 2), (1, 3)], 4),
+        TetrominoType::L => ([(0, 2), (1, 0), (1, 1), (1, 2)], 3),
+        TetrominoType::J => ([(0, 0), (1, 0), (1, 1), (1, 2)], 3),
+        // Turning in place, the O tetromino keeps its blocks
+        TetrominoType::O => return [[(0, 1), (0, 2), (1, 1), (1, 2)]; 4],
+        TetrominoType::Z => ([(0, 0), (0, 1), (1, 1), (1, 2)], 3),
+        TetrominoType::S => ([(0, 1), (0, 2), (1, 0), (1, 1)], 3),
+        TetrominoType::T => ([(0, 1), (1, 0), (1, 1), (1, 2)], 3),
+    };
+    let mut states = [spawn; 4];
+    for rotation in 1..4 {
+        states[rotation] = states[rotation - 1].map(|(y, x)| (x, size - 1 - y));
+    }
+    states
+}
+
+fn ars_states(shape: TetrominoType) -> States {
+    match shape {
+        TetrominoType::E | TetrominoType::G => panic!("Empty tetromino shouldn't be here"),
+        TetrominoType::I => ARS_I,
+        TetrominoType::L => ARS_L,
+        TetrominoType::J => ARS_J,
+        TetrominoType::O => ARS_O,
+        TetrominoType::Z => ARS_Z,
+        TetrominoType::S => ARS_S,
+        TetrominoType::T => ARS_T,
+    }
+}
+
+fn nrs_states(shape: TetrominoType) -> States {
+    match shape {
+        TetrominoType::E | TetrominoType::G => panic!("Empty tetromino shouldn't be here"),
+        TetrominoType::I => NRS_I,
+        TetrominoType::L => NRS_L,
+        TetrominoType::J => NRS_J,
+        TetrominoType::O => NRS_O,
+        TetrominoType::Z => NRS_Z,
+        TetrominoType::S => NRS_S,
+        TetrominoType::T => NRS_T,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Kicks of the guideline tables, `(x, y)` with y going up, for each turn:
+    // 0->R, R->2, 2->L, L->0, then 0->L, R->0, 2->R, L->2
+    type Reference = [[(i8, i8); 5]; 8];
+
+    const SRS: Reference = [
+        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    ];
+    const SRS_I: Reference = [
+        [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+        [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+        [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+        [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+        [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+        [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+        [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+        [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    ];
+    const SRS_PLUS_I: Reference = [
+        [(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)],
+        [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+        [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+        [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+        [(0, 0), (-1, 0), (2, 0), (2, -1), (-1, 2)],
+        [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+        [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+        [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    ];
+
+    fn assert_kicks(system: RotationSystem, shape: TetrominoType, reference: &Reference) {
+        let definition = system.definition();
+        for (turn, kicks) in reference.iter().enumerate() {
+            let (rotation, clockwise) = (turn as u8 % 4, turn < 4);
+            let expected: Vec<(i8, i8)> = kicks.iter().map(|(x, y)| (-y, *x)).collect();
+            assert_eq!(
+                definition.kicks(shape, rotation, clockwise),
+                expected,
+                "{system:?} {shape:?} turn {turn}"
+            );
+        }
+    }
+
+    #[test]
+    fn guideline_kicks() {
+        use TetrominoType::{I, J, L, S, T, Z};
+        for shape in [J, L, S, T, Z] {
+            assert_kicks(RotationSystem::Srs, shape, &SRS);
+            assert_kicks(RotationSystem::SrsPlus, shape, &SRS);
+        }
+        assert_kicks(RotationSystem::Srs, I, &SRS_I);
+        assert_kicks(RotationSystem::SrsPlus, I, &SRS_PLUS_I);
+    }
+
+    #[test]
+    fn srs_plus_turns_both_ways_alike() {
+        // Mirrored, turning clockwise from a state is turning counter clockwise from the
+        // mirrored state: R and L swap, 0 and 2 stay
+        let definition = RotationSystem::SrsPlus.definition();
+        for rotation in 0..4 {
+            let mirrored = (4 - rotation) % 4;
+            let clockwise: Vec<(i8, i8)> = definition
+                .kicks(TetrominoType::I, rotation, true)
+                .iter()
+                .map(|(row, column)| (*row, -column))
+                .collect();
+            assert_eq!(
+                definition.kicks(TetrominoType::I, mirrored, false),
+                clockwise
+            );
+        }
+    }
+
+    #[test]
+    fn ars_kicks() {
+        let definition = RotationSystem::Ars.definition();
+        for shape in (1..=7).map(TetrominoType::from_u8) {
+            let expected: &[(i8, i8)] = match shape {
+                TetrominoType::I | TetrominoType::O => &[(0, 0)],
+                _ => &[(0, 0), (0, 1), (0, -1)],
+            };
+            for rotation in 0..4 {
+                assert_eq!(definition.kicks(shape, rotation, true), expected);
+                assert_eq!(definition.kicks(shape, rotation, false), expected);
+            }
+            let center_column = matches!(
+                shape,
+                TetrominoType::L | TetrominoType::J | TetrominoType::T
+            );
+            assert_eq!(definition.has_center_column(shape), center_column);
+        }
+    }
+
+    #[test]
+    fn override_one_piece() {
+        let definition = Definition::parse(
+            r#"
+            base = "ars"
+            [t]
+            states = [
+                [[0, 0], [0, 1], [0, 2], [1, 1]],
+                [[0, 1], [1, 1], [2, 1], [1, 0]],
+                [[1, 0], [1, 1], [1, 2], [0, 1]],
+                [[0, 1], [1, 1], [2, 1], [1, 2]],
+            ]
+            clockwise = [[[0, 0]], [[0, 0], [-1, 0]], [], [[0, 0]]]
+            center_column = false
+            "#,
+        )
+        .unwrap();
+        let ars = RotationSystem::Ars.definition();
+        let t = TetrominoType::T;
+        assert_eq!(
+            definition.layout(t, 0),
+            &Layout::new([(0, 0), (0, 1), (0, 2), (1, 1)])
+        );
+        assert_eq!(definition.kicks(t, 1, true), [(0, 0), (-1, 0)]);
+        assert!(definition.kicks(t, 2, true).is_empty());
+        // What the file leaves out comes from the base
+        assert_eq!(definition.kicks(t, 1, false), ars.kicks(t, 1, false));
+        assert!(!definition.has_center_column(t));
+        for shape in (1..=6).map(TetrominoType::from_u8) {
+            for rotation in 0..4 {
+                assert_eq!(
+                    definition.layout(shape, rotation),
+                    ars.layout(shape, rotation)
+                );
+                assert_eq!(
+                    definition.kicks(shape, rotation, true),
+                    ars.kicks(shape, rotation, true)
+                );
+            }
+            assert_eq!(
+                definition.has_center_column(shape),
+                ars.has_center_column(shape)
+            );
+        }
+    }
+
+    #[test]
+    fn example_file() {
+        let definition = Definition::parse(include_str!("../rotation_systems/example.toml"));
+        let definition = definition.unwrap();
+        let srs = RotationSystem::Srs.definition();
+        let t = TetrominoType::T;
+        for rotation in 0..4 {
+            assert_eq!(definition.layout(t, rotation), srs.layout(t, rotation));
+            let mut kicks = srs.kicks(t, rotation, true).to_vec();
+            kicks.push((-1, 0));
+            assert_eq!(definition.kicks(t, rotation, true), kicks);
+        }
+    }
+
+    #[test]
+    fn refuse_invalid_files() {
+        let error = |text: &str| match Definition::parse(text) {
+            Ok(_) => panic!("{text} was accepted"),
+            Err(error) => error.to_string(),
+        };
+        let states = |states: &str| error(&format!("[T]\nstates = {states}"));
+        let kicks = |kicks: &str| error(&format!("[T]\nclockwise = {kicks}"));
+        let bad_states = "states of [T] has to be 4 states of 4 cells from 0 to 3";
+        let bad_kicks = "clockwise of [T] has to be 4 lists of offsets from -4 to 4";
+
+        // A cell outside the box, 3 cells, 3 states, not a pair
+        let cells = "[[0, 1], [1, 0], [1, 1], [1, 2]]";
+        assert_eq!(
+            states(&format!(
+                "[[[0, 1], [1, 0], [1, 1], [1, 4]], {cells}, {cells}, {cells}]"
+            )),
+            bad_states
+        );
+        assert_eq!(
+            states(&format!(
+                "[[[0, 1], [1, 0], [1, 1]], {cells}, {cells}, {cells}]"
+            )),
+            bad_states
+        );
+        assert_eq!(states(&format!("[{cells}, {cells}, {cells}]")), bad_states);
+        assert_eq!(
+            states(&format!(
+                "[[[0, 1], [1, 0], [1, 1], [1]], {cells}, {cells}, {cells}]"
+            )),
+            bad_states
+        );
+        assert_eq!(states("\"T\""), bad_states);
+        // An offset too far, 3 lists, not a number
+        assert_eq!(kicks("[[[0, 5]], [], [], []]"), bad_kicks);
+        assert_eq!(kicks("[[], [], []]"), bad_kicks);
+        assert_eq!(kicks("[[[0, true]], [], [], []]"), bad_kicks);
+
+        assert_eq!(
+            error("base = \"custom\""),
+            "base has to be a built-in rotation system"
+        );
+        assert_eq!(error("base = \"tgm\""), "unknown base tgm");
+        assert_eq!(
+            error("base = 1"),
+            "base has to be the name of a rotation system"
+        );
+        assert_eq!(error("kicks = true"), "unknown key kicks");
+        assert_eq!(error("[X]"), "[X] is not a tetromino");
+        assert_eq!(error("[TT]"), "[TT] is not a tetromino");
+        assert_eq!(error("[T]\nspin = 1"), "spin of [T] is not a known key");
+        assert_eq!(
+            error("[T]\ncenter_column = 1"),
+            "center_column of [T] has to be true or false"
+        );
+        assert_eq!(error("[T]\n[T]"), "line 2: table [T] written twice");
+    }
+}
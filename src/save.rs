@@ -0,0 +1,124 @@
+//! The solo game in progress, written at every lock and when the player leaves it, to be
+//! continued from the menu exactly where it was. Saves hold every field of `Tetris`, those of
+//! another version are refused rather than misread. So are those of a custom rotation system,
+//! its file not being kept with them.
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::rotation_system::RotationSystem;
+use crate::tetris::Tetris;
+
+pub const DEFAULT_FILE: &str = "save.json";
+/// Bumped whenever the fields of `Tetris` or of anything it holds change
+const VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize)]
+struct Save<G> {
+    version: u32,
+    game: G,
+}
+
+pub fn save(path: &Path, game: &Tetris) -> Result<(), Box<dyn Error>> {
+    // Written beside then moved, so closing the terminal never leaves half a save
+    let temporary = path.with_extension("tmp");
+    let save = Save {
+        version: VERSION,
+        game,
+    };
+    fs::write(&temporary, serde_json::to_string(&save)?)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<Tetris, Box<dyn Error>> {
+    let json = fs::read_to_string(path)
+        .map_err(|error| format!("cannot read {}: {error}", path.display()))?;
+    let invalid = |error| format!("invalid save {}: {error}", path.display());
+    let save: Save<Value> = serde_json::from_str(&json).map_err(invalid)?;
+    if save.version != VERSION {
+        return Err(format!(
+            "{} was saved by another version of the game ({})",
+            path.display(),
+            save.version
+        )
+        .into());
+    }
+    let game: Tetris = serde_json::from_value(save.game).map_err(invalid)?;
+    if game.current().rotation_system() == RotationSystem::Custom {
+        return Err(format!(
+            "{} was played with a custom rotation system",
+            path.display()
+        )
+        .into());
+    }
+    Ok(game)
+}
+
+/// Forget the saved game, once it is over
+pub fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::action::Action;
+    use crate::game_mode::{GameMode, GameSettings};
+    use crate::state::State;
+
+    /// File of the temporary directory only used by one test
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("save-{}-{name}.json", std::process::id()))
+    }
+
+    #[test]
+    fn load_what_was_saved() {
+        let path = path("round-trip");
+        let mut game = Tetris::with_seed(GameMode::Marathon150, 11);
+        game.apply(Action::RotateClockwise);
+        game.apply(Action::HardDrop);
+        save(&path, &game).unwrap();
+        let loaded = load(&path).unwrap();
+        remove(&path).unwrap();
+        assert_eq!(State::of(&loaded), State::of(&game));
+    }
+
+    #[test]
+    fn refuse_other_versions() {
+        let path = path("version");
+        let game = Tetris::with_seed(GameMode::Endless, 1);
+        let save = Save {
+            version: VERSION + 1,
+            game: &game,
+        };
+        fs::write(&path, serde_json::to_string(&save).unwrap()).unwrap();
+        let error = load(&path).err().unwrap();
+        remove(&path).unwrap();
+        assert!(error.to_string().contains("another version"));
+    }
+
+    #[test]
+    fn refuse_custom_rotation_systems() {
+        let path = path("custom");
+        let mut game = Tetris::with_seed(GameMode::Endless, 1);
+        game.apply_settings(GameSettings {
+            rotation_system: Some(RotationSystem::Custom),
+            ..GameSettings::default()
+        });
+        save(&path, &game).unwrap();
+        let error = load(&path).err().unwrap();
+        remove(&path).unwrap();
+        assert!(error.to_string().contains("custom rotation system"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::action::Action;
+use crate::display::crossterm::Config;
+use crate::versus::{PLAYERS, Versus};
+
+/// What a player in the lobby is doing, as seen by another player
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PlayerStatus {
+    Idle,
+    Playing,
+    ChallengedByYou,
+    ChallengesYou,
+}
+
+impl PlayerStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlayerStatus::Idle => "",
+            PlayerStatus::Playing => "playing",
+            PlayerStatus::ChallengedByYou => "challenged",
+            PlayerStatus::ChallengesYou => "challenges you!",
+        }
+    }
+}
+
+struct Player {
+    id: usize,
+    name: String,
+    challenged: Option<usize>, // Player this one wants to play against
+    busy: bool,                // In a game, challenges are refused
+    game: Option<(Arc<Mutex<Match>>, usize)>, // Match started by an accepted challenge, and the board
+}
+
+/// Everyone connected to the server
+#[derive(Default)]
+pub struct Lobby {
+    players: Vec<Player>,
+    next_id: usize,
+}
+
+impl Lobby {
+    /// Add a player, returns its id
+    pub fn join(&mut self, name: String) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.players.push(Player {
+            id,
+            name,
+            challenged: None,
+            busy: false,
+            game: None,
+        });
+        id
+    }
+
+    pub fn leave(&mut self, id: usize) {
+        self.players.retain(|player| player.id != id);
+        for player in self.players.iter_mut() {
+            if player.challenged == Some(id) {
+                player.challenged = None;
+            }
+        }
+    }
+
+    /// The other players with what they are doing
+    pub fn others(&self, viewer: usize) -> Vec<(usize, String, PlayerStatus)> {
+        let challenged = self.player(viewer).and_then(|player| player.challenged);
+        self.players
+            .iter()
+            .filter(|player| player.id != viewer)
+            .map(|player| {
+                let status = if player.busy {
+                    PlayerStatus::Playing
+                } else if player.challenged == Some(viewer) {
+                    PlayerStatus::ChallengesYou
+                } else if challenged == Some(player.id) {
+                    PlayerStatus::ChallengedByYou
+                } else {
+                    PlayerStatus::Idle
+                };
+                (player.id, player.name.clone(), status)
+            })
+            .collect()
+    }
+
+    pub fn name(&self, id: usize) -> Option<&str> {
+        self.player(id).map(|player| player.name.as_str())
+    }
+
+    fn player(&self, id: usize) -> Option<&Player> {
+        self.players.iter().find(|player| player.id == id)
+    }
+
+    fn player_mut(&mut self, id: usize) -> Option<&mut Player> {
+        self.players.iter_mut().find(|player| player.id == id)
+    }
+
+    /// Challenge `to`, the match starts right away if `to` already challenged `from`
+    pub fn challenge(&mut self, from: usize, to: usize, config: &Config) {
+        let (Some(challenger), Some(opponent)) = (self.player(from), self.player(to)) else {
+            return;
+        };
+        if challenger.busy || opponent.busy {
+            return;
+        }
+        if opponent.challenged != Some(from) {
+            if let Some(challenger) = self.player_mut(from) {
+                challenger.challenged = Some(to);
+            }
+            return;
+        }
+
+        // The first to challenge plays the first board
+        let names = [opponent.name.clone(), challenger.name.clone()];
+        let game = Arc::new(Mutex::new(Match::new(names, config)));
+        for (board, id) in [to, from].into_iter().enumerate() {
+            if let Some(player) = self.player_mut(id) {
+                player.challenged = None;
+                player.busy = true;
+                player.game = Some((game.clone(), board));
+            }
+        }
+    }
+
+    /// Match the player has to join, with the board it plays
+    pub fn take_match(&mut self, id: usize) -> Option<(Arc<Mutex<Match>>, usize)> {
+        self.player_mut(id).and_then(|player| player.game.take())
+    }
+
+    pub fn set_busy(&mut self, id: usize, busy: bool) {
+        if let Some(player) = self.player_mut(id) {
+            player.busy = busy;
+            if busy {
+                player.challenged = None;
+            }
+        }
+    }
+}
+
+/// Versus between two clients. Both sessions tick it, whichever gets there first.
+pub struct Match {
+    versus: Versus,
+    names: [String; PLAYERS],
+    tick_rate: Duration,
+    last_tick: Instant,
+    ready: [bool; PLAYERS], // Wants the next round
+    left: [bool; PLAYERS],
+}
+
+impl Match {
+    fn new(names: [String; PLAYERS], config: &Config) -> Self {
+        Self {
+            versus: Versus::new(config.best_of, config.settings),
+            names,
+            tick_rate: config.tick_rate,
+            last_tick: Instant::now(),
+            ready: [false; PLAYERS],
+            left: [false; PLAYERS],
+        }
+    }
+
+    /// Play the ticks that should have happened since the last update
+    pub fn update(&mut self) {
+        if self.versus.round_winner().is_some() || self.left.contains(&true) {
+            self.last_tick = Instant::now();
+            return;
+        }
+        while self.last_tick.elapsed() >= self.tick_rate {
+            self.versus.on_tick();
+            self.last_tick += self.tick_rate;
+        }
+    }
+
+    pub fn apply(&mut self, board: usize, action: Action) {
+        if self.versus.round_winner().is_none() {
+            self.versus.player_mut(board).apply(action);
+        }
+    }
+
+    /// The player of `board` wants the next round, it starts once both want it
+    pub fn ready(&mut self, board: usize) {
+        if self.versus.round_winner().is_none() || self.versus.is_over() {
+            return;
+        }
+        self.ready[board] = true;
+        if self.ready.iter().all(|ready| *ready) {
+            self.versus.next_round();
+            self.ready = [false; PLAYERS];
+            self.last_tick = Instant::now();
+        }
+    }
+
+    pub fn leave(&mut self, board: usize) {
+        self.left[board] = true;
+    }
+
+    pub fn versus(&self) -> &Versus {
+        &self.versus
+    }
+
+    pub fn names(&self) -> &[String; PLAYERS] {
+        &self.names
+    }
+
+    pub fn opponent_left(&self, board: usize) -> bool {
+        self.left[(board + 1) % PLAYERS]
+    }
+
+    /// The player of `board` is ready for the next round but the opponent is not yet
+    pub fn is_waiting(&self, board: usize) -> bool {
+        self.ready[board] && !self.ready[(board + 1) % PLAYERS]
+    }
+}
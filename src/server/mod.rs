@@ -0,0 +1,51 @@
+//! Server mode: every telnet client connected to the port gets its own session,
+//! a lobby where the players can challenge each other or play alone
+
+pub mod lobby;
+mod session;
+mod telnet;
+
+use std::io;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use crate::display::crossterm::Config;
+use crate::server::lobby::Lobby;
+
+pub const DEFAULT_PORT: u16 = 2323;
+
+pub fn serve(port: u16, config: Config) -> io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    println!("Listening on port {port}, play with `telnet <address> {port}`");
+    let lobby = Arc::new(Mutex::new(Lobby::default()));
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("{e}");
+                continue;
+            }
+        };
+        let lobby = lobby.clone();
+        let config = config.clone();
+        thread::spawn(move || {
+            let client = stream
+                .peer_addr()
+                .map(|address| address.to_string())
+                .unwrap_or_default();
+            println!("{client} connected");
+            if let Err(e) = session::run(stream, lobby, config) {
+                eprintln!("{client}: {e}");
+            }
+            println!("{client} disconnected");
+        });
+    }
+    Ok(())
+}
+
+/// A session panicking while holding a lock must not take the whole server down
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
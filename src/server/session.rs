@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::io::Write;
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crossterm::event::KeyCode;
+use crossterm::execute;
+use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::Terminal;
+use ratatui::backend::Backend;
+
+use crate::display::crossterm::{Config, run_menus, wait_end_screen};
+use crate::display::input::Input;
+use crate::display::keymap;
+use crate::display::{lobby_ui, versus_ui};
+use crate::server::lobby::{Lobby, Match, PlayerStatus};
+use crate::server::lock;
+use crate::server::telnet::{DEFAULT_SIZE, NEGOTIATION, TelnetBackend, TelnetInput};
+
+const MAX_NAME_LENGTH: usize = 16;
+/// How often the lobby is redrawn to show who joined or challenged
+const LOBBY_REFRESH: Duration = Duration::from_millis(250);
+
+/// Serve one client from the name prompt until it disconnects
+pub fn run(
+    mut stream: TcpStream,
+    lobby: Arc<Mutex<Lobby>>,
+    config: Config,
+) -> Result<(), Box<dyn Error>> {
+    stream.write_all(&NEGOTIATION)?;
+    let size = Arc::new(Mutex::new(DEFAULT_SIZE));
+    let mut input = TelnetInput::new(stream.try_clone()?, size.clone());
+    let mut terminal = Terminal::new(TelnetBackend::new(stream.try_clone()?, size))?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    terminal.hide_cursor()?;
+    terminal.clear()?;
+
+    let result = ask_name(&mut terminal, &mut input).and_then(|name| {
+        let Some(name) = name else {
+            return Ok(());
+        };
+        let id = lock(&lobby).join(name);
+        let result = run_lobby(&mut terminal, &mut input, &lobby, id, &config);
+        lock(&lobby).leave(id);
+        result
+    });
+
+    // The client may already be gone
+    let _ = execute!(terminal.backend_mut(), LeaveAlternateScreen);
+    let _ = terminal.show_cursor();
+    let _ = stream.shutdown(Shutdown::Both);
+    result
+}
+
+/// `None` if the client leaves instead
+fn ask_name<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let mut name = String::new();
+    loop {
+        terminal.draw(|frame| lobby_ui::draw_name_prompt(frame, &name))?;
+        match input.wait_key()? {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Enter if !name.trim().is_empty() => return Ok(Some(name.trim().to_string())),
+            KeyCode::Backspace => {
+                name.pop();
+            }
+            KeyCode::Char(c) if name.len() < MAX_NAME_LENGTH => name.push(c),
+            _ => {}
+        }
+    }
+}
+
+fn run_lobby<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    lobby: &Mutex<Lobby>,
+    id: usize,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let mut selected = 0;
+    loop {
+        let game = lock(lobby).take_match(id);
+        if let Some((game, board)) = game {
+            run_match(terminal, input, &game, board, config.tick_rate)?;
+            lock(lobby).set_busy(id, false);
+            continue;
+        }
+
+        let (name, others) = {
+            let lobby = lock(lobby);
+            let name = lobby.name(id).unwrap_or_default().to_string();
+            (name, lobby.others(id))
+        };
+        selected = selected.min(others.len().saturating_sub(1));
+        terminal.draw(|frame| lobby_ui::draw(frame, &name, &others, selected))?;
+
+        match input.next_key(LOBBY_REFRESH)? {
+            Some(KeyCode::Esc | KeyCode::Char('q')) => return Ok(()),
+            Some(KeyCode::Up | KeyCode::Char('w')) => selected = selected.saturating_sub(1),
+            Some(KeyCode::Down | KeyCode::Char('s')) => {
+                selected = (selected + 1).min(others.len().saturating_sub(1))
+            }
+            Some(KeyCode::Enter) => {
+                if let Some((other, _, status)) = others.get(selected)
+                    && *status != PlayerStatus::Playing
+                {
+                    lock(lobby).challenge(id, *other, config);
+                }
+            }
+            Some(KeyCode::Char('p')) => {
+                lock(lobby).set_busy(id, true);
+                let result = run_menus(terminal, input, config);
+                lock(lobby).set_busy(id, false);
+                result?;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn run_match<B: Backend>(
+    terminal: &mut Terminal<B>,
+    input: &mut impl Input,
+    game: &Mutex<Match>,
+    board: usize,
+    tick_rate: Duration,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        let is_over = {
+            let mut game = lock(game);
+            game.update();
+            let names: Vec<String> = game
+                .names()
+                .iter()
+                .enumerate()
+                .map(|(id, name)| {
+                    if id == board {
+                        format!("{name} (you)")
+                    } else {
+                        name.clone()
+                    }
+                })
+                .collect();
+            let status = if game.opponent_left(board) {
+                Some("The other player left, Enter to go back to the lobby")
+            } else if game.is_waiting(board) {
+                Some("Waiting for the other player...")
+            } else {
+                None
+            };
+            terminal.draw(|frame| {
+                versus_ui::draw(frame, game.versus(), [&names[0], &names[1]]);
+                if let Some(status) = status {
+                    versus_ui::draw_status(frame, status);
+                }
+            })?;
+            game.versus().is_over() || game.opponent_left(board)
+        };
+        if is_over {
+            let result = wait_end_screen(input);
+            lock(game).leave(board);
+            return result.map(|_| ());
+        }
+
+        let key = input.next_key(tick_rate);
+        let mut game = lock(game);
+        match key {
+            Ok(None) => {}
+            Ok(Some(KeyCode::Esc)) | Err(_) => {
+                game.leave(board);
+                return key.map(|_| ()).map_err(|e| e.into());
+            }
+            Ok(Some(KeyCode::Enter)) => game.ready(board),
+            Ok(Some(key)) => {
+                if let Some(action) = keymap::action(&keymap::SOLO, key) {
+                    game.apply(board, action);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Talking to a telnet client: the terminal is drawn with escape sequences written to the socket
+//! and the keys are parsed from the bytes it sends
+
+use std::io::{self, BufWriter, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossterm::event::KeyCode;
+use ratatui::backend::{Backend, ClearType, CrosstermBackend, WindowSize};
+use ratatui::buffer::Cell;
+use ratatui::layout::{Position, Size};
+
+use crate::display::input::Input;
+use crate::server::lock;
+
+/// Used until the client tells its window size, if it ever does
+pub const DEFAULT_SIZE: Size = Size::new(80, 24);
+
+const IAC: u8 = 255; // Interpret as command
+const SB: u8 = 250; // Start of a subnegotiation
+const SE: u8 = 240; // End of a subnegotiation
+const IP: u8 = 244; // Interrupt, sent for Ctrl-C
+const WILL: u8 = 251;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+const NAWS: u8 = 31; // Negotiate about window size
+/// Longest subnegotiation or control sequence kept, longer ones are dropped
+const MAX_SEQUENCE: usize = 64;
+
+/// Asks the client to send every key right away without echoing it, and to report its size
+pub const NEGOTIATION: [u8; 9] = [IAC, WILL, ECHO, IAC, WILL, SUPPRESS_GO_AHEAD, IAC, DO, NAWS];
+
+/// Keys typed in a telnet client, read by a background thread
+pub struct TelnetInput {
+    keys: Receiver<KeyCode>,
+}
+
+impl TelnetInput {
+    /// Read `stream` until it closes, updating `size` when the client window is resized
+    pub fn new(mut stream: TcpStream, size: Arc<Mutex<Size>>) -> Self {
+        let (sender, keys) = mpsc::channel();
+        thread::spawn(move || {
+            let mut parser = Parser::default();
+            let mut buffer = [0; 256];
+            while let Ok(read) = stream.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                let mut parsed = Vec::new();
+                for byte in &buffer[..read] {
+                    parser.feed(*byte, &mut parsed);
+                }
+                parser.end_of_read(&mut parsed);
+                for event in parsed {
+                    let sent = match event {
+                        Parsed::Key(key) => sender.send(key).is_ok(),
+                        Parsed::Size(new_size) => {
+                            *lock(&size) = new_size;
+                            true
+                        }
+                    };
+                    if !sent {
+                        return;
+                    }
+                }
+            }
+        });
+        Self { keys }
+    }
+}
+
+impl Input for TelnetInput {
+    fn next_key(&mut self, timeout: Duration) -> io::Result<Option<KeyCode>> {
+        match self.keys.recv_timeout(timeout) {
+            Ok(key) => Ok(Some(key)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "the client disconnected",
+            )),
+        }
+    }
+}
+
+enum Parsed {
+    Key(KeyCode),
+    Size(Size),
+}
+
+#[derive(Default)]
+enum State {
+    #[default]
+    Data,
+    CarriageReturn, // Enter may be followed by a line feed or a null byte to skip
+    Command,        // After IAC
+    Option,         // After IAC WILL, WONT, DO or DONT
+    Subnegotiation,
+    SubnegotiationCommand, // IAC inside a subnegotiation
+    Escape,
+    ControlSequence, // After ESC [
+    SingleShift,     // After ESC O, some terminals send the arrows this way
+}
+
+#[derive(Default)]
+struct Parser {
+    state: State,
+    data: Vec<u8>, // Subnegotiation or control sequence being read
+}
+
+impl Parser {
+    fn feed(&mut self, byte: u8, parsed: &mut Vec<Parsed>) {
+        let mut key = |key| parsed.push(Parsed::Key(key));
+        match self.state {
+            State::Data => match byte {
+                IAC => self.state = State::Command,
+                27 => self.state = State::Escape,
+                b'\r' => {
+                    key(KeyCode::Enter);
+                    self.state = State::CarriageReturn;
+                }
+                b'\n' => key(KeyCode::Enter),
+                8 | 127 => key(KeyCode::Backspace),
+                3 => key(KeyCode::Esc), // Ctrl-C
+                b' '..=b'~' => key(KeyCode::Char(byte as char)),
+                _ => {}
+            },
+            State::CarriageReturn => {
+                self.state = State::Data;
+                if byte != b'\n' && byte != 0 {
+                    self.feed(byte, parsed);
+                }
+            }
+            State::Command => {
+                self.state = match byte {
+                    WILL..=DONT => State::Option,
+                    SB => {
+                        self.data.clear();
+                        State::Subnegotiation
+                    }
+                    IP => {
+                        key(KeyCode::Esc);
+                        State::Data
+                    }
+                    _ => State::Data,
+                }
+            }
+            State::Option => self.state = State::Data,
+            State::Subnegotiation => match byte {
+                IAC => self.state = State::SubnegotiationCommand,
+                _ => self.push(byte),
+            },
+            State::SubnegotiationCommand => match byte {
+                SE => {
+                    if let [NAWS, width_high, width_low, height_high, height_low, ..] =
+                        self.data[..]
+                    {
+                        let width = u16::from_be_bytes([width_high, width_low]);
+                        let height = u16::from_be_bytes([height_high, height_low]);
+                        if width > 0 && height > 0 {
+                            parsed.push(Parsed::Size(Size::new(width, height)));
+                        }
+                    }
+                    self.state = State::Data;
+                }
+                IAC => {
+                    self.state = State::Subnegotiation;
+                    self.push(IAC);
+                }
+                _ => self.state = State::Data,
+            },
+            State::Escape => match byte {
+                b'[' => {
+                    self.data.clear();
+                    self.state = State::ControlSequence;
+                }
+                b'O' => self.state = State::SingleShift,
+                27 => key(KeyCode::Esc),
+                _ => {
+                    // Escape alone followed by another key
+                    key(KeyCode::Esc);
+                    self.state = State::Data;
+                    self.feed(byte, parsed);
+                }
+            },
+            State::ControlSequence => match byte {
+                0x40..=0x7e => {
+                    let code = match (byte, self.data.as_slice()) {
+                        (b'A', _) => Some(KeyCode::Up),
+                        (b'B', _) => Some(KeyCode::Down),
+                        (b'C', _) => Some(KeyCode::Right),
+                        (b'D', _) => Some(KeyCode::Left),
+                        (b'~', b"3") => Some(KeyCode::Delete),
+                        _ => None,
+                    };
+                    if let Some(code) = code {
+                        key(code);
+                    }
+                    self.state = State::Data;
+                }
+                _ => self.push(byte),
+            },
+            State::SingleShift => {
+                match byte {
+                    b'A' => key(KeyCode::Up),
+                    b'B' => key(KeyCode::Down),
+                    b'C' => key(KeyCode::Right),
+                    b'D' => key(KeyCode::Left),
+                    _ => {}
+                }
+                self.state = State::Data;
+            }
+        }
+    }
+
+    /// Keep a byte of the sequence being read, a client never ending it would fill the memory
+    fn push(&mut self, byte: u8) {
+        if self.data.len() < MAX_SEQUENCE {
+            self.data.push(byte);
+        } else {
+            self.data.clear();
+            self.state = State::Data;
+        }
+    }
+
+    /// Clients send a whole escape sequence at once, an escape ending a read is the Esc key
+    fn end_of_read(&mut self, parsed: &mut Vec<Parsed>) {
+        if let State::Escape = self.state {
+            parsed.push(Parsed::Key(KeyCode::Esc));
+            self.state = State::Data;
+        }
+    }
+}
+
+/// Crossterm backend writing to the socket, with the size reported by the client
+/// since there is no local terminal to ask
+pub struct TelnetBackend {
+    inner: CrosstermBackend<BufWriter<TcpStream>>,
+    size: Arc<Mutex<Size>>,
+}
+
+impl TelnetBackend {
+    pub fn new(stream: TcpStream, size: Arc<Mutex<Size>>) -> Self {
+        Self {
+            inner: CrosstermBackend::new(BufWriter::new(stream)),
+            size,
+        }
+    }
+}
+
+impl Write for TelnetBackend {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(&mut self.inner)
+    }
+}
+
+impl Backend for TelnetBackend {
+    fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a Cell)>,
+    {
+        self.inner.draw(content)
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        self.inner.hide_cursor()
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        self.inner.show_cursor()
+    }
+
+    fn get_cursor_position(&mut self) -> io::Result<Position> {
+        // Only needed by inline viewports, asking the client would mix the answer with the keys
+        Ok(Position::ORIGIN)
+    }
+
+    fn set_cursor_position<P: Into<Position>>(&mut self, position: P) -> io::Result<()> {
+        self.inner.set_cursor_position(position)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.inner.clear()
+    }
+
+    fn clear_region(&mut self, clear_type: ClearType) -> io::Result<()> {
+        self.inner.clear_region(clear_type)
+    }
+
+    fn size(&self) -> io::Result<Size> {
+        Ok(*lock(&self.size))
+    }
+
+    fn window_size(&mut self) -> io::Result<WindowSize> {
+        Ok(WindowSize {
+            columns_rows: self.size()?,
+            pixels: Size::default(),
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Backend::flush(&mut self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> (Vec<KeyCode>, Vec<Size>) {
+        let mut parser = Parser::default();
+        let mut parsed = Vec::new();
+        for byte in bytes {
+            parser.feed(*byte, &mut parsed);
+        }
+        parser.end_of_read(&mut parsed);
+        let (mut keys, mut sizes) = (Vec::new(), Vec::new());
+        for event in parsed {
+            match event {
+                Parsed::Key(key) => keys.push(key),
+                Parsed::Size(size) => sizes.push(size),
+            }
+        }
+        (keys, sizes)
+    }
+
+    #[test]
+    fn keys_and_sizes() {
+        let (keys, sizes) = parse(b"a\r\n\x1b[A\x1bOD\x1b[3~\x1b");
+        assert_eq!(
+            keys,
+            [
+                KeyCode::Char('a'),
+                KeyCode::Enter,
+                KeyCode::Up,
+                KeyCode::Left,
+                KeyCode::Delete,
+                KeyCode::Esc
+            ]
+        );
+        assert!(sizes.is_empty());
+        let (keys, sizes) = parse(&[IAC, SB, NAWS, 0, 100, 0, 40, IAC, SE, b'x']);
+        assert_eq!(keys, [KeyCode::Char('x')]);
+        assert_eq!(sizes, [Size::new(100, 40)]);
+    }
+
+    #[test]
+    fn endless_sequences_are_dropped() {
+        for prefix in [&[IAC, SB][..], b"\x1b["] {
+            let mut parser = Parser::default();
+            let mut parsed = Vec::new();
+            for byte in prefix.iter().chain(&[b'1'; 10_000]) {
+                parser.feed(*byte, &mut parsed);
+                assert!(parser.data.len() <= MAX_SEQUENCE);
+            }
+            // Back to reading keys
+            assert!(matches!(parser.state, State::Data));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Setups: a starting board with the tetrominoes to play on it, saved as JSON files by the
+//! editor and shared by the puzzles, or written as fumen.
+
+use std::error::Error;
+#[cfg(feature = "serde")]
+use std::fs;
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::fumen;
+use crate::tetris::{MAP_WIDTH, Map, TRUE_MAP_HEIGHT};
+use crate::tetromino_type::TetrominoType;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Setup {
+    /// Rows of the board, the last one on the floor: tetromino letters, `G` or `#` for
+    /// garbage and `.` for empty cells
+    pub board: Vec<String>,
+    /// Letters of the tetrominoes dealt first, in order
+    pub queue: String,
+    /// Letter of the tetromino already held
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub hold: Option<char>,
+}
+
+impl Setup {
+    /// Setup of `map` from its highest filled row
+    pub fn new(map: &Map, queue: &[TetrominoType], hold: TetrominoType) -> Self {
+        let top = map
+            .iter()
+            .position(|row| row.iter().any(|cell| *cell != TetrominoType::E))
+            .unwrap_or(TRUE_MAP_HEIGHT);
+        Self {
+            board: map[top..]
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| match cell {
+                            TetrominoType::G => 'G',
+                            cell => cell.letter(),
+                        })
+                        .collect()
+                })
+                .collect(),
+            queue: queue.iter().map(|shape| shape.letter()).collect(),
+            hold: (hold != TetrominoType::E).then(|| hold.letter()),
+        }
+    }
+
+    pub fn map(&self) -> Map {
+        let mut map = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+        let rows = &self.board[self.board.len().saturating_sub(TRUE_MAP_HEIGHT)..];
+        let top = TRUE_MAP_HEIGHT - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, letter) in row.chars().take(MAP_WIDTH).enumerate() {
+                map[top + y][x] = match letter {
+                    'G' | 'g' | '#' => TetrominoType::G,
+                    letter => TetrominoType::from_letter(letter),
+                };
+            }
+        }
+        map
+    }
+
+    pub fn queue(&self) -> Vec<TetrominoType> {
+        self.queue
+            .chars()
+            .map(TetrominoType::from_letter)
+            .filter(|shape| *shape != TetrominoType::E)
+            .collect()
+    }
+
+    pub fn hold(&self) -> TetrominoType {
+        self.hold
+            .map(TetrominoType::from_letter)
+            .unwrap_or(TetrominoType::E)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let json = fs::read_to_string(path)
+            .map_err(|error| format!("cannot read {}: {error}", path.display()))?;
+        Ok(serde_json::from_str(&json)
+            .map_err(|error| format!("invalid setup {}: {error}", path.display()))?)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn load(_path: &Path) -> Result<Self, Box<dyn Error>> {
+        Err("setup files are JSON, read and written only with the serde feature".into())
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn save(&self, _path: &Path) -> Result<(), Box<dyn Error>> {
+        Err("setup files are JSON, read and written only with the serde feature".into())
+    }
+
+    /// Board of the first page of a fumen, with the hold and the queue of its comment
+    pub fn from_fumen(data: &str) -> Result<Self, Box<dyn Error>> {
+        let pages = fumen::decode(data)?;
+        let page = pages.first().ok_or("the fumen has no page")?;
+        let (hold, queue) = fumen::quiz(&page.comment).unwrap_or((TetrominoType::E, Vec::new()));
+        Ok(Self::new(&page.map, &queue, hold))
+    }
+
+    pub fn to_fumen(&self) -> String {
+        fumen::encode(
+            &self.map(),
+            &fumen::quiz_comment(self.hold(), &self.queue()),
+        )
+    }
+}
@@ -0,0 +1,187 @@
+//! Stable view of a game for the tools outside the engine: debug dumps, replays, bots. Unlike
+//! saves, which follow the fields of `Tetris`, the schema only changes with `VERSION`. Dumps
+//! are written as JSON, or as bincode for the tools reading many of them, where the version
+//! comes first as a little endian `u32`.
+//!
+//! ```json
+//! {
+//!     "version": 1,
+//!     "mode": "Endless",
+//!     "board": ["..........", "...", "IIII..LLLG"],
+//!     "piece": { "shape": "T", "rotation": 0, "row": 2, "column": 3, "cells": [[2, 4], ...] },
+//!     "queue": { "hold": null, "can_hold": true, "next": ["S", "Z", "O", "I", "J"] },
+//!     "stats": { "score": 0, "level": 1, "lines": 0, ... }
+//! }
+//! ```
+
+use std::error::Error;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_mode::GameMode;
+use crate::tetris::{GameOverReason, Tetris};
+use crate::tetromino_type::TetrominoType;
+
+/// Bumped whenever a field is renamed, removed or changes meaning
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub version: u32,
+    pub mode: GameMode,
+    /// Every row of the map, hidden ones included, from the top: tetromino letters, `G` for
+    /// garbage and `.` for empty cells
+    pub board: Vec<String>,
+    /// `None` between a lock and the next spawn, and once the game is over
+    pub piece: Option<Piece>,
+    pub queue: Queue,
+    pub stats: Stats,
+    pub game_over: Option<GameOverReason>,
+    pub is_won: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Piece {
+    pub shape: TetrominoType,
+    /// 0 at spawn, then one more for each clockwise turn
+    pub rotation: u8,
+    /// Top left corner of the rotation box in the map
+    pub row: i8,
+    pub column: i8,
+    /// Map coordinates `(row, column)` of the four blocks
+    pub cells: [(i8, i8); 4],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Queue {
+    pub hold: Option<TetrominoType>,
+    /// Whether the hold can still be used before the next lock
+    pub can_hold: bool,
+    /// Tetrominoes coming next, in order, as shown to the player
+    pub next: Vec<TetrominoType>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub score: usize,
+    /// Level as shown to the player: from 1, or from 0 in Master
+    pub level: usize,
+    pub lines: usize,
+    pub pieces: usize,
+    pub attack: usize,
+    pub pending_garbage: usize,
+    /// Line clears in a row up to the last lock
+    pub combo: usize,
+    pub back_to_back: bool,
+    pub finesse_faults: usize,
+    /// Ticks since the start of the game
+    pub ticks: usize,
+}
+
+impl State {
+    pub fn of(game: &Tetris) -> Self {
+        let piece = game.is_falling().then(|| {
+            let current = game.current();
+            Piece {
+                shape: current.shape(),
+                rotation: current.rotation(),
+                row: current.pos().0,
+                column: current.pos().1,
+                cells: current.cells(),
+            }
+        });
+        Self {
+            version: VERSION,
+            mode: game.mode(),
+            board: game
+                .board()
+                .lines()
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| match cell {
+                            TetrominoType::G => 'G',
+                            cell => cell.letter(),
+                        })
+                        .collect()
+                })
+                .collect(),
+            piece,
+            queue: Queue {
+                hold: (game.hold() != TetrominoType::E).then_some(game.hold()),
+                can_hold: game.can_hold(),
+                next: game.nexts(),
+            },
+            stats: Stats {
+                score: game.score(),
+                level: game.level(),
+                lines: game.cleared_lines(),
+                pieces: game.locked_pieces(),
+                attack: game.total_attack(),
+                pending_garbage: game.pending_garbage(),
+                combo: game.consecutive_clears(),
+                back_to_back: game.back_to_back(),
+                finesse_faults: game.finesse_faults(),
+                ticks: game.time(),
+            },
+            game_over: game.game_over_reason(),
+            is_won: game.is_over() && !game.is_lost(),
+        }
+    }
+
+    /// Write the state of `game` to `state-<tick>.json`, or as bincode to `state-<tick>.bin`
+    pub fn dump(game: &Tetris, binary: bool) -> Result<(), Box<dyn Error>> {
+        let state = Self::of(game);
+        match binary {
+            true => fs::write(
+                format!("state-{}.bin", game.time()),
+                bincode::serialize(&state)?,
+            )?,
+            false => fs::write(
+                format!("state-{}.json", game.time()),
+                serde_json::to_string_pretty(&state)?,
+            )?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+
+    fn state() -> State {
+        let mut game = Tetris::with_seed(GameMode::Endless, 3);
+        game.apply(Action::MoveLeft);
+        game.apply(Action::HardDrop);
+        while !game.is_falling() {
+            game.on_tick();
+        }
+        game.apply(Action::Hold);
+        game.apply(Action::RotateCounterClockwise);
+        State::of(&game)
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let state = state();
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(serde_json::from_str::<State>(&json).unwrap(), state);
+    }
+
+    #[test]
+    fn levels_are_the_ones_shown() {
+        assert_eq!(state().stats.level, 1);
+        let master = State::of(&Tetris::with_seed(GameMode::Master, 3));
+        assert_eq!(master.stats.level, 0);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let state = state();
+        let bytes = bincode::serialize(&state).unwrap();
+        assert_eq!(bytes[..4], VERSION.to_le_bytes());
+        assert_eq!(bincode::deserialize::<State>(&bytes).unwrap(), state);
+    }
+}
@@ -0,0 +1,503 @@
+//! Tetris Bot Protocol: an external bot runs as a child process and exchanges one JSON message
+//! per line over its stdin and stdout. The bot is told the board and the queue, it suggests
+//! placements and the first one it is possible to reach is played.
+
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::action::Action;
+use crate::ai::Controller;
+use crate::placement;
+use crate::tetris::{FPS, MAP_WIDTH, TRUE_MAP_HEIGHT, Tetris};
+use crate::tetromino_type::TetrominoType;
+
+/// Rows of the board sent to the bot, the ones above the map are always empty
+const BOARD_HEIGHT: usize = 40;
+/// Time for the bot to start and answer the first messages
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Map = [[TetrominoType; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+// Tetromino, cells as `Move::cells` and spin of the move being played
+type Target = (TetrominoType, [(usize, usize); 4], Spin);
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Piece {
+    I,
+    O,
+    T,
+    L,
+    J,
+    S,
+    Z,
+}
+
+impl Piece {
+    fn from_tetromino(tetromino: TetrominoType) -> Option<Self> {
+        match tetromino {
+            TetrominoType::I => Some(Piece::I),
+            TetrominoType::O => Some(Piece::O),
+            TetrominoType::T => Some(Piece::T),
+            TetrominoType::L => Some(Piece::L),
+            TetrominoType::J => Some(Piece::J),
+            TetrominoType::S => Some(Piece::S),
+            TetrominoType::Z => Some(Piece::Z),
+            TetrominoType::E | TetrominoType::G => None,
+        }
+    }
+
+    fn tetromino(&self) -> TetrominoType {
+        match self {
+            Piece::I => TetrominoType::I,
+            Piece::O => TetrominoType::O,
+            Piece::T => TetrominoType::T,
+            Piece::L => TetrominoType::L,
+            Piece::J => TetrominoType::J,
+            Piece::S => TetrominoType::S,
+            Piece::Z => TetrominoType::Z,
+        }
+    }
+
+    /// Cells around the center of the piece facing north, the y axis going up
+    fn cells(&self) -> [(i32, i32); 4] {
+        match self {
+            Piece::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            Piece::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            Piece::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+            Piece::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+            Piece::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            Piece::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+            Piece::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct PieceLocation {
+    #[serde(rename = "type")]
+    piece: Piece,
+    orientation: Orientation,
+    x: i32,
+    y: i32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Move {
+    location: PieceLocation,
+    spin: Spin,
+}
+
+impl Move {
+    /// Cells of the placed piece as `(row, column)` of the map, `None` if one is outside of it
+    fn cells(&self) -> Option<[(usize, usize); 4]> {
+        let location = &self.location;
+        let turns = match location.orientation {
+            Orientation::North => 0,
+            Orientation::East => 1,
+            Orientation::South => 2,
+            Orientation::West => 3,
+        };
+        let mut cells = [(0, 0); 4];
+        for (cell, (mut x, mut y)) in cells.iter_mut().zip(location.piece.cells()) {
+            for _ in 0..turns {
+                (x, y) = (y, -x);
+            }
+            let column = usize::try_from(location.x + x).ok()?;
+            let height = usize::try_from(location.y + y).ok()?;
+            if column >= MAP_WIDTH || height >= TRUE_MAP_HEIGHT {
+                return None;
+            }
+            *cell = (TRUE_MAP_HEIGHT - 1 - height, column);
+        }
+        cells.sort_unstable();
+        Some(cells)
+    }
+}
+
+/// Messages sent to the bot
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FrontendMessage {
+    Rules {},
+    Start {
+        hold: Option<Piece>,
+        queue: Vec<Piece>,
+        combo: usize,
+        back_to_back: bool,
+        board: Vec<Vec<Option<char>>>,
+    },
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        chosen: Move,
+    },
+    NewPiece {
+        piece: Piece,
+    },
+    Quit,
+}
+
+/// Messages received from the bot, the other ones are ignored
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BotMessage {
+    Info { name: String },
+    Ready,
+    Error { reason: String },
+    Suggestion { moves: Vec<Move> },
+}
+
+/// Bot following the Tetris Bot Protocol, playing at a fixed number of pieces per second
+pub struct ExternalBot {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<BotMessage>,
+    pieces_per_second: f64,
+    piece: Option<usize>, // Locked pieces count of the board when the suggestion was asked
+    is_started: bool,
+    is_waiting: bool, // For a suggestion
+    // The board as the bot knows it once the move it suggested is played
+    expected_map: Option<Map>,
+    queue: Vec<TetrominoType>, // The current tetromino first
+    hold: TetrominoType,
+    target: Option<Target>,
+    plan: Vec<Action>,
+    planned_from: Option<[(usize, usize); 4]>, // Cells of the tetromino the next action expects
+    done: usize,                               // Actions of the plan already applied
+    ticks: usize,                              // Ticks spent on the current tetromino
+}
+
+impl ExternalBot {
+    /// Start the bot with `command`, its arguments being separated by spaces
+    pub fn launch(command: &str, pieces_per_second: f64) -> Result<Self, Box<dyn Error>> {
+        let mut arguments = command.split_whitespace();
+        let program = arguments.next().ok_or("the bot command is empty")?;
+        let mut child = Command::new(program)
+            .args(arguments)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // Anything the bot logs would be drawn over the game
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("could not start the bot `{program}`: {e}"))?;
+        let stdin = child.stdin.take().ok_or("no stdin for the bot")?;
+        let stdout = child.stdout.take().ok_or("no stdout for the bot")?;
+
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                // Messages of newer versions of the protocol are skipped
+                if let Ok(message) = serde_json::from_str(&line)
+                    && sender.send(message).is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        let mut bot = Self {
+            name: program.to_string(),
+            child,
+            stdin,
+            messages,
+            pieces_per_second: pieces_per_second.max(0.1),
+            piece: None,
+            is_started: false,
+            is_waiting: false,
+            expected_map: None,
+            queue: Vec::new(),
+            hold: TetrominoType::E,
+            target: None,
+            plan: Vec::new(),
+            planned_from: None,
+            done: 0,
+            ticks: 0,
+        };
+        match bot.wait_message()? {
+            BotMessage::Info { name } => bot.name = name,
+            _ => return Err("the bot did not introduce itself".into()),
+        }
+        bot.send(&FrontendMessage::Rules {});
+        match bot.wait_message()? {
+            BotMessage::Ready => Ok(bot),
+            BotMessage::Error { reason } => {
+                Err(format!("the bot refused the rules: {reason}").into())
+            }
+            _ => Err("the bot did not say it was ready".into()),
+        }
+    }
+
+    fn wait_message(&self) -> Result<BotMessage, Box<dyn Error>> {
+        self.messages
+            .recv_timeout(STARTUP_TIMEOUT)
+            .map_err(|_| "the bot did not answer".into())
+    }
+
+    fn send(&mut self, message: &FrontendMessage) {
+        // A bot that quit stops being asked anything, the tetromino then only falls
+        if let Ok(line) = serde_json::to_string(message) {
+            let _ = writeln!(self.stdin, "{line}").and_then(|_| self.stdin.flush());
+        }
+    }
+
+    /// Tell the bot about the new tetromino, starting over if the board is not the one it expects
+    fn sync(&mut self, game: &Tetris) {
+        let queue: Vec<TetrominoType> = std::iter::once(game.current().shape())
+            .chain(game.nexts())
+            .collect();
+        let is_expected = self.is_started
+            && self.expected_map.is_some()
+            && self.expected_map == game.board().to_map()
+            && self.hold == game.hold()
+            && queue.starts_with(&self.queue);
+        if is_expected {
+            for &tetromino in &queue[self.queue.len()..] {
+                if let Some(piece) = Piece::from_tetromino(tetromino) {
+                    self.send(&FrontendMessage::NewPiece { piece });
+                }
+            }
+        } else {
+            if self.is_started {
+                self.send(&FrontendMessage::Stop);
+            }
+            let board = (0..BOARD_HEIGHT)
+                .map(|height| {
+                    (0..MAP_WIDTH)
+                        .map(|x| {
+                            let row = TRUE_MAP_HEIGHT.checked_sub(height + 1)?;
+                            Self::cell(game.board().get(row, x))
+                        })
+                        .collect()
+                })
+                .collect();
+            self.send(&FrontendMessage::Start {
+                hold: Piece::from_tetromino(game.hold()),
+                queue: queue
+                    .iter()
+                    .filter_map(|t| Piece::from_tetromino(*t))
+                    .collect(),
+                combo: game.consecutive_clears(),
+                back_to_back: game.back_to_back(),
+                board,
+            });
+            self.is_started = true;
+        }
+        self.queue = queue;
+        self.hold = game.hold();
+    }
+
+    fn cell(tetromino: TetrominoType) -> Option<char> {
+        match tetromino {
+            TetrominoType::E => None,
+            TetrominoType::G => Some('G'),
+            tetromino => Piece::from_tetromino(tetromino).map(|piece| match piece {
+                Piece::I => 'I',
+                Piece::O => 'O',
+                Piece::T => 'T',
+                Piece::L => 'L',
+                Piece::J => 'J',
+                Piece::S => 'S',
+                Piece::Z => 'Z',
+            }),
+        }
+    }
+
+    /// Play the first suggested move that can be reached
+    fn choose(&mut self, game: &Tetris, moves: &[Move]) {
+        let chosen = moves.iter().find_map(|chosen| {
+            let target = (
+                chosen.location.piece.tetromino(),
+                chosen.cells()?,
+                chosen.spin,
+            );
+            let plan = Self::find_plan(game, target)?;
+            Some((*chosen, target, plan))
+        });
+        let Some((chosen, target, plan)) = chosen else {
+            // Nothing reachable, the bot gets the whole board again on the next tetromino
+            self.plan = vec![Action::HardDrop];
+            self.planned_from = None;
+            self.expected_map = None;
+            return;
+        };
+        self.send(&FrontendMessage::Play { chosen });
+        self.plan = plan;
+        self.planned_from = Self::cells(game);
+        self.target = Some(target);
+        self.expected_map = game.board().to_map().map(|map| Self::place(&map, target));
+
+        // What the bot does with its queue when playing the move
+        let piece = target.0;
+        if self.queue.first() != Some(&piece) {
+            if self.hold == TetrominoType::E && !self.queue.is_empty() {
+                self.hold = self.queue.remove(0);
+            } else if let Some(first) = self.queue.first_mut() {
+                std::mem::swap(first, &mut self.hold);
+            }
+        }
+        if !self.queue.is_empty() {
+            self.queue.remove(0);
+        }
+    }
+
+    fn replan(&mut self, game: &Tetris) {
+        self.plan = self
+            .target
+            .and_then(|target| Self::find_plan(game, target))
+            .unwrap_or(vec![Action::HardDrop]);
+        self.done = 0;
+        self.planned_from = Self::cells(game);
+    }
+
+    /// Actions bringing the current or the held tetromino on `target`, ending with a hard drop.
+    /// Spins have to end with a rotation for the game to count them.
+    fn find_plan(game: &Tetris, target: Target) -> Option<Vec<Action>> {
+        let (shape, cells, spin) = target;
+        let reach = |game: &Tetris| {
+            placement::placements(game)
+                .into_iter()
+                .find(|placement| {
+                    placement.cells() == cells && (spin == Spin::None || placement.is_spin())
+                })
+                .map(|placement| placement.actions().to_vec())
+        };
+        if game.current().shape() == shape {
+            return reach(game);
+        }
+        let mut held = game.clone();
+        held.apply(Action::Hold);
+        if held.current().shape() != shape {
+            return None;
+        }
+        reach(&held).map(|actions| [&[Action::Hold], actions.as_slice()].concat())
+    }
+
+    /// Cells of the current tetromino, as `Move::cells`
+    fn cells(game: &Tetris) -> Option<[(usize, usize); 4]> {
+        let mut cells = [(0, 0); 4];
+        for (cell, (row, column)) in cells.iter_mut().zip(game.current().cells()) {
+            *cell = (usize::try_from(row).ok()?, usize::try_from(column).ok()?);
+        }
+        Some(cells)
+    }
+
+    /// `map` once `target` is locked and the full lines are cleared
+    fn place(map: &Map, (tetromino, cells, _): Target) -> Map {
+        let mut placed = *map;
+        for (row, column) in cells {
+            placed[row][column] = tetromino;
+        }
+        let mut cleared = [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+        let rows: Vec<_> = placed
+            .iter()
+            .filter(|row| row.contains(&TetrominoType::E))
+            .collect();
+        for (to, row) in cleared[TRUE_MAP_HEIGHT - rows.len()..].iter_mut().zip(rows) {
+            *to = *row;
+        }
+        cleared
+    }
+}
+
+impl Controller for ExternalBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_tick(&mut self, game: &mut Tetris) {
+        if !game.is_falling() {
+            return;
+        }
+        if self.piece != Some(game.locked_pieces()) {
+            self.piece = Some(game.locked_pieces());
+            self.sync(game);
+            self.send(&FrontendMessage::Suggest);
+            self.is_waiting = true;
+            self.target = None;
+            self.plan.clear();
+            self.done = 0;
+            self.ticks = 0;
+        }
+        self.ticks += 1;
+
+        if self.is_waiting {
+            loop {
+                match self.messages.try_recv() {
+                    Ok(BotMessage::Suggestion { moves }) => {
+                        self.is_waiting = false;
+                        self.choose(game, &moves);
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => return,
+                }
+            }
+        }
+
+        let ticks_per_piece = FPS as f64 / self.pieces_per_second;
+        let wait = (ticks_per_piece - self.plan.len() as f64).max(0.0) as usize;
+        if self.ticks <= wait || self.done >= self.plan.len() {
+            return;
+        }
+        if self.planned_from.is_some() && self.planned_from != Self::cells(game) {
+            // Gravity moved the tetromino, look for a way from where it is now
+            self.replan(game);
+        }
+        let action = self.plan[self.done];
+        let before = Self::cells(game);
+        game.apply(action);
+        self.done += 1;
+        if before == Self::cells(game) && action != Action::Hold && action != Action::HardDrop {
+            self.replan(game);
+        } else {
+            self.planned_from = Self::cells(game);
+        }
+    }
+
+    fn reset(&mut self) {
+        if self.is_started {
+            self.send(&FrontendMessage::Stop);
+        }
+        self.is_started = false;
+        self.is_waiting = false;
+        self.piece = None;
+        self.plan.clear();
+        // A suggestion for the previous board may still come
+        while self.messages.try_recv().is_ok() {}
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        self.send(&FrontendMessage::Quit);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -1,130 +1,655 @@
+use crate::action::Action;
+use crate::attack::{ClearInfo, TSpin};
+use crate::board::Board;
+use crate::finesse::{self, Finesse, Judgement, Training};
+use crate::fnv::Fnv1a;
+use crate::game_mode::{Delays, GameMode, GameSettings};
+use crate::garbage::GarbageQueue;
+use crate::master;
+use crate::opener::Practice;
+use crate::puzzle::{Goal, Puzzle};
+use crate::rotation_system::RotationSystem;
+use crate::setup::Setup;
 use crate::tetromino::{Tetromino, TetrominoTrait};
 use crate::tetromino_type::TetrominoType;
 use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 use ratatui::prelude::{Line, Span};
 use ratatui::style::{Color, Stylize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 pub const FPS: usize = 60;
-pub const TRUE_MAP_HEIGHT: usize = 22;
+pub const TRUE_MAP_HEIGHT: usize = 24;
 pub const MAP_WIDTH: usize = 10;
 pub const MAP_HEIGHT: usize = 20;
 pub const HIDDEN_ROWS: usize = TRUE_MAP_HEIGHT - MAP_HEIGHT;
+pub const SPAWN_ROW: i8 = HIDDEN_ROWS as i8 - 2; // Tetrominoes spawn just above the visible field
+pub const LOCK_DELAY: usize = FPS / 2;
+pub const CHEESE_HEIGHT: usize = 9; // Garbage lines kept on the map in cheese race
 
+pub type Map = [[TetrominoType; MAP_WIDTH]; TRUE_MAP_HEIGHT];
+
+/// Guideline rule that ended the game, or the end of a puzzle queue
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GameOverReason {
+    /// A new tetromino couldn't spawn, even one row higher
+    BlockOut,
+    /// A tetromino locked entirely above the visible field
+    LockOut,
+    /// Garbage pushed the stack above the top of the map
+    TopOut,
+    /// Every tetromino of the puzzle was played without reaching its goal
+    OutOfPieces,
+}
+
+impl GameOverReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameOverReason::BlockOut => "Block out",
+            GameOverReason::LockOut => "Lock out",
+            GameOverReason::TopOut => "Top out",
+            GameOverReason::OutOfPieces => "Out of tetrominoes",
+        }
+    }
+}
+
+/// Everything drawn for a game, sent to spectators. A game rebuilt from it only displays,
+/// the bags and the randomness are not part of it.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub mode: GameMode,
+    pub board: Board,
+    pub current: Tetromino,
+    pub falling: bool,
+    pub hold: TetrominoType,
+    pub nexts: Vec<TetrominoType>,
+    pub score: usize,
+    pub level: usize,
+    pub cleared_lines: usize,
+    pub cleared_garbage: usize,
+    pub pending_garbage: usize,
+    pub tick: usize,
+    pub total_attack: usize,
+    pub locked_pieces: usize,
+    pub finesse_faults: usize,
+    pub last_finesse: Option<Judgement>,
+    pub training: Option<Training>,
+    pub practice: Option<Practice>,
+    pub puzzle: Option<Puzzle>,
+    pub last_clear: Option<ClearInfo>,
+    pub game_over: Option<GameOverReason>,
+    pub is_won: bool,
+    pub section_times: Vec<usize>,
+    pub gm_qualified: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum Phase {
+    Falling,
+    LineClear(usize), // Remaining ticks before the stack falls
+    Entry(usize),     // Remaining ticks before the next tetromino spawns
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Tetris {
+    mode: GameMode,
+    settings: GameSettings,
     score: usize,
     level: usize,
     cleared_lines: usize,
     hold: TetrominoType,
     bag: Vec<TetrominoType>,
     next_bag: Vec<TetrominoType>,
-    map: [[TetrominoType; MAP_WIDTH]; TRUE_MAP_HEIGHT],
+    board: Board,
     current: Tetromino,
+    garbage: GarbageQueue,
+    cleared_garbage: usize,
     tick: usize,
-    is_blocked: bool,
-    is_lost: bool,
+    game_over: Option<GameOverReason>,
+    is_won: bool,
     has_hold_this_round: bool,
+    phase: Phase,
+    clearing_lines: Vec<usize>,
+    buffered_rotation: i8, // Rotation asked during a delay: 1 clockwise, -1 counter clockwise
+    buffered_hold: bool,   // Hold asked during a delay
+
+    fall_progress: f64, // Fraction of cell the current tetromino has fallen
+    lock_timer: usize,  // Ticks spent on the ground since the last step down
+    soft_drop: usize,   // Cells the current tetromino has been soft dropped
+
+    last_move_rotation: bool, // Whether the last successful move of the current tetromino was a rotation
+    consecutive_clears: usize,
+    back_to_back: bool,
+    last_clear: Option<ClearInfo>,
+    total_attack: usize,
+    outgoing_attack: usize, // Attack left after countering pending garbage, waiting to be sent
+    locked_pieces: usize,
+    finesse: Finesse,
+    training: Option<Training>,
+    practice: Option<Practice>,
+    hint: Option<(TetrominoType, [(i8, i8); 4])>, // Suggested by a solver, until the next lock
+    puzzle: Option<Arc<Puzzle>>,                  // Shared by the copies kept to undo placements
 
-    last_fall: usize,
-    last_lock: usize,
+    combo: usize,
+    section_times: Vec<usize>,
+    gm_qualified: bool,
+
+    rng: ChaCha12Rng, // Only source of randomness, so a seed and the inputs replay a whole game
 }
 
 impl Tetris {
     fn refill_bag(&mut self) {
+        // Puzzles only deal their own queue
+        if self.puzzle.is_some() {
+            return;
+        }
         self.bag = self.next_bag.clone();
         let mut new_bag: Vec<TetrominoType> = (1..8).map(TetrominoType::from_u8).collect();
-        new_bag.shuffle(&mut rand::rng());
+        new_bag.shuffle(&mut self.rng);
         self.next_bag = new_bag;
     }
 
-    pub fn new() -> Self {
+    pub fn new(mode: GameMode) -> Self {
+        Self::with_seed(mode, rand::rng().random())
+    }
+
+    /// Start over in the same mode with the same settings, on a new seed. Puzzles start
+    /// over from their board.
+    pub fn restart(&mut self) {
+        let settings = self.settings;
+        *self = match self.puzzle.take() {
+            Some(puzzle) => Self::with_puzzle(Puzzle::clone(&puzzle)),
+            None => Self::new(self.mode),
+        };
+        self.apply_settings(settings);
+    }
+
+    /// Game on the board of `setup` with its hold, dealing its queue before the random bags
+    pub fn with_setup(mode: GameMode, setup: &Setup) -> Self {
+        let mut tetris = Self::new(mode);
+        tetris.load_setup(setup);
+        tetris
+    }
+
+    /// Game on the board of `puzzle`, dealing its queue and nothing after
+    pub fn with_puzzle(puzzle: Puzzle) -> Self {
+        let mut tetris = Self::new(GameMode::Puzzle);
+        let setup = puzzle.setup.clone();
+        tetris.next_bag = Vec::new();
+        tetris.puzzle = Some(Arc::new(puzzle));
+        tetris.load_setup(&setup);
+        tetris
+    }
+
+    fn load_setup(&mut self, setup: &Setup) {
+        self.board = Board::from(setup.map());
+        self.hold = setup.hold();
+        let queue = setup.queue();
+        let first = if queue.is_empty() {
+            self.current.shape()
+        } else {
+            self.bag = queue.into_iter().rev().collect();
+            self.pop_next()
+        };
+        self.spawn(first);
+    }
+
+    /// Same seed, same mode and same inputs on every tick always give the same game
+    pub fn with_seed(mode: GameMode, seed: u64) -> Self {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
         let mut bag: Vec<TetrominoType> = (1..8).map(TetrominoType::from_u8).collect();
         let mut next_bag: Vec<TetrominoType> = (1..8).map(TetrominoType::from_u8).collect();
-        bag.shuffle(&mut rand::rng());
-        next_bag.shuffle(&mut rand::rng());
-        let current = Tetromino::new(bag.pop().unwrap_or(TetrominoType::E));
-        Self {
+        bag.shuffle(&mut rng);
+        next_bag.shuffle(&mut rng);
+        if let Some(opener) = mode.opener() {
+            bag = opener.bag().iter().rev().copied().collect();
+        }
+        let board = Board::default();
+        let current = Tetromino::new(
+            bag.pop().unwrap_or(TetrominoType::E),
+            mode.rotation_system(),
+            board.width(),
+        );
+        let (garbage_pattern, garbage_change_probability) = mode.garbage_pattern();
+        let mut tetris = Self {
+            mode,
+            settings: GameSettings::default(),
             score: 0,
             level: 0,
             cleared_lines: 0,
             hold: TetrominoType::E,
             bag,
             next_bag,
-            map: [[TetrominoType::E; MAP_WIDTH]; TRUE_MAP_HEIGHT],
+            board,
             current,
+            garbage: GarbageQueue::new(garbage_pattern, garbage_change_probability, rng.random()),
+            cleared_garbage: 0,
             tick: 0,
-            is_blocked: false,
-            is_lost: false,
+            game_over: None,
+            is_won: false,
             has_hold_this_round: false,
-            last_fall: 0,
-            last_lock: 0,
-        }
+            phase: Phase::Falling,
+            clearing_lines: Vec::new(),
+            buffered_rotation: 0,
+            buffered_hold: false,
+            fall_progress: 0.0,
+            lock_timer: 0,
+            soft_drop: 0,
+            last_move_rotation: false,
+            consecutive_clears: 0,
+            back_to_back: false,
+            last_clear: None,
+            total_attack: 0,
+            outgoing_attack: 0,
+            locked_pieces: 0,
+            finesse: Finesse::default(),
+            training: None,
+            practice: mode.opener().map(|_| Practice::default()),
+            hint: None,
+            puzzle: None,
+            combo: 1,
+            section_times: Vec::new(),
+            gm_qualified: true,
+            rng,
+        };
+        tetris.refill_cheese();
+        tetris.start_piece();
+        tetris
     }
 
     pub fn on_tick(&mut self) {
+        if self.is_over() {
+            return;
+        }
         self.tick += 1;
 
-        // will run every
-        //      (0.8 - ((self.level) * 0.007)).powf(self.level) SECONDS
-        if self.last_fall
-            + (FPS as f64 * (0.8 - ((self.level as f64) * 0.007)).powf(self.level as f64)) as usize
-            <= self.tick
+        if let Some(interval) = self.mode.garbage_interval()
+            && self.tick.is_multiple_of(interval)
         {
-            self.last_fall = self.tick;
+            self.garbage.queue(1);
+        }
+
+        match self.phase {
+            Phase::Falling => self.fall(),
+            Phase::LineClear(remaining) if remaining > 1 => {
+                self.phase = Phase::LineClear(remaining - 1)
+            }
+            Phase::LineClear(_) => {
+                self.collapse_lines();
+                self.start_entry(self.delays().line_are);
+            }
+            Phase::Entry(remaining) if remaining > 1 => self.phase = Phase::Entry(remaining - 1),
+            Phase::Entry(_) => self.spawn_next(),
+        }
+    }
+
+    fn fall(&mut self) {
+        // Gravity can be a fraction of cell (slow levels) or several cells (up to 20G) per tick
+        self.fall_progress += self.mode.gravity(self.level, self.board.height());
+        while self.fall_progress >= 1.0 {
+            if !self.can_move([1, 0]) {
+                self.fall_progress = 0.0;
+                break;
+            }
+            self.fall_progress -= 1.0;
+            self.current.r#move([1, 0]);
+            self.lock_timer = 0;
+            self.last_move_rotation = false;
+        }
+
+        //lock after the lock delay once the tetromino touched the ground
+        if self.can_move([1, 0]) {
+            self.lock_timer = 0;
+        } else {
+            self.lock_timer += 1;
+            if self.lock_timer >= self.delays().lock {
+                self.lock_current();
+            }
+        }
+    }
+
+    pub fn apply_settings(&mut self, settings: GameSettings) {
+        self.settings = settings;
+        let (pattern, change_probability) = self.mode.garbage_pattern();
+        self.garbage.set_pattern(
+            settings.garbage_pattern.unwrap_or(pattern),
+            settings
+                .garbage_change_probability
+                .unwrap_or(change_probability),
+        );
+        let width = settings.width.unwrap_or(MAP_WIDTH);
+        let height = settings.height.unwrap_or(MAP_HEIGHT) + HIDDEN_ROWS;
+        if width != self.board.width() || height != self.board.height() {
+            self.resize(width, height);
+        }
+        if self.rotation_system() != self.current.rotation_system() && self.locked_pieces == 0 {
+            self.spawn(self.current.shape());
+        }
+    }
+
+    /// Rotation system chosen for the game, or the one of its mode
+    fn rotation_system(&self) -> RotationSystem {
+        self.settings
+            .rotation_system
+            .unwrap_or(self.mode.rotation_system())
+    }
+
+    /// Play on a board of another size, only before the first lock of a mode allowing it.
+    /// Boards loaded from a setup keep their size.
+    fn resize(&mut self, width: usize, height: usize) {
+        let is_untouched = self.board.is_empty() || self.mode.cheese_lines().is_some();
+        if !self.mode.is_resizable() || self.locked_pieces > 0 || !is_untouched {
+            return;
+        }
+        self.board = Board::new(width, height);
+        self.garbage.set_width(width);
+        self.refill_cheese();
+        self.spawn(self.current.shape());
+    }
+
+    fn delays(&self) -> Delays {
+        let mut delays = self.mode.delays(self.level);
+        if let Some(are) = self.settings.are {
+            delays.are = are;
+            delays.line_are = are;
+        }
+        if let Some(line_clear) = self.settings.line_clear {
+            delays.line_clear = line_clear;
+        }
+        delays
+    }
+
+    fn pop_next(&mut self) -> TetrominoType {
+        let next = self.bag.pop().unwrap_or(TetrominoType::E);
+        if self.bag.is_empty() {
+            self.refill_bag();
+        }
+        next
+    }
+
+    fn spawn_next(&mut self) {
+        let mut next = self.pop_next();
+        // Initial Hold System: hold asked during the delays swaps the piece before it appears
+        if self.buffered_hold {
+            self.buffered_hold = false;
+            self.has_hold_this_round = true;
+            let held = self.hold;
+            self.hold = next;
+            next = if held == TetrominoType::E {
+                self.pop_next()
+            } else {
+                held
+            };
+        }
+        // Once a puzzle queue is empty, the held tetromino is the last one
+        if next == TetrominoType::E {
+            next = std::mem::replace(&mut self.hold, TetrominoType::E);
+        }
+        if next == TetrominoType::E {
+            self.game_over = Some(GameOverReason::OutOfPieces);
+            return;
+        }
+        self.spawn(next);
+        if self.mode == GameMode::Master && !master::is_section_stop(self.level) {
+            self.level += 1;
+        }
+    }
 
-            if self.can_move([1, 0]) {
-                self.r#move([1, 0]);
-                self.is_blocked = false;
+    fn spawn(&mut self, shape: TetrominoType) {
+        self.current = Tetromino::new(shape, self.rotation_system(), self.board.width());
+        self.start_piece();
+        self.phase = Phase::Falling;
+        self.fall_progress = 0.0;
+        self.lock_timer = 0;
+        self.soft_drop = 0;
+        self.last_move_rotation = false;
+
+        // Initial Rotation System: rotation asked during the delays is applied as the piece appears
+        let rotation = match self.buffered_rotation {
+            1 => Some(Action::RotateClockwise),
+            -1 => Some(Action::RotateCounterClockwise),
+            _ => None,
+        };
+        if let Some(rotation) = rotation {
+            if rotation == Action::RotateClockwise {
+                self.current.rotate_clockwise(&self.board);
             } else {
-                self.is_blocked = true;
-                self.last_lock = self.tick;
+                self.current.rotate_counter_clockwise(&self.board);
             }
+            self.finesse.record(rotation);
         }
+        self.buffered_rotation = 0;
 
-        //lock 30 tick after can't move if is_blocked is true
-        if self.last_lock + (FPS / 2) <= self.tick {
-            self.last_lock = self.tick;
+        if !self.can_move([0, 0]) {
+            // Blocked spawn, try one row higher before giving up
+            self.current.r#move([-1, 0]);
+            if !self.can_move([0, 0]) {
+                self.game_over = Some(GameOverReason::BlockOut);
+                return;
+            }
+        }
+        // Guideline tetrominoes drop one row as soon as they spawn
+        if self.can_move([1, 0]) {
+            self.current.r#move([1, 0]);
+        }
+    }
 
-            if !self.can_move([1, 0]) {
-                if self.is_blocked {
-                    self.lock_current();
-                    self.is_blocked = false;
-                } else {
-                    self.is_blocked = true;
-                }
+    /// Start counting the presses of the tetromino that just appeared, and in finesse training
+    /// pick where it has to go
+    fn start_piece(&mut self) {
+        self.finesse.start(&self.current);
+        if self.mode != GameMode::FinesseTraining {
+            return;
+        }
+        let mut training = self.training.unwrap_or(Training {
+            shape: TetrominoType::E,
+            target: [(0, 0); 4],
+            placed: 0,
+            is_retry: false,
+        });
+        // A missed tetromino comes back with the same target, unless it was held
+        if !training.is_retry || training.shape != self.current.shape() {
+            let placements = finesse::placements(&self.current, self.board.width());
+            if let Some((tetromino, _)) =
+                placements.get(self.rng.random_range(0..placements.len().max(1)))
+            {
+                training.shape = self.current.shape();
+                training.target = tetromino.cells();
             }
         }
+        self.training = Some(training);
+    }
+
+    fn start_entry(&mut self, delay: usize) {
+        if delay == 0 {
+            self.spawn_next();
+        } else {
+            self.phase = Phase::Entry(delay);
+        }
     }
 
     fn lock_current(&mut self) {
-        for y in 0..self.current.pieces().len() {
-            for x in 0..self.current.pieces()[y].len() {
-                if self.current.pieces()[y][x] == TetrominoType::E {
-                    continue;
-                }
-                self.map[(self.current.pos().0 + y as i8) as usize]
-                    [(self.current.pos().1 + x as i8) as usize] = self.current.shape();
+        let t_spin = self.t_spin();
+        self.board.lock(&self.current);
+        self.has_hold_this_round = false;
+        self.hint = None;
+        let finesse = self.finesse.judge(&self.current, self.board.width());
+        // Counted even when it ends the game, it is on the board
+        self.locked_pieces += 1;
+
+        // Cells come in reading order, the last one is the lowest
+        let lowest_row = self.current.cells()[3].0;
+        if lowest_row < HIDDEN_ROWS as i8 {
+            self.game_over = Some(GameOverReason::LockOut);
+            return;
+        }
+        if self.mode == GameMode::FinesseTraining {
+            self.end_training_piece(finesse);
+            return;
+        }
+        self.check_opener();
+
+        self.clearing_lines = self.board.full_lines();
+        let cleared_lines = self.clearing_lines.len();
+        self.cleared_garbage += self
+            .clearing_lines
+            .iter()
+            .filter(|y| self.board.line(**y).contains(&TetrominoType::G))
+            .count();
+
+        let clear = self.clear_info(cleared_lines, t_spin);
+        self.add_score(&clear);
+        self.check_puzzle(&clear);
+        self.total_attack += clear.attack();
+        // Attacking counters the garbage waiting to rise before reaching the opponent
+        self.outgoing_attack += self.garbage.cancel(clear.attack());
+        if cleared_lines > 0 || t_spin != TSpin::None {
+            self.last_clear = Some(clear);
+        }
+
+        if self.is_over() {
+            return;
+        }
+        if cleared_lines == 0 {
+            // Garbage only rises when the player doesn't clear lines
+            let holes = self.garbage.take_holes();
+            self.insert_garbage_rows(&holes);
+            if self.is_over() {
+                return;
             }
+            self.start_entry(self.delays().are);
+        } else if self.delays().line_clear > 0 {
+            self.phase = Phase::LineClear(self.delays().line_clear);
+        } else {
+            self.collapse_lines();
+            self.start_entry(self.delays().line_are);
         }
-        self.current = Tetromino::new(self.bag.pop().unwrap_or(TetrominoType::E));
+    }
 
-        for y in 0..self.current.pieces().len() {
-            for x in 0..self.current.pieces()[y].len() {
-                if self.current.pieces()[y][x] == TetrominoType::E {
-                    continue;
-                }
-                if self.map[(self.current.pos().0 + y as i8) as usize]
-                    [(self.current.pos().1 + x as i8) as usize]
-                    != TetrominoType::E
-                {
-                    self.is_lost = true;
-                }
+    /// The board is emptied after every tetromino, which comes back until it is placed on the
+    /// target with the fewest presses
+    fn end_training_piece(&mut self, finesse: Option<Judgement>) {
+        if let Some(training) = self.training.as_mut() {
+            let is_placed = training.target == self.current.cells()
+                && finesse.is_some_and(|finesse| !finesse.is_fault());
+            if is_placed {
+                training.placed += 1;
+            } else {
+                self.bag.push(self.current.shape());
             }
+            training.is_retry = !is_placed;
         }
+        self.board = Board::new(self.board.width(), self.board.height());
+        self.start_entry(self.delays().are);
+    }
 
-        if self.bag.is_empty() {
-            self.refill_bag();
+    /// Compare the tetromino that just locked with where the opener expects it, before its
+    /// lines are cleared. After a miss or once built, the game goes on without checks.
+    fn check_opener(&mut self) {
+        let (Some(opener), Some(practice)) = (self.mode.opener(), self.practice.as_mut()) else {
+            return;
+        };
+        let Some(step) = practice.step(opener) else {
+            return;
+        };
+        let mut cells = self.current.cells();
+        cells.sort();
+        if (self.current.shape(), cells) == step {
+            practice.placed += 1;
+        } else {
+            practice.is_missed = true;
+        }
+    }
+
+    /// Whether the lock that just happened reached the goal of the puzzle
+    fn check_puzzle(&mut self, clear: &ClearInfo) {
+        let Some(puzzle) = &self.puzzle else {
+            return;
+        };
+        self.is_won |= match puzzle.goal {
+            Goal::Lines(lines) => self.cleared_lines >= lines,
+            Goal::TSpinTriple => clear.t_spin == TSpin::Full && clear.lines == 3,
+            Goal::PerfectClear => clear.perfect_clear,
+            Goal::Survive(pieces) => self.locked_pieces >= pieces,
+        };
+    }
+
+    /// Describe the lock that just happened and update the combo and back to back chains
+    fn clear_info(&mut self, cleared_lines: usize, t_spin: TSpin) -> ClearInfo {
+        // Everything left is in the lines being cleared
+        let perfect_clear = cleared_lines > 0
+            && (0..self.board.height())
+                .all(|y| self.clearing_lines.contains(&y) || self.board.row(y) == 0);
+
+        let mut clear = ClearInfo {
+            lines: cleared_lines,
+            t_spin,
+            perfect_clear,
+            combo: None,
+            back_to_back: false,
+        };
+        if cleared_lines == 0 {
+            self.consecutive_clears = 0;
+            return clear;
         }
-        let cleared_lines = self.check_lines();
+        clear.combo = Some(self.consecutive_clears);
+        self.consecutive_clears += 1;
+        clear.back_to_back = clear.is_difficult() && self.back_to_back;
+        self.back_to_back = clear.is_difficult();
+        clear
+    }
+
+    /// Three corner rule: a T that rotated in place with 3 of its corners blocked is a T-Spin,
+    /// a Mini if one of the corners it points to is free
+    fn t_spin(&self) -> TSpin {
+        if self.current.shape() != TetrominoType::T || !self.last_move_rotation {
+            return TSpin::None;
+        }
+        let cells = self.current.cells();
+        let directions: [(i8, i8); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+        // The center of the T is the only cell with 3 neighbours, the T points away from the missing one
+        let Some((center, back)) = cells.iter().find_map(|(y, x)| {
+            let missing: Vec<&(i8, i8)> = directions
+                .iter()
+                .filter(|(dy, dx)| !cells.contains(&(y + dy, x + dx)))
+                .collect();
+            (missing.len() == 1).then(|| ((*y, *x), *missing[0]))
+        }) else {
+            return TSpin::None;
+        };
+
+        let is_blocked = |dy: i8, dx: i8| self.board.is_blocked(center.0 + dy, center.1 + dx);
+        let corners: [(i8, i8); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+        let blocked = corners
+            .iter()
+            .filter(|(dy, dx)| is_blocked(*dy, *dx))
+            .count();
+        let front_blocked = corners
+            .iter()
+            .filter(|(dy, dx)| (back.0 != 0 && *dy == -back.0) || (back.1 != 0 && *dx == -back.1))
+            .filter(|(dy, dx)| is_blocked(*dy, *dx))
+            .count();
+
+        match (blocked, front_blocked) {
+            (0..3, _) => TSpin::None,
+            (_, 2) => TSpin::Full,
+            _ => TSpin::Mini,
+        }
+    }
+
+    fn add_score(&mut self, clear: &ClearInfo) {
+        if self.mode == GameMode::Master {
+            self.add_master_score(clear);
+            return;
+        }
+        let cleared_lines = clear.lines;
+
         self.score += (self.level + 1)
             * match cleared_lines {
                 0 => 0,
@@ -136,59 +661,185 @@ impl Tetris {
                     panic!("You shouldn't clear that much line at once")
                 }
             };
-        self.cleared_lines += cleared_lines as usize;
+        self.cleared_lines += cleared_lines;
         self.level = self.cleared_lines / 10;
-        self.has_hold_this_round = false;
+        if let Some(level_cap) = self.mode.level_cap() {
+            self.level = self.level.min(level_cap);
+        }
+        if let Some(goal) = self.mode.line_goal() {
+            self.is_won = self.cleared_lines >= goal;
+        }
+        if let Some(goal) = self.mode.cheese_lines() {
+            self.is_won = self.cleared_garbage >= goal;
+        }
     }
 
-    fn check_lines(&mut self) -> u8 {
-        let mut lines: u8 = 0;
-        'row: for y in (0..self.map.len()).rev() {
-            for x in 0..self.map[y].len() {
-                if self.map[y][x] == TetrominoType::E {
-                    // If line has Empty space, it's not empty
-                    continue 'row;
-                }
-            }
+    fn add_master_score(&mut self, clear: &ClearInfo) {
+        let cleared_lines = clear.lines;
+        if cleared_lines == 0 {
+            self.combo = 1;
+            return;
+        }
+        self.combo += 2 * cleared_lines - 2;
+        self.score += master::score(
+            self.level,
+            cleared_lines,
+            self.soft_drop,
+            self.combo,
+            clear.perfect_clear,
+        );
+        self.cleared_lines += cleared_lines;
 
-            lines = self.delete_line(y);
-            self.map[0] = [TetrominoType::E; MAP_WIDTH]; // Clear the top line as it won't be moved from the line -1
+        let previous_level = self.level;
+        self.level = (self.level + cleared_lines).min(master::MAX_LEVEL);
+        if self.level / master::SECTION_LENGTH > previous_level / master::SECTION_LENGTH
+            || self.level == master::MAX_LEVEL
+        {
+            let section_start: usize = self.section_times.iter().sum();
+            self.section_times.push(self.tick - section_start);
         }
-        lines
+        for (level, min_score, max_time) in master::GM_REQUIREMENTS {
+            if previous_level < level && self.level >= level {
+                self.gm_qualified &= self.score >= min_score && self.tick <= max_time;
+            }
+        }
+        self.is_won = self.level == master::MAX_LEVEL;
+    }
+
+    /// Receive an attack, its lines will rise on the next lock that doesn't clear any line
+    pub fn queue_garbage(&mut self, lines: usize) {
+        self.garbage.queue(lines);
+    }
+
+    /// Attack to send to the opponent since the last call
+    pub fn take_outgoing_attack(&mut self) -> usize {
+        std::mem::take(&mut self.outgoing_attack)
+    }
+
+    pub fn pending_garbage(&self) -> usize {
+        self.garbage.pending()
     }
 
-    fn delete_line(&mut self, line: usize) -> u8 {
-        for i in (0..line).rev() {
-            // Move each line below
-            self.map[i + 1] = self.map[i];
+    /// Garbage lines still to clear in cheese race
+    pub fn garbage_left(&self) -> Option<usize> {
+        self.mode
+            .cheese_lines()
+            .map(|goal| goal.saturating_sub(self.cleared_garbage))
+    }
+
+    /// Raise cheese race garbage back to its minimum height, without adding more lines than left to dig
+    fn refill_cheese(&mut self) {
+        let Some(garbage_left) = self.garbage_left() else {
+            return;
+        };
+        let on_map = self
+            .board
+            .lines()
+            .filter(|line| line.contains(&TetrominoType::G))
+            .count();
+        let missing = garbage_left.min(CHEESE_HEIGHT).saturating_sub(on_map);
+        let holes = self.garbage.holes(missing);
+        self.insert_garbage_rows(&holes);
+    }
+
+    /// Push the map up and fill the bottom with garbage lines, `holes` being given from top to bottom
+    fn insert_garbage_rows(&mut self, holes: &[usize]) {
+        let (width, height) = (self.board.width(), self.board.height());
+        let lines = holes.len().min(height);
+        if lines == 0 {
+            return;
         }
-        if !self.map[line].into_iter().any(|x| x == TetrominoType::E)
-        // If we just copied another full line, delete it again
-        {
-            return self.delete_line(line) + 1;
+        if (0..lines).any(|y| self.board.row(y) != 0) {
+            self.game_over = Some(GameOverReason::TopOut);
+        }
+
+        self.board.push_up(lines);
+        for (y, hole) in (height - lines..).zip(holes) {
+            let mut line = vec![TetrominoType::G; width];
+            line[*hole] = TetrominoType::E;
+            self.board.set_line(y, &line);
         }
-        1
+    }
+
+    /// Remove the lines being cleared and move everything above them down
+    fn collapse_lines(&mut self) {
+        self.board.collapse(&self.clearing_lines);
+        self.clearing_lines.clear();
+        self.refill_cheese();
     }
 
     pub fn display_map(&self) -> Vec<Line<'_>> {
-        let mut display_map_data: [[TetrominoType; MAP_WIDTH]; MAP_HEIGHT] =
-            [[TetrominoType::E; MAP_WIDTH]; MAP_HEIGHT];
-        display_map_data.copy_from_slice(&self.map[HIDDEN_ROWS..]);
+        let mut display_map_data: Vec<Vec<TetrominoType>> = self
+            .board
+            .lines()
+            .skip(HIDDEN_ROWS)
+            .map(<[TetrominoType]>::to_vec)
+            .collect();
+
+        if let Phase::LineClear(remaining) = self.phase {
+            // Cleared lines vanish from the center to the sides
+            let progress = 1.0 - remaining as f64 / self.delays().line_clear.max(1) as f64;
+            let half_width = self.board.width() as f64 / 2.0;
+            for y in self.clearing_lines.iter().filter(|y| **y >= HIDDEN_ROWS) {
+                for (x, cell) in display_map_data[y - HIDDEN_ROWS].iter_mut().enumerate() {
+                    if (x as f64 + 0.5 - half_width).abs() < progress * half_width {
+                        *cell = TetrominoType::E;
+                    }
+                }
+            }
+        }
+        if self.phase != Phase::Falling {
+            return Self::display_lines(&display_map_data, None, None);
+        }
 
         //TODO: probably move this to the part where the program create the Span etc.
-        for y in 0..self.current.pieces().len() {
-            for x in 0..self.current.pieces()[y].len() {
-                if self.current.pieces()[y][x] != TetrominoType::E
-                    && y as i8 + self.current.pos().0 >= HIDDEN_ROWS as i8
-                {
-                    display_map_data
-                        [(y as i8 + self.current.pos().0 - HIDDEN_ROWS as i8) as usize]
-                        [(x as i8 + self.current.pos().1) as usize] = self.current.pieces()[y][x];
+        for (y, x) in self.current.cells() {
+            if y >= HIDDEN_ROWS as i8 {
+                display_map_data[y as usize - HIDDEN_ROWS][x as usize] = self.current.shape();
+            }
+        }
+
+        // Training and opener target, drawn under the tetromino
+        let target = self.target().map(|(shape, cells)| {
+            let mut target_map = self.blank_display();
+            for (y, x) in cells {
+                if y >= HIDDEN_ROWS as i8 {
+                    target_map[y as usize - HIDDEN_ROWS][x as usize] = shape;
                 }
             }
+            target_map
+        });
+
+        Self::display_lines(&display_map_data, Some(self.current_shadow()), target)
+    }
+
+    /// Where the current tetromino has to lock in finesse training and while building an
+    /// opener, or where a solver suggests putting it
+    fn target(&self) -> Option<(TetrominoType, [(i8, i8); 4])> {
+        if let Some(training) = self.training {
+            return Some((training.shape, training.target));
+        }
+        if let (Some(opener), Some(practice)) = (self.mode.opener(), self.practice)
+            && let Some(step) = practice.step(opener)
+        {
+            return Some(step);
         }
+        self.hint
+    }
+
+    /// Draw a suggested placement `(shape, cells)` as a target, until the next lock
+    pub fn show_hint(&mut self, hint: Option<(TetrominoType, [(i8, i8); 4])>) {
+        self.hint = hint;
+    }
 
-        let shadow_map = self.current_shadow();
+    fn display_lines(
+        display_map_data: &[Vec<TetrominoType>],
+        shadow_map: Option<Vec<Vec<TetrominoType>>>,
+        target_map: Option<Vec<Vec<TetrominoType>>>,
+    ) -> Vec<Line<'static>> {
+        let cell = |map: &Option<Vec<Vec<TetrominoType>>>, y: usize, x: usize| {
+            map.as_ref().map_or(TetrominoType::E, |map| map[y][x])
+        };
 
         display_map_data
             .iter()
@@ -198,12 +849,14 @@ impl Tetris {
                     row.iter()
                         .enumerate()
                         .map(|(x, col)| {
-                            if col != TetrominoType::E || shadow_map[y][x] == TetrominoType::E {
+                            let (target, shadow) =
+                                (cell(&target_map, y, x), cell(&shadow_map, y, x));
+                            if *col == TetrominoType::E && target != TetrominoType::E {
+                                Span::raw("[]").fg(target.color()).bg(Color::Reset)
+                            } else if *col != TetrominoType::E || shadow == TetrominoType::E {
                                 Span::raw("  ").bg(col.color())
                             } else {
-                                Span::raw("::")
-                                    .fg(shadow_map[y][x].color())
-                                    .bg(Color::Reset)
+                                Span::raw("::").fg(shadow.color()).bg(Color::Reset)
                             }
                         })
                         .collect::<Vec<Span>>(),
@@ -216,59 +869,307 @@ impl Tetris {
         self.score
     }
 
+    /// Level as displayed to the player, starting at 1 except in Master mode where it starts at 0
+    pub fn level(&self) -> usize {
+        match self.mode {
+            GameMode::Master => self.level,
+            _ => self.level + 1,
+        }
+    }
+
+    /// Level needed to complete the current Master section
+    pub fn section_limit(&self) -> usize {
+        master::section_limit(self.level)
+    }
+
+    /// Time spent on each completed Master section, in ticks
+    pub fn section_times(&self) -> &[usize] {
+        &self.section_times
+    }
+
+    pub fn grade(&self) -> &'static str {
+        let grade = master::grade(self.score);
+        if self.is_won && self.gm_qualified && grade == "S9" {
+            return "GM";
+        }
+        grade
+    }
+
+    pub fn last_clear(&self) -> Option<ClearInfo> {
+        self.last_clear
+    }
+
+    /// Attack per minute
+    pub fn apm(&self) -> f64 {
+        if self.tick == 0 {
+            return 0.0;
+        }
+        self.total_attack as f64 * (60 * FPS) as f64 / self.tick as f64
+    }
+
+    /// Attack per piece
+    pub fn app(&self) -> f64 {
+        if self.locked_pieces == 0 {
+            return 0.0;
+        }
+        self.total_attack as f64 / self.locked_pieces as f64
+    }
+
+    /// Line clears in a row up to the last lock, 0 if it cleared nothing
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub fn consecutive_clears(&self) -> usize {
+        self.consecutive_clears
+    }
+
+    /// Whether the next difficult clear gets the back to back bonus
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+
+    /// Tetrominoes placed with more presses than needed
+    pub fn finesse_faults(&self) -> usize {
+        self.finesse.faults()
+    }
+
+    /// Presses used for the last tetromino, `None` if it was not judged
+    pub fn last_finesse(&self) -> Option<Judgement> {
+        self.finesse.last()
+    }
+
+    /// Target and results of finesse training, `None` in the other modes
+    pub fn training(&self) -> Option<Training> {
+        self.training
+    }
+
+    /// Puzzle being solved, `None` in the other modes
+    pub fn puzzle(&self) -> Option<&Puzzle> {
+        self.puzzle.as_deref()
+    }
+
+    /// Progress in the opener, `None` in the other modes
+    pub fn practice(&self) -> Option<Practice> {
+        self.practice
+    }
+
+    pub fn total_attack(&self) -> usize {
+        self.total_attack
+    }
+
+    /// Time since the start of the game, in ticks
+    pub fn time(&self) -> usize {
+        self.tick
+    }
+
+    pub fn cleared_lines(&self) -> usize {
+        self.cleared_lines
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// Digest of the board and the counters, equal for two copies of a game still in sync
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        for cell in self.board.lines().flatten() {
+            hasher.write(&[*cell as u8]);
+        }
+        let (row, column) = self.current.pos();
+        hasher.write(&[self.current.shape() as u8, row as u8, column as u8]);
+        hasher.write(&[self.hold as u8]);
+        for counter in [
+            self.score,
+            self.cleared_lines,
+            self.total_attack,
+            self.garbage.pending(),
+            self.tick,
+        ] {
+            hasher.write_u64(counter as u64);
+        }
+        hasher.finish()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            mode: self.mode,
+            board: self.board.clone(),
+            current: self.current.clone(),
+            falling: self.phase == Phase::Falling,
+            hold: self.hold,
+            nexts: self.nexts(),
+            score: self.score,
+            level: self.level,
+            cleared_lines: self.cleared_lines,
+            cleared_garbage: self.cleared_garbage,
+            pending_garbage: self.garbage.pending(),
+            tick: self.tick,
+            total_attack: self.total_attack,
+            locked_pieces: self.locked_pieces,
+            finesse_faults: self.finesse.faults(),
+            last_finesse: self.finesse.last(),
+            training: self.training,
+            practice: self.practice,
+            puzzle: self.puzzle.as_deref().cloned(),
+            last_clear: self.last_clear,
+            game_over: self.game_over,
+            is_won: self.is_won,
+            section_times: self.section_times.clone(),
+            gm_qualified: self.gm_qualified,
+        }
+    }
+
+    /// Game looking like the snapshot, only meant to be drawn
+    pub fn from_snapshot(snapshot: Snapshot) -> Self {
+        let mut tetris = Self::with_seed(snapshot.mode, 0);
+        tetris.board = snapshot.board;
+        tetris.current = snapshot.current;
+        // Any delay hides the current tetromino
+        if !snapshot.falling {
+            tetris.phase = Phase::Entry(1);
+        }
+        tetris.hold = snapshot.hold;
+        tetris.bag = snapshot.nexts.into_iter().rev().collect();
+        tetris.next_bag = Vec::new();
+        tetris.score = snapshot.score;
+        tetris.level = snapshot.level;
+        tetris.cleared_lines = snapshot.cleared_lines;
+        tetris.cleared_garbage = snapshot.cleared_garbage;
+        tetris.garbage.queue(snapshot.pending_garbage);
+        tetris.tick = snapshot.tick;
+        tetris.total_attack = snapshot.total_attack;
+        tetris.locked_pieces = snapshot.locked_pieces;
+        tetris.finesse = Finesse::from_counters(snapshot.finesse_faults, snapshot.last_finesse);
+        tetris.training = snapshot.training;
+        tetris.practice = snapshot.practice;
+        tetris.puzzle = snapshot.puzzle.map(Arc::new);
+        tetris.last_clear = snapshot.last_clear;
+        tetris.game_over = snapshot.game_over;
+        tetris.is_won = snapshot.is_won;
+        tetris.section_times = snapshot.section_times;
+        tetris.gm_qualified = snapshot.gm_qualified;
+        tetris
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn current(&self) -> &Tetromino {
+        &self.current
+    }
+
+    pub fn locked_pieces(&self) -> usize {
+        self.locked_pieces
+    }
+
+    /// Whether the current tetromino can be controlled, false during delays and once the game is over
+    pub fn is_falling(&self) -> bool {
+        self.phase == Phase::Falling && !self.is_over()
+    }
+
+    pub fn apply(&mut self, action: Action) {
+        if self.is_falling() {
+            self.finesse.record(action);
+        }
+        match action {
+            Action::MoveLeft => self.r#move([0, -1]),
+            Action::MoveRight => self.r#move([0, 1]),
+            Action::SoftDrop => self.r#move([1, 0]),
+            Action::HardDrop => self.hard_drop(),
+            Action::RotateClockwise => self.rotate_clockwise(),
+            Action::RotateCounterClockwise => self.rotate_counter_clockwise(),
+            Action::Hold => self.hold_current(),
+        }
+    }
+
+    /// Drop the current tetromino as low as possible and lock it right away
+    pub fn hard_drop(&mut self) {
+        if self.phase != Phase::Falling || self.is_over() {
+            return;
+        }
+        while self.can_move([1, 0]) {
+            self.current.r#move([1, 0]);
+            self.last_move_rotation = false;
+        }
+        self.lock_current();
+    }
+
     pub fn rotate_counter_clockwise(&mut self) {
-        self.current.rotate_counter_clockwise(self.map);
+        if self.phase == Phase::Falling {
+            if self.current.rotate_counter_clockwise(&self.board) {
+                self.last_move_rotation = true;
+            }
+        } else {
+            self.buffered_rotation = -1;
+        }
     }
 
     pub fn rotate_clockwise(&mut self) {
-        self.current.rotate_clockwise(self.map);
+        if self.phase == Phase::Falling {
+            if self.current.rotate_clockwise(&self.board) {
+                self.last_move_rotation = true;
+            }
+        } else {
+            self.buffered_rotation = 1;
+        }
     }
 
     fn can_move(&self, vector: [i8; 2]) -> bool {
-        for (y, row) in self.current.pieces().as_slice().iter().enumerate() {
-            for (x, piece) in row.iter().enumerate() {
-                if *piece == TetrominoType::E {
-                    continue;
-                }
-                let next_y: i8 = y as i8 + self.current.pos().0 + vector[0];
-                let next_x: i8 = x as i8 + self.current.pos().1 + vector[1];
+        self.fits(&self.current, vector)
+    }
 
-                if next_y >= TRUE_MAP_HEIGHT as i8 {
-                    return false;
-                }
-                if next_x < 0 || next_x >= MAP_WIDTH as i8 {
-                    return false;
-                }
-                if self.map[next_y as usize][next_x as usize] != TetrominoType::E {
-                    return false;
-                }
-            }
-        }
-        true
+    fn fits(&self, tetromino: &Tetromino, vector: [i8; 2]) -> bool {
+        self.board.fits(tetromino, vector)
     }
 
     pub fn r#move(&mut self, vector: [i8; 2]) {
-        if self.can_move(vector) {
+        if self.phase == Phase::Falling && self.can_move(vector) {
             if vector[0] != 0 {
-                self.last_fall = self.tick;
+                self.fall_progress = 0.0;
+                self.lock_timer = 0;
+                self.soft_drop += 1;
             }
             self.current.r#move(vector);
+            self.last_move_rotation = false;
         }
     }
 
     pub fn is_lost(&self) -> bool {
-        self.is_lost
+        self.game_over.is_some()
+    }
+
+    pub fn game_over_reason(&self) -> Option<GameOverReason> {
+        self.game_over
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.is_lost() || self.is_won
+    }
+
+    /// Whether the hold was not used since the last lock
+    pub fn can_hold(&self) -> bool {
+        !self.has_hold_this_round
     }
 
     pub fn hold_current(&mut self) {
-        if self.has_hold_this_round {
+        if !self.can_hold() {
+            return;
+        }
+        if self.phase != Phase::Falling {
+            self.buffered_hold = true;
+            return;
+        }
+        // Nothing left to take instead at the end of a puzzle
+        if self.hold == TetrominoType::E && self.nexts().is_empty() {
             return;
         }
         let swap: TetrominoType = self.current.shape();
         if self.hold == TetrominoType::E {
-            self.current = Tetromino::new(self.bag.pop().unwrap_or(TetrominoType::E));
+            let next = self.pop_next();
+            self.spawn(next);
         } else {
-            self.current = Tetromino::new(self.hold);
+            self.spawn(self.hold);
         }
         self.hold = swap;
         self.has_hold_this_round = true;
@@ -295,40 +1196,268 @@ impl Tetris {
         nexts.to_vec()
     }
 
-    fn current_shadow(&self) -> [[TetrominoType; MAP_WIDTH]; MAP_HEIGHT] {
-        let mut shadow_map = [[TetrominoType::E; MAP_WIDTH]; MAP_HEIGHT];
+    fn current_shadow(&self) -> Vec<Vec<TetrominoType>> {
+        let mut shadow_map = self.blank_display();
 
-        let mut worst_down: usize = MAP_HEIGHT - 2; // 2 is the minimum tetromino height IN THE WAY I HANDLED THEM
-        for piece_y in 0..self.current.pieces().len() {
-            for piece_x in 0..self.current.pieces()[piece_y].len() {
-                let mut y: usize =
-                    self.current.pos().0 as usize - (if self.current.pos().0 > 0 { 1 } else { 0 }); //minus 1 but don't do it if it's 0 as it's  usize
-                if self.current.pieces()[piece_y][piece_x] == TetrominoType::E {
-                    continue;
-                }
-                while piece_y + y < MAP_HEIGHT
-                    && self.map[piece_y + y + HIDDEN_ROWS]
-                        [(piece_x as i8 + self.current.pos().1) as usize]
-                        == TetrominoType::E
-                {
-                    y += 1;
-                }
-                if y > 0 && y - 1 < worst_down {
-                    // Avoid y - 1 if y is 0 since it's a usize
-                    worst_down = y - 1;
-                }
-            }
+        let mut shadow = self.current.clone();
+        while self.fits(&shadow, [1, 0]) {
+            shadow.r#move([1, 0]);
         }
 
-        for piece_y in 0..self.current.pieces().len() {
-            for piece_x in 0..self.current.pieces()[piece_y].len() {
-                if self.current.pieces()[piece_y][piece_x] != TetrominoType::E {
-                    shadow_map[piece_y + worst_down]
-                        [(piece_x as i8 + self.current.pos().1) as usize] = self.current.shape();
-                }
+        for (y, x) in shadow.cells() {
+            if y >= HIDDEN_ROWS as i8 {
+                shadow_map[y as usize - HIDDEN_ROWS][x as usize] = shadow.shape();
             }
         }
 
         shadow_map
     }
+
+    /// Empty cells for every visible row of the board
+    fn blank_display(&self) -> Vec<Vec<TetrominoType>> {
+        vec![vec![TetrominoType::E; self.board.width()]; self.board.visible_height()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbage::HolePattern;
+    use crate::placement;
+
+    /// Play `actions` on the current tetromino, then wait for the next one
+    fn play(game: &mut Tetris, actions: &[Action]) {
+        for action in actions {
+            game.apply(*action);
+        }
+        while !game.is_falling() && !game.is_over() {
+            game.on_tick();
+        }
+    }
+
+    /// Game on `rows`, the last one on the floor, dealing `queue`
+    fn game(rows: &[&str], queue: &str) -> Tetris {
+        let setup = Setup {
+            board: rows.iter().map(|row| row.to_string()).collect(),
+            queue: queue.to_string(),
+            hold: None,
+        };
+        Tetris::with_setup(GameMode::Versus, &setup)
+    }
+
+    /// Move the current tetromino to `pos` in `rotation`, turning there last if `spin`, then
+    /// drop it
+    fn lock_at(game: &mut Tetris, rotation: u8, pos: (i8, i8), spin: bool) {
+        let current = game.current();
+        game.current = Tetromino::placed(current.shape(), current.rotation_system(), rotation, pos);
+        game.last_move_rotation = spin;
+        play(game, &[Action::HardDrop]);
+    }
+
+    #[test]
+    fn single() {
+        let mut game = game(&["G.........", "GGGGGG...."], "I");
+        lock_at(&mut game, 0, (2, 6), false);
+        assert_eq!(game.last_clear().unwrap().name(), "Single");
+        assert_eq!(game.take_outgoing_attack(), 0);
+    }
+
+    #[test]
+    fn t_spin_double() {
+        let rows = ["..G.......", "...GGGGGGG", "G.GGGGGGGG"];
+        let mut game = game(&rows, "T");
+        lock_at(&mut game, 2, (21, 0), true);
+        assert_eq!(game.last_clear().unwrap().name(), "T-Spin Double");
+        assert_eq!(game.take_outgoing_attack(), 4);
+    }
+
+    #[test]
+    fn t_spin_triple() {
+        let rows = ["G.........", "G.GGGGGGGG", "..GGGGGGGG", "G.GGGGGGGG"];
+        let mut game = game(&rows, "T");
+        lock_at(&mut game, 3, (21, 0), true);
+        assert_eq!(game.last_clear().unwrap().name(), "T-Spin Triple");
+        assert_eq!(game.take_outgoing_attack(), 6);
+    }
+
+    #[test]
+    fn back_to_back_tetris() {
+        let mut rows = vec!["G........."];
+        rows.extend(["GGGGGGGGG."; 8]);
+        let mut game = game(&rows, "II");
+        lock_at(&mut game, 1, (20, 7), false);
+        assert_eq!(game.last_clear().unwrap().name(), "Tetris");
+        assert_eq!(game.take_outgoing_attack(), 4);
+        lock_at(&mut game, 1, (20, 7), false);
+        assert_eq!(game.last_clear().unwrap().name(), "B2B Tetris");
+        assert_eq!(game.take_outgoing_attack(), 5);
+    }
+
+    #[test]
+    fn combo() {
+        let mut rows = vec!["G........."];
+        rows.extend(["GGGGGGGG.."; 10]);
+        let mut game = game(&rows, "OOOOOT");
+        let mut sent = Vec::new();
+        for _ in 0..5 {
+            lock_at(&mut game, 0, (2, 7), false);
+            sent.push(game.take_outgoing_attack());
+        }
+        // Doubles sending 1 line each, and the combo bonus from the third clear on
+        assert_eq!(sent, [1, 1, 2, 2, 2]);
+        // A lock without a clear ends the chain
+        lock_at(&mut game, 0, (2, 0), false);
+        assert_eq!(game.take_outgoing_attack(), 0);
+        assert_eq!(game.consecutive_clears(), 0);
+    }
+
+    #[test]
+    fn perfect_clear() {
+        let mut game = game(&["GGGGGGGG..", "GGGGGGGG.."], "O");
+        lock_at(&mut game, 0, (2, 7), false);
+        let clear = game.last_clear().unwrap();
+        assert_eq!(clear.name(), "Perfect Clear Double");
+        assert_eq!(game.take_outgoing_attack(), 11);
+        assert!(game.board().is_empty());
+    }
+
+    #[test]
+    fn attacks_cancel_the_garbage_received() {
+        let rows = ["..G.......", "...GGGGGGG", "G.GGGGGGGG"];
+        let mut game = game(&rows, "TO");
+        game.queue_garbage(1);
+        game.queue_garbage(2);
+        lock_at(&mut game, 2, (21, 0), true);
+        // 3 of the 4 lines of the T-Spin Double went to the garbage
+        assert_eq!(game.pending_garbage(), 0);
+        assert_eq!(game.take_outgoing_attack(), 1);
+        assert_eq!(game.total_attack(), 4);
+
+        game.queue_garbage(5);
+        lock_at(&mut game, 0, (2, 4), false);
+        // Without clearing a line, the garbage rises
+        assert_eq!(game.pending_garbage(), 0);
+        assert_eq!(game.board().row(23).count_ones(), 9);
+    }
+
+    #[test]
+    fn the_piece_locking_out_is_counted() {
+        // The O can only lock above the visible field
+        let mut game = game(&["....GG...."; MAP_HEIGHT], "OO");
+        play(&mut game, &[Action::HardDrop]);
+        assert_eq!(game.game_over_reason(), Some(GameOverReason::LockOut));
+        assert_eq!(game.locked_pieces(), 1);
+    }
+
+    #[test]
+    fn garbage_rises_under_the_stack() {
+        let mut game = game(&["GGGG......"], "O");
+        game.garbage.set_pattern(HolePattern::Clean, 0.0);
+        game.queue_garbage(2);
+        game.queue_garbage(1);
+        lock_at(&mut game, 0, (2, 7), false);
+        // The stack is pushed up by the 3 lines, which share their hole
+        assert_eq!(game.pending_garbage(), 0);
+        assert_eq!(game.board().row(18), 0);
+        assert_eq!(game.board().row(19), 0b11_0000_0000);
+        assert_eq!(game.board().row(20), 0b11_0000_1111);
+        let holes: Vec<u32> = (21..24)
+            .map(|row| !game.board().row(row) & 0b11_1111_1111)
+            .collect();
+        assert!(
+            holes
+                .iter()
+                .all(|hole| hole.count_ones() == 1 && *hole == holes[0])
+        );
+    }
+
+    /// Lock each step of the opener of `mode` on its target, holding when the tetromino
+    /// dealt is not the one of the step, and give what every lock cleared
+    fn build_opener(mode: GameMode) -> (Tetris, Vec<Option<ClearInfo>>) {
+        let mut game = Tetris::with_seed(mode, 5);
+        let mut clears = Vec::new();
+        for (shape, cells) in mode.opener().unwrap().steps() {
+            if game.current().shape() != *shape {
+                play(&mut game, &[Action::Hold]);
+            }
+            let target = cells.map(|(row, column)| (row as usize, column as usize));
+            let placement = placement::placements(&game)
+                .into_iter()
+                .find(|placement| placement.cells() == target)
+                .expect("every step can be reached");
+            play(&mut game, placement.actions());
+            clears.push(game.last_clear());
+        }
+        (game, clears)
+    }
+
+    #[test]
+    fn openers_are_built_on_their_steps() {
+        for (mode, lines) in [
+            (GameMode::OpenerTki, 2),
+            (GameMode::OpenerPco, 0),
+            (GameMode::OpenerDt, 5),
+        ] {
+            let (game, clears) = build_opener(mode);
+            let practice = game.practice().unwrap();
+            assert!(!practice.is_missed);
+            assert!(practice.is_done(mode.opener().unwrap()));
+            assert_eq!(game.cleared_lines(), lines);
+            assert_eq!(game.target(), None);
+            let spins: Vec<(TSpin, usize)> = clears
+                .iter()
+                .flatten()
+                .filter(|clear| clear.lines > 0)
+                .map(|clear| (clear.t_spin, clear.lines))
+                .collect();
+            let expected: &[(TSpin, usize)] = match mode {
+                GameMode::OpenerTki => &[(TSpin::Full, 2)],
+                GameMode::OpenerDt => &[(TSpin::Full, 2), (TSpin::Full, 3)],
+                _ => &[],
+            };
+            assert_eq!(spins, expected);
+        }
+    }
+
+    #[test]
+    fn opener_steps_are_checked_in_order() {
+        let mut game = Tetris::with_seed(GameMode::OpenerDt, 5);
+        let steps = GameMode::OpenerDt.opener().unwrap().steps();
+        play(&mut game, &[Action::Hold]);
+        assert_eq!(game.target(), Some(steps[0]));
+        // The I standing on the left comes with the second bag
+        let (shape, cells) = steps[6];
+        assert_eq!(game.current().shape(), shape);
+        let target = cells.map(|(row, column)| (row as usize, column as usize));
+        let placement = placement::placements(&game)
+            .into_iter()
+            .find(|placement| placement.cells() == target)
+            .unwrap();
+        play(&mut game, placement.actions());
+        let practice = game.practice().unwrap();
+        assert_eq!((practice.placed, practice.is_missed), (0, true));
+        assert_eq!(game.target(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut game = Tetris::with_seed(GameMode::CheeseRace10, 7);
+        play(&mut game, &[Action::MoveLeft, Action::HardDrop]);
+        play(&mut game, &[Action::Hold]);
+        play(&mut game, &[Action::RotateClockwise, Action::MoveRight]);
+        let json = serde_json::to_string(&game).unwrap();
+        let mut copy: Tetris = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+        assert_eq!(copy.board(), game.board());
+        assert_eq!(copy.current(), game.current());
+        // Same bags and timers: both games go on the same way
+        for _ in 0..10 {
+            play(&mut game, &[Action::HardDrop]);
+            play(&mut copy, &[Action::HardDrop]);
+        }
+        assert_eq!(copy.board(), game.board());
+        assert_eq!(copy.nexts(), game.nexts());
+        assert_eq!(copy.time(), game.time());
+    }
 }
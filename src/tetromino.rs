@@ -1,255 +1,178 @@
+use crate::board::Board;
+use crate::rotation_system::RotationSystem;
 use crate::tetromino_type::TetrominoType;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Tetromino {
     shape: TetrominoType,
+    rotation_system: RotationSystem,
     rotation: u8,
     pos: (i8, i8),
-    pieces: Vec<Vec<TetrominoType>>,
+}
+
+/// Blocks of a tetromino in one rotation state, relative to its position
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Layout {
+    cells: [(i8, i8); 4], // In reading order
+    masks: [u16; 4],      // Filled columns of each row, bit `x` for column `x`
 }
 
 pub trait TetrominoTrait {
-    fn rotate_clockwise(
-        &mut self,
-        map: [[TetrominoType; crate::tetris::MAP_WIDTH]; crate::tetris::TRUE_MAP_HEIGHT],
-    );
-    fn rotate_counter_clockwise(
-        &mut self,
-        map: [[TetrominoType; crate::tetris::MAP_WIDTH]; crate::tetris::TRUE_MAP_HEIGHT],
-    );
+    /// Rotate if there is enough room, returns whether the tetromino rotated
+    fn rotate_clockwise(&mut self, board: &Board) -> bool;
+    fn rotate_counter_clockwise(&mut self, board: &Board) -> bool;
 }
 
 impl TetrominoTrait for Tetromino {
-    fn rotate_clockwise(
-        &mut self,
-        map: [[TetrominoType; crate::tetris::MAP_WIDTH]; crate::tetris::TRUE_MAP_HEIGHT],
-    ) {
-        match self.shape {
-            TetrominoType::E => panic!("Empty tetromino shouldn't be here"),
-            TetrominoType::O => { /*No rotation needed*/ }
-            TetrominoType::I => self.rotate_i(
-                map,
-                [4, 8, 12, 13, 14, 15, 11, 7, 3, 2, 1, 0],
-                [9, 10, 6, 5],
-            ),
-            _ => self.rotate(map, [3, 6, 7, 8, 5, 2, 1, 0]),
-        };
-        self.rotation = (self.rotation + 1) % 4;
+    fn rotate_clockwise(&mut self, board: &Board) -> bool {
+        self.rotate(board, true)
     }
 
-    fn rotate_counter_clockwise(
-        &mut self,
-        map: [[TetrominoType; crate::tetris::MAP_WIDTH]; crate::tetris::TRUE_MAP_HEIGHT],
-    ) {
-        match self.shape {
-            TetrominoType::E => panic!("Empty tetromino shouldn't be here"),
-            TetrominoType::O => { /*No rotation needed*/ }
-            TetrominoType::I => self.rotate_i(
-                map,
-                [0, 1, 2, 3, 7, 11, 15, 14, 13, 12, 8, 4],
-                [5, 6, 10, 9],
-            ),
-            _ => self.rotate(map, [0, 1, 2, 5, 8, 7, 6, 3]),
-        }
-        self.rotation = (self.rotation + 3) % 4;
+    fn rotate_counter_clockwise(&mut self, board: &Board) -> bool {
+        self.rotate(board, false)
     }
 }
 
-impl Tetromino {
-    pub fn new(shape: TetrominoType) -> Self {
-        if shape == TetrominoType::E {
-            panic!("Cannot create Tetris");
+impl Layout {
+    pub const fn new(mut cells: [(i8, i8); 4]) -> Self {
+        let mut i = 0;
+        while i < cells.len() {
+            let mut j = i + 1;
+            while j < cells.len() {
+                let (a, b) = (cells[i], cells[j]);
+                if b.0 < a.0 || (b.0 == a.0 && b.1 < a.1) {
+                    cells[i] = b;
+                    cells[j] = a;
+                }
+                j += 1;
+            }
+            i += 1;
         }
-        let pieces: Vec<Vec<TetrominoType>> = match shape {
-            TetrominoType::E => {
-                vec![vec![TetrominoType::E; 4]; 4]
-            }
-            TetrominoType::I => {
-                vec![
-                    vec![TetrominoType::E; 4],
-                    vec![TetrominoType::I; 4],
-                    vec![TetrominoType::E; 4],
-                    vec![TetrominoType::E; 4],
-                ]
-            }
-            TetrominoType::L => {
-                vec![
-                    vec![TetrominoType::E, TetrominoType::E, TetrominoType::L],
-                    vec![TetrominoType::L, TetrominoType::L, TetrominoType::L],
-                    vec![TetrominoType::E, TetrominoType::E, TetrominoType::E],
-                ]
-            }
-            TetrominoType::J => {
-                vec![
-                    vec![TetrominoType::J, TetrominoType::E, TetrominoType::E],
-                    vec![TetrominoType::J, TetrominoType::J, TetrominoType::J],
-                    vec![TetrominoType::E, TetrominoType::E, TetrominoType::E],
-                ]
-            }
-            TetrominoType::O => {
-                vec![
-                    vec![TetrominoType::E, TetrominoType::O, TetrominoType::O],
-                    vec![TetrominoType::E, TetrominoType::O, TetrominoType::O],
-                ]
-            }
-            TetrominoType::Z => {
-                vec![
-                    vec![TetrominoType::Z, TetrominoType::Z, TetrominoType::E],
-                    vec![TetrominoType::E, TetrominoType::Z, TetrominoType::Z],
-                    vec![TetrominoType::E, TetrominoType::E, TetrominoType::E],
-                ]
-            }
-            TetrominoType::S => {
-                vec![
-                    vec![TetrominoType::E, TetrominoType::S, TetrominoType::S],
-                    vec![TetrominoType::S, TetrominoType::S, TetrominoType::E],
-                    vec![TetrominoType::E, TetrominoType::E, TetrominoType::E],
-                ]
-            }
-            TetrominoType::T => {
-                vec![
-                    vec![TetrominoType::E, TetrominoType::T, TetrominoType::E],
-                    vec![TetrominoType::T, TetrominoType::T, TetrominoType::T],
-                    vec![TetrominoType::E, TetrominoType::E, TetrominoType::E],
-                ]
-            }
-        };
-        Self {
-            shape,
-            rotation: 0,
-            pos: (0, 3),
-            pieces,
+        let mut masks = [0; 4];
+        let mut i = 0;
+        while i < cells.len() {
+            let (y, x) = cells[i];
+            masks[y as usize] |= 1 << x;
+            i += 1;
         }
+        Self { cells, masks }
     }
 
-    fn can_rotate(
-        &self,
-        map: [[TetrominoType; crate::tetris::MAP_WIDTH]; crate::tetris::TRUE_MAP_HEIGHT],
-        round_order: [usize; 8],
-    ) -> u8 {
-        let mut swap_pos: usize;
-        for i in 0..round_order.len() {
-            swap_pos = round_order[(i + 2) % 8];
-            if self.pieces[round_order[i] / 3][round_order[i] % 3] == TetrominoType::E {
-                continue;
-            }
-
-            let next_x: i8 = self.pos.1 + (swap_pos % 3) as i8;
-            let next_y: i8 = self.pos.0 + (swap_pos / 3) as i8;
-
-            if next_y >= crate::tetris::TRUE_MAP_HEIGHT as i8 {
-                return 0;
-            }
-            if next_x < 0 || next_x >= crate::tetris::MAP_WIDTH as i8 {
-                return 0;
-            }
-            if map[next_y as usize][next_x as usize] != TetrominoType::E {
-                return 0;
-            }
-        }
-        1
+    /// Filled columns of each row of the bounding box, bit `x` for column `x`
+    pub fn masks(&self) -> &[u16; 4] {
+        &self.masks
     }
+}
 
-    fn rotate(
-        &mut self,
-        map: [[TetrominoType; crate::tetris::MAP_WIDTH]; crate::tetris::TRUE_MAP_HEIGHT],
-        round_order: [usize; 8],
-    ) {
-        let mut swap: TetrominoType;
-        for _ in 0..self.can_rotate(map, round_order) {
-            for i in 0..round_order.len() - 2 {
-                swap = self.pieces[round_order[i] / 3][round_order[i] % 3];
-                self.pieces[round_order[i] / 3][round_order[i] % 3] =
-                    self.pieces[round_order[i + 2] / 3][round_order[i + 2] % 3];
-                self.pieces[round_order[i + 2] / 3][round_order[i + 2] % 3] = swap;
-            }
-        }
+impl Tetromino {
+    /// Tetromino spawning above the middle of a board `width` columns wide
+    pub fn new(shape: TetrominoType, rotation_system: RotationSystem, width: usize) -> Self {
+        let column = (width as i8 - 3) / 2;
+        Self::placed(
+            shape,
+            rotation_system,
+            0,
+            (crate::tetris::SPAWN_ROW, column),
+        )
     }
-    fn can_rotate_i(
-        &self,
-        map: [[TetrominoType; crate::tetris::MAP_WIDTH]; crate::tetris::TRUE_MAP_HEIGHT],
-        round_order1: [usize; 12],
-        round_order2: [usize; 4],
-    ) -> u8 {
-        let mut swap_pos: usize;
-        for i in 0..round_order1.len() {
-            swap_pos = round_order1[(i + 3) % 12];
-            if self.pieces[round_order1[i] / 4][round_order1[i] % 4] == TetrominoType::E {
-                continue;
-            }
 
-            let next_x: i8 = self.pos.1 + (swap_pos % 4) as i8;
-            let next_y: i8 = self.pos.0 + (swap_pos / 4) as i8;
-
-            if next_y >= crate::tetris::TRUE_MAP_HEIGHT as i8 {
-                return 0;
-            }
-            if next_x < 0 || next_x >= crate::tetris::MAP_WIDTH as i8 {
-                return 0;
-            }
-            if map[next_y as usize][next_x as usize] != TetrominoType::E {
-                return 0;
-            }
+    /// Tetromino in the given rotation state at `pos`
+    pub fn placed(
+        shape: TetrominoType,
+        rotation_system: RotationSystem,
+        rotation: u8,
+        pos: (i8, i8),
+    ) -> Self {
+        if shape == TetrominoType::E || shape == TetrominoType::G {
+            panic!("Cannot create Tetris");
         }
-        for i in 0..round_order2.len() {
-            swap_pos = round_order2[(i + 1) % 4];
-            if self.pieces[round_order2[i] / 4][round_order2[i] % 4] == TetrominoType::E {
-                continue;
-            }
-
-            let next_x: i8 = self.pos.1 + (swap_pos % 4) as i8;
-            let next_y: i8 = self.pos.0 + (swap_pos / 4) as i8;
-
-            if next_y >= crate::tetris::TRUE_MAP_HEIGHT as i8 {
-                return 0;
-            }
-            if next_x < 0 || next_x >= crate::tetris::MAP_WIDTH as i8 {
-                return 0;
-            }
-            if map[next_y as usize][next_x as usize] != TetrominoType::E {
-                return 0;
-            }
+        Self {
+            shape,
+            rotation_system,
+            rotation: rotation % 4,
+            pos,
         }
-        1
     }
 
-    fn rotate_i(
-        &mut self,
-        map: [[TetrominoType; crate::tetris::MAP_WIDTH]; crate::tetris::TRUE_MAP_HEIGHT],
-        round_order1: [usize; 12],
-        round_order2: [usize; 4],
-    ) {
-        let mut swap: TetrominoType;
-        for _ in 0..self.can_rotate_i(map, round_order1, round_order2) {
-            for i in 0..round_order1.len() - 3 {
-                swap = self.pieces[round_order1[i] / 4][round_order1[i] % 4];
-                self.pieces[round_order1[i] / 4][round_order1[i] % 4] =
-                    self.pieces[round_order1[i + 3] / 4][round_order1[i + 3] % 4];
-                self.pieces[round_order1[i + 3] / 4][round_order1[i + 3] % 4] = swap;
-            }
-            for i in 0..round_order2.len() - 1 {
-                swap = self.pieces[round_order2[i] / 4][round_order2[i] % 4];
-                self.pieces[round_order2[i] / 4][round_order2[i] % 4] =
-                    self.pieces[round_order2[i + 1] / 4][round_order2[i + 1] % 4];
-                self.pieces[round_order2[i + 1] / 4][round_order2[i + 1] % 4] = swap;
+    /// Turn with the first kick of the rotation system leaving room for the tetromino
+    fn rotate(&mut self, board: &Board, clockwise: bool) -> bool {
+        let definition = self.rotation_system.definition();
+        let rotation = (self.rotation + if clockwise { 1 } else { 3 }) % 4;
+        let mut turned = Self { rotation, ..*self };
+        // Center column rule: blocked in its middle column first, the tetromino doesn't kick
+        if definition.has_center_column(self.shape) && !board.fits(&turned, [0, 0]) {
+            let blocked = turned
+                .cells()
+                .into_iter()
+                .zip(turned.layout().cells)
+                .find(|((row, column), _)| board.is_blocked(*row, *column));
+            if let Some((_, (_, 1))) = blocked {
+                return false;
             }
         }
+        let Some((rows, columns)) = definition
+            .kicks(self.shape, self.rotation, clockwise)
+            .iter()
+            .find(|(rows, columns)| board.fits(&turned, [*rows, *columns]))
+        else {
+            return false;
+        };
+        turned.r#move([*rows, *columns]);
+        *self = turned;
+        true
     }
 
     pub fn pos(&self) -> (i8, i8) {
         self.pos
     }
 
-    pub fn pieces(&self) -> &Vec<Vec<TetrominoType>> {
-        &self.pieces
+    /// Blocks of the current rotation state, precomputed for every tetromino
+    pub fn layout(&self) -> &'static Layout {
+        self.rotation_system
+            .definition()
+            .layout(self.shape, self.rotation)
     }
 
     pub fn shape(&self) -> TetrominoType {
         self.shape
     }
 
+    pub fn rotation_system(&self) -> RotationSystem {
+        self.rotation_system
+    }
+
+    /// Rotation state, 0 being the spawn one and each clockwise turn adding 1
+    pub fn rotation(&self) -> u8 {
+        self.rotation
+    }
+
+    /// Map coordinates `(row, column)` of the four blocks, in reading order
+    pub fn cells(&self) -> [(i8, i8); 4] {
+        self.layout()
+            .cells
+            .map(|(y, x)| (self.pos.0 + y, self.pos.1 + x))
+    }
+
     pub fn r#move(&mut self, vector: [i8; 2]) {
         self.pos.0 += vector[0];
         self.pos.1 += vector[1];
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_round_trip() {
+        for shape in (1..=7).map(TetrominoType::from_u8) {
+            let mut tetromino = Tetromino::new(shape, RotationSystem::Srs, 10);
+            tetromino.rotate_clockwise(&Board::default());
+            let json = serde_json::to_string(&tetromino).unwrap();
+            assert_eq!(serde_json::from_str::<Tetromino>(&json).unwrap(), tetromino);
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use ratatui::prelude::{Color, Line, Span};
 use ratatui::style::Stylize;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TetrominoType {
     E = 0, //Empty
     I = 1,
@@ -11,6 +14,7 @@ pub enum TetrominoType {
     Z = 5,
     S = 6,
     T = 7,
+    G = 8, //Garbage
 }
 
 impl PartialEq<TetrominoType> for &TetrominoType {
@@ -24,6 +28,7 @@ impl PartialEq<TetrominoType> for &TetrominoType {
             (TetrominoType::Z, TetrominoType::Z) => true,
             (TetrominoType::S, TetrominoType::S) => true,
             (TetrominoType::T, TetrominoType::T) => true,
+            (TetrominoType::G, TetrominoType::G) => true,
             (_, _) => false,
         }
     }
@@ -39,10 +44,39 @@ impl TetrominoType {
             5 => TetrominoType::Z,
             6 => TetrominoType::S,
             7 => TetrominoType::T,
+            8 => TetrominoType::G,
             _ => TetrominoType::E,
         }
     }
 
+    /// Tetromino named by its letter, `E` for anything else
+    pub fn from_letter(letter: char) -> TetrominoType {
+        match letter.to_ascii_uppercase() {
+            'I' => TetrominoType::I,
+            'L' => TetrominoType::L,
+            'J' => TetrominoType::J,
+            'O' => TetrominoType::O,
+            'Z' => TetrominoType::Z,
+            'S' => TetrominoType::S,
+            'T' => TetrominoType::T,
+            _ => TetrominoType::E,
+        }
+    }
+
+    pub fn letter(&self) -> char {
+        match self {
+            TetrominoType::I => 'I',
+            TetrominoType::L => 'L',
+            TetrominoType::J => 'J',
+            TetrominoType::O => 'O',
+            TetrominoType::Z => 'Z',
+            TetrominoType::S => 'S',
+            TetrominoType::T => 'T',
+            TetrominoType::G => '#',
+            TetrominoType::E => '.',
+        }
+    }
+
     pub fn color(&self) -> Color {
         match self {
             TetrominoType::I => Color::Cyan,
@@ -52,6 +86,7 @@ impl TetrominoType {
             TetrominoType::Z => Color::Red,
             TetrominoType::S => Color::Green,
             TetrominoType::T => Color::Magenta,
+            TetrominoType::G => Color::Gray,
             TetrominoType::E => Color::White,
         }
     }
@@ -59,7 +94,7 @@ impl TetrominoType {
     pub fn as_ratatui_text(&self) -> Vec<Line<'_>> {
         //TODO try to center O and I tetromino by moving them 1 char to the right
         (match self {
-            TetrominoType::E => {
+            TetrominoType::E | TetrominoType::G => {
                 vec![vec![]]
             }
             TetrominoType::I => {
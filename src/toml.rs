@@ -0,0 +1,302 @@
+//! Just enough TOML for the rotation system and opener files: tables, and keys holding strings, integers,
+//! booleans or arrays of them, written over as many lines as needed. Dotted keys, inline
+//! tables, floats and dates are left out.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+/// Keys of every table by table name, the keys written before any table being under ""
+pub type Document = BTreeMap<String, BTreeMap<String, Value>>;
+
+pub fn parse(text: &str) -> Result<Document, Box<dyn Error>> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        index: 0,
+    };
+    let mut document = Document::from([(String::new(), BTreeMap::new())]);
+    let mut table = String::new();
+    loop {
+        parser.skip_blank();
+        let Some(c) = parser.peek() else {
+            break;
+        };
+        if c == '[' {
+            parser.index += 1;
+            table = parser.key()?;
+            parser.expect(']')?;
+            if document.insert(table.clone(), BTreeMap::new()).is_some() {
+                return Err(parser.error(&format!("table [{table}] written twice")));
+            }
+        } else {
+            let key = parser.key()?;
+            parser.expect('=')?;
+            let value = parser.value()?;
+            let keys = document.entry(table.clone()).or_default();
+            if keys.insert(key.clone(), value).is_some() {
+                return Err(parser.error(&format!("key {key} written twice")));
+            }
+        }
+        parser.end_of_line()?;
+    }
+    Ok(document)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn error(&self, message: &str) -> Box<dyn Error> {
+        let line = self.chars[..self.index.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1;
+        format!("line {line}: {message}").into()
+    }
+
+    /// Spaces and tabs, staying on the line
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.index += 1;
+        }
+    }
+
+    /// Whitespace, line breaks and comments
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => self.index += 1,
+                Some('#') => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.index += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Box<dyn Error>> {
+        self.skip_spaces();
+        if self.peek() != Some(expected) {
+            return Err(self.error(&format!("expected {expected}")));
+        }
+        self.index += 1;
+        Ok(())
+    }
+
+    /// Nothing but a comment left on the line
+    fn end_of_line(&mut self) -> Result<(), Box<dyn Error>> {
+        self.skip_spaces();
+        match self.peek() {
+            None | Some('\r' | '\n' | '#') => Ok(()),
+            Some(c) => Err(self.error(&format!("unexpected {c}"))),
+        }
+    }
+
+    fn key(&mut self) -> Result<String, Box<dyn Error>> {
+        self.skip_spaces();
+        if self.peek() == Some('"') {
+            return self.string();
+        }
+        let start = self.index;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            self.index += 1;
+        }
+        if self.index == start {
+            return Err(self.error("expected a key"));
+        }
+        Ok(self.chars[start..self.index].iter().collect())
+    }
+
+    fn value(&mut self) -> Result<Value, Box<dyn Error>> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => self.array(),
+            Some('t' | 'f') => {
+                let word = self.key()?;
+                match word.as_str() {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    _ => Err(self.error(&format!("unknown value {word}"))),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' => self.integer(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Box<dyn Error>> {
+        self.index += 1;
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('"') => break,
+                Some('\\') => {
+                    self.index += 1;
+                    match self.peek() {
+                        Some('"') => string.push('"'),
+                        Some('\\') => string.push('\\'),
+                        Some('n') => string.push('\n'),
+                        Some('t') => string.push('\t'),
+                        _ => return Err(self.error("unknown escape sequence")),
+                    }
+                }
+                Some(c) => string.push(c),
+            }
+            self.index += 1;
+        }
+        self.index += 1;
+        Ok(string)
+    }
+
+    fn integer(&mut self) -> Result<Value, Box<dyn Error>> {
+        let start = self.index;
+        if matches!(self.peek(), Some('-' | '+')) {
+            self.index += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '_') {
+            self.index += 1;
+        }
+        let digits: String = self.chars[start..self.index]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect();
+        digits
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| self.error(&format!("invalid integer {digits}")))
+    }
+
+    /// Values between brackets, a comma after the last one being allowed
+    fn array(&mut self) -> Result<Value, Box<dyn Error>> {
+        self.index += 1;
+        let mut values = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.peek() == Some(']') {
+                break;
+            }
+            values.push(self.value()?);
+            self.skip_blank();
+            match self.peek() {
+                Some(',') => self.index += 1,
+                Some(']') => break,
+                _ => return Err(self.error("expected , or ]")),
+            }
+        }
+        self.index += 1;
+        Ok(Value::Array(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        match parse(text) {
+            Ok(document) => panic!("{text} was read as {document:?}"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn values() {
+        let document = parse(
+            r##"
+            # A comment
+            name = "a \"quoted\"\t\\ string" # after a value
+            "quoted key" = "# not a comment"
+            count = -1_000
+            plus = +7
+            yes = true
+            no = false
+
+            [table]
+            nested = [
+                [1, 2],   # comment inside
+                [],
+                ["three", false,],
+            ]
+            "##,
+        )
+        .unwrap();
+        let root = &document[""];
+        assert_eq!(
+            root["name"],
+            Value::String("a \"quoted\"\t\\ string".into())
+        );
+        assert_eq!(root["quoted key"], Value::String("# not a comment".into()));
+        assert_eq!(root["count"], Value::Integer(-1000));
+        assert_eq!(root["plus"], Value::Integer(7));
+        assert_eq!(root["yes"], Value::Boolean(true));
+        assert_eq!(root["no"], Value::Boolean(false));
+        assert_eq!(
+            document["table"]["nested"],
+            Value::Array(vec![
+                Value::Array(vec![Value::Integer(1), Value::Integer(2)]),
+                Value::Array(vec![]),
+                Value::Array(vec![Value::String("three".into()), Value::Boolean(false)]),
+            ])
+        );
+        assert_eq!(document.len(), 2);
+    }
+
+    #[test]
+    fn empty_tables() {
+        let document = parse("\n[a]\n[b] # nothing\n").unwrap();
+        assert!(document[""].is_empty());
+        assert!(document["a"].is_empty());
+        assert!(document["b"].is_empty());
+    }
+
+    #[test]
+    fn written_twice() {
+        assert_eq!(error("a = 1\nb = 2\na = 3"), "line 3: key a written twice");
+        assert_eq!(
+            error("[t]\na = 1\n\n[u]\n[t]"),
+            "line 5: table [t] written twice"
+        );
+        // The same key in two tables is fine
+        assert!(parse("a = 1\n[t]\na = 2").is_ok());
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        assert_eq!(error("a = \"open\nb = 1"), "line 1: unterminated string");
+        assert_eq!(error("\n\na = \"\\x\""), "line 3: unknown escape sequence");
+        assert_eq!(error("a = [1, 2\n\n3]"), "line 3: expected , or ]");
+        assert_eq!(error("a = [1,\n  ="), "line 2: expected a value");
+        assert_eq!(error("a = 1 2"), "line 1: unexpected 2");
+        assert_eq!(error("a = tru"), "line 1: unknown value tru");
+        assert_eq!(error("a = -"), "line 1: invalid integer -");
+        assert_eq!(
+            error("a = 99999999999999999999"),
+            "line 1: invalid integer 99999999999999999999"
+        );
+        assert_eq!(error("a 1"), "line 1: expected =");
+        assert_eq!(error("[t\n"), "line 1: expected ]");
+        assert_eq!(error("= 1"), "line 1: expected a key");
+        assert_eq!(error("a = 1.5"), "line 1: unexpected .");
+    }
+}
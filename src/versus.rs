@@ -0,0 +1,158 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::fnv::Fnv1a;
+use crate::game_mode::GameMode;
+use crate::game_mode::GameSettings;
+use crate::tetris::Tetris;
+
+pub const PLAYERS: usize = 2;
+
+/// Two boards playing against each other, each attack being sent as garbage to the opponent
+pub struct Versus {
+    players: [Tetris; PLAYERS],
+    settings: GameSettings,
+    wins: [usize; PLAYERS],
+    best_of: usize,
+    round_winner: Option<usize>,
+    round: usize,     // Rounds started before this one
+    rng: ChaCha12Rng, // Seeds of the boards of each round
+}
+
+impl Versus {
+    pub fn new(best_of: usize, settings: GameSettings) -> Self {
+        Self::with_seed(best_of, settings, rand::rng().random())
+    }
+
+    /// Two matches with the same seed and the same inputs play out the same way
+    pub fn with_seed(best_of: usize, settings: GameSettings, seed: u64) -> Self {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        Self {
+            players: [
+                Self::new_board(settings, rng.random()),
+                Self::new_board(settings, rng.random()),
+            ],
+            settings,
+            wins: [0; PLAYERS],
+            best_of: best_of.max(1),
+            round_winner: None,
+            round: 0,
+            rng,
+        }
+    }
+
+    /// Match looking like the one of a player, only meant to be drawn
+    pub fn from_snapshots(
+        players: [Tetris; PLAYERS],
+        wins: [usize; PLAYERS],
+        best_of: usize,
+        round_winner: Option<usize>,
+    ) -> Self {
+        Self {
+            players,
+            settings: GameSettings::default(),
+            wins,
+            best_of: best_of.max(1),
+            round_winner,
+            round: 0,
+            rng: ChaCha12Rng::seed_from_u64(0),
+        }
+    }
+
+    fn new_board(settings: GameSettings, seed: u64) -> Tetris {
+        let mut board = Tetris::with_seed(GameMode::Versus, seed);
+        board.apply_settings(settings);
+        board
+    }
+
+    pub fn on_tick(&mut self) {
+        if self.round_winner.is_some() {
+            return;
+        }
+        for player in self.players.iter_mut() {
+            player.on_tick();
+        }
+        self.exchange_garbage();
+        self.check_round();
+    }
+
+    /// Send what each player attacked to the other one
+    fn exchange_garbage(&mut self) {
+        let attacks = self
+            .players
+            .each_mut()
+            .map(|player| player.take_outgoing_attack());
+        for (id, attack) in attacks.into_iter().enumerate() {
+            self.players[(id + 1) % PLAYERS].queue_garbage(attack);
+        }
+    }
+
+    fn check_round(&mut self) {
+        let lost: Vec<usize> = (0..PLAYERS)
+            .filter(|id| self.players[*id].is_lost())
+            .collect();
+        // If both top out on the same tick, the round goes to nobody
+        if lost.len() == 1 {
+            let winner = (lost[0] + 1) % PLAYERS;
+            self.wins[winner] += 1;
+            self.round_winner = Some(winner);
+        } else if lost.len() == PLAYERS {
+            self.next_round();
+        }
+    }
+
+    pub fn next_round(&mut self) {
+        if self.is_over() {
+            return;
+        }
+        self.players = [
+            Self::new_board(self.settings, self.rng.random()),
+            Self::new_board(self.settings, self.rng.random()),
+        ];
+        self.round_winner = None;
+        self.round += 1;
+    }
+
+    pub fn player(&self, id: usize) -> &Tetris {
+        &self.players[id]
+    }
+
+    pub fn player_mut(&mut self, id: usize) -> &mut Tetris {
+        &mut self.players[id]
+    }
+
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        for player in &self.players {
+            hasher.write_u64(player.state_hash());
+        }
+        for count in self.wins.into_iter().chain([self.round]) {
+            hasher.write_u64(count as u64);
+        }
+        hasher.finish()
+    }
+
+    pub fn wins(&self) -> [usize; PLAYERS] {
+        self.wins
+    }
+
+    pub fn best_of(&self) -> usize {
+        self.best_of
+    }
+
+    /// Rounds started before the current one, a round both players lose being started again
+    #[cfg(test)]
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    /// Winner of the round that just ended, `None` while the round is being played
+    pub fn round_winner(&self) -> Option<usize> {
+        self.round_winner
+    }
+
+    /// A player won the majority of the rounds
+    pub fn is_over(&self) -> bool {
+        self.wins.iter().any(|wins| *wins > self.best_of / 2)
+    }
+}